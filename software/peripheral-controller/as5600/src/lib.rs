@@ -0,0 +1,230 @@
+#![no_std]
+
+//! AS5600 magnetic angle sensor, an absolute encoder of a single revolution.
+//! The angle is unwrapped into a multi-turn position, which is exact as long as
+//! the shaft turns by less than half a revolution between two updates.
+
+mod register;
+
+use crate::register::Register;
+
+use core::fmt::Debug;
+
+use embedded_hal as hal;
+use hal::blocking::i2c::{Write, WriteRead};
+
+use units::{Time, Angle, AngularVelocity};
+use encoder::{Update, GetPosition, GetVelocity};
+use rotary_encoder::velocity::{VelocityEstimator, Difference};
+
+pub const ADDRESS: u8 = 0x36;
+
+/// Counts per revolution of the 12 bit angle
+pub const RESOLUTION: u16 = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MagnetStatus {
+    pub detected: bool,
+    /// The AGC is at its maximum gain
+    pub too_weak: bool,
+    /// The AGC is at its minimum gain
+    pub too_strong: bool
+}
+
+impl MagnetStatus {
+    fn from_bits(status: u8) -> Self {
+        Self {
+            detected: status & 1 << 5 != 0,
+            too_weak: status & 1 << 4 != 0,
+            too_strong: status & 1 << 3 != 0
+        }
+    }
+
+    /// Detected and in range, so that the angle can be trusted
+    pub fn is_ok(&self) -> bool {
+        self.detected && !self.too_weak && !self.too_strong
+    }
+}
+
+pub struct As5600<I2C, V = Difference>
+where
+    V: VelocityEstimator
+{
+    i2c: I2C,
+    velocity_estimator: V,
+
+    /// The position grows as the raw angle decreases
+    pub reverse: bool,
+    /// Raw angle taken as zero
+    pub zero: u16,
+
+    count: i64,
+    last_raw_angle: u16,
+    // time of the updates that failed to read the angle since the last good one
+    pending_time: Time,
+    errors: u32
+}
+
+impl<I2C, E> As5600<I2C>
+where
+    I2C: WriteRead<Error = E> + Write<Error = E>,
+    E: Debug,
+{
+    /// Reads the initial angle, the position starts within the first revolution
+    pub fn new(i2c: I2C, reverse: bool, zero: u16) -> Result<Self, E> {
+        let mut as5600 = Self {
+            i2c,
+            velocity_estimator: Difference::default(),
+
+            reverse,
+            zero,
+
+            count: 0,
+            last_raw_angle: 0,
+            pending_time: Time::ZERO,
+            errors: 0
+        };
+
+        let raw_angle = as5600.read_raw_angle()?;
+        as5600.last_raw_angle = raw_angle;
+        as5600.count = as5600.angle_from_zero(raw_angle) as i64;
+        as5600.velocity_estimator.offset(as5600.get_revolutions());
+
+        Ok(as5600)
+    }
+}
+
+impl<I2C, E, V> As5600<I2C, V>
+where
+    I2C: WriteRead<Error = E> + Write<Error = E>,
+    E: Debug,
+    V: VelocityEstimator
+{
+    /// Replaces the difference of the positions of the last two updates
+    pub fn with_velocity_estimator<W: VelocityEstimator>(self, mut velocity_estimator: W) -> As5600<I2C, W> {
+        velocity_estimator.offset(self.get_revolutions());
+
+        As5600 {
+            i2c: self.i2c,
+            velocity_estimator,
+
+            reverse: self.reverse,
+            zero: self.zero,
+
+            count: self.count,
+            last_raw_angle: self.last_raw_angle,
+            pending_time: self.pending_time,
+            errors: self.errors
+        }
+    }
+
+    /// 12 bit angle, before the zero and the direction
+    pub fn read_raw_angle(&mut self) -> Result<u16, E> {
+        Ok(self.read_register_u16(Register::RAW_ANGLE_H)? & 0x0FFF)
+    }
+
+    pub fn read_magnet_status(&mut self) -> Result<MagnetStatus, E> {
+        Ok(MagnetStatus::from_bits(self.read_register_u8(Register::STATUS)?))
+    }
+
+    /// Gain of the automatic gain control, it is in the middle of its range at the ideal magnet distance
+    pub fn read_agc(&mut self) -> Result<u8, E> {
+        self.read_register_u8(Register::AGC)
+    }
+
+    /// 12 bit magnitude of the magnetic field
+    pub fn read_magnitude(&mut self) -> Result<u16, E> {
+        Ok(self.read_register_u16(Register::MAGNITUDE_H)? & 0x0FFF)
+    }
+
+    /// Multi-turn count, `RESOLUTION` per revolution
+    pub fn get_count(&self) -> i64 {
+        self.count
+    }
+
+    /// Updates that failed to read the angle, the position is kept then
+    pub fn get_errors(&self) -> u32 {
+        self.errors
+    }
+
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    fn angle_from_zero(&self, raw_angle: u16) -> u16 {
+        let angle = raw_angle.wrapping_sub(self.zero) % RESOLUTION;
+
+        match self.reverse {
+            true => (RESOLUTION - angle) % RESOLUTION,
+            false => angle
+        }
+    }
+
+    fn read_register_u8(&mut self, register: Register) -> Result<u8, E> {
+        let mut buffer = [0u8; 1];
+        self.i2c.write_read(ADDRESS, &[register.addr()], &mut buffer)?;
+        Ok(buffer[0])
+    }
+
+    fn read_register_u16(&mut self, register: Register) -> Result<u16, E> {
+        let mut buffer = [0u8; 2];
+        self.i2c.write_read(ADDRESS, &[register.addr()], &mut buffer)?;
+        Ok(u16::from_be_bytes(buffer))
+    }
+}
+
+impl<I2C, V: VelocityEstimator> As5600<I2C, V> {
+    fn get_revolutions(&self) -> f32 {
+        self.count as f32 / RESOLUTION as f32
+    }
+}
+
+impl<I2C, E, V> Update for As5600<I2C, V>
+where
+    I2C: WriteRead<Error = E> + Write<Error = E>,
+    E: Debug,
+    V: VelocityEstimator
+{
+    fn update(&mut self, time_delta: Time) {
+        self.pending_time += time_delta;
+
+        let raw_angle = match self.read_raw_angle() {
+            Ok(raw_angle) => raw_angle,
+            Err(_) => {
+                self.errors = self.errors.wrapping_add(1);
+                return;
+            }
+        };
+
+        // the shorter way around, within half a revolution
+        let half = (RESOLUTION / 2) as i64;
+        let delta = (raw_angle as i64 - self.last_raw_angle as i64 + half).rem_euclid(RESOLUTION as i64) - half;
+        self.last_raw_angle = raw_angle;
+
+        self.count += if self.reverse { -delta } else { delta };
+        self.velocity_estimator.update(self.get_revolutions(), self.pending_time.to_seconds());
+        self.pending_time = Time::ZERO;
+    }
+}
+
+impl<I2C, V> GetPosition for As5600<I2C, V>
+where
+    V: VelocityEstimator
+{
+    type Position = Angle;
+
+    fn get_position(&self) -> Self::Position {
+        Angle::from_revolutions(self.get_revolutions())
+    }
+}
+
+impl<I2C, V> GetVelocity for As5600<I2C, V>
+where
+    V: VelocityEstimator
+{
+    type Velocity = AngularVelocity;
+
+    fn get_velocity(&self) -> Self::Velocity {
+        AngularVelocity::from_rps(self.velocity_estimator.get_velocity())
+    }
+}
@@ -0,0 +1,16 @@
+#![allow(non_camel_case_types, clippy::upper_case_acronyms)]
+
+#[repr(u8)]
+pub enum Register {
+    STATUS = 0x0B,
+    RAW_ANGLE_H = 0x0C,
+    AGC = 0x1A,
+    MAGNITUDE_H = 0x1B,
+}
+
+impl Register {
+    /// Get register address
+    pub fn addr(self) -> u8 {
+        self as u8
+    }
+}
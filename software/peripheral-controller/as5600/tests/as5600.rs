@@ -0,0 +1,130 @@
+use std::{cell::RefCell, rc::Rc};
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+use as5600::{As5600, ADDRESS, MagnetStatus};
+use units::{Time, Angle};
+use encoder::{Update, GetPosition, GetVelocity};
+
+const TIME_DELTA: Time = Time::from_seconds(0.025);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BusError;
+
+/// Register file of the sensor, shared with the test
+#[derive(Default)]
+struct Registers {
+    bytes: [u8; 0x20],
+    failing: bool
+}
+
+#[derive(Clone, Default)]
+struct MockI2c(Rc<RefCell<Registers>>);
+
+impl MockI2c {
+    fn set_raw_angle(&self, angle: u16) {
+        self.0.borrow_mut().bytes[0x0C..0x0E].copy_from_slice(&angle.to_be_bytes());
+    }
+}
+
+impl Write for MockI2c {
+    type Error = BusError;
+
+    fn write(&mut self, _address: u8, _bytes: &[u8]) -> Result<(), Self::Error> {
+        Err(BusError)
+    }
+}
+
+impl WriteRead for MockI2c {
+    type Error = BusError;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let registers = self.0.borrow();
+        if registers.failing || address != ADDRESS || bytes.len() != 1 {
+            return Err(BusError);
+        }
+
+        // the address auto-increments
+        let start = bytes[0] as usize;
+        buffer.copy_from_slice(&registers.bytes[start..start + buffer.len()]);
+        Ok(())
+    }
+}
+
+#[test]
+fn reads_the_absolute_angle_at_start() {
+    let i2c = MockI2c::default();
+    i2c.set_raw_angle(1024);
+
+    let as5600 = As5600::new(i2c.clone(), false, 0).unwrap();
+    assert_eq!(as5600.get_position().to_revolutions(), 0.25);
+
+    // relative to the zero, in reverse
+    let as5600 = As5600::new(i2c, true, 2048).unwrap();
+    assert_eq!(as5600.get_position().to_revolutions(), 0.25);
+}
+
+#[test]
+fn unwraps_multiple_turns_both_ways() {
+    let i2c = MockI2c::default();
+    i2c.set_raw_angle(4000);
+    let mut as5600 = As5600::new(i2c.clone(), false, 0).unwrap();
+
+    // 1.5 revolutions per second, 153.6 counts per update
+    let mut angle = 4000.0;
+    for _ in 0..100 {
+        angle += 153.6;
+        i2c.set_raw_angle((angle as u32 % 4096) as u16);
+        as5600.update(TIME_DELTA);
+    }
+    assert_eq!(as5600.get_count(), angle as i64);
+    assert!((as5600.get_velocity().to_rps() - 1.5).abs() < 0.01, "{:?}", as5600.get_velocity());
+
+    for _ in 0..300 {
+        angle -= 153.6;
+        i2c.set_raw_angle((angle as i64).rem_euclid(4096) as u16);
+        as5600.update(TIME_DELTA);
+    }
+    assert_eq!(as5600.get_count(), angle as i64);
+    assert!(as5600.get_position() < Angle::from_revolutions(-5.0));
+}
+
+#[test]
+fn bus_errors_keep_the_position() {
+    let i2c = MockI2c::default();
+    i2c.set_raw_angle(0);
+    let mut as5600 = As5600::new(i2c.clone(), false, 0).unwrap();
+
+    i2c.0.borrow_mut().failing = true;
+    i2c.set_raw_angle(400);
+    as5600.update(TIME_DELTA);
+    assert_eq!(as5600.get_position(), Angle::ZERO);
+    assert_eq!(as5600.get_errors(), 1);
+
+    // the movement is spread over both updates
+    i2c.0.borrow_mut().failing = false;
+    as5600.update(TIME_DELTA);
+    assert_eq!(as5600.get_count(), 400);
+    assert!((as5600.get_velocity().to_rps() - 400.0 / 4096.0 / 0.05).abs() < 1e-3, "{:?}", as5600.get_velocity());
+}
+
+#[test]
+fn reads_the_magnet_status_and_gain() {
+    let i2c = MockI2c::default();
+    {
+        let mut registers = i2c.0.borrow_mut();
+        registers.bytes[0x0B] = 0b0010_0000;
+        registers.bytes[0x1A] = 128;
+        registers.bytes[0x1B..0x1D].copy_from_slice(&0x0ABC_u16.to_be_bytes());
+    }
+    let mut as5600 = As5600::new(i2c.clone(), false, 0).unwrap();
+
+    let status = as5600.read_magnet_status().unwrap();
+    assert_eq!(status, MagnetStatus { detected: true, too_weak: false, too_strong: false });
+    assert!(status.is_ok());
+    assert_eq!(as5600.read_agc().unwrap(), 128);
+    assert_eq!(as5600.read_magnitude().unwrap(), 0x0ABC);
+
+    i2c.0.borrow_mut().bytes[0x0B] = 0b0011_0000;
+    assert!(!as5600.read_magnet_status().unwrap().is_ok());
+}
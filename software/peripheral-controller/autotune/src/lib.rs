@@ -0,0 +1,268 @@
+#![no_std]
+
+//! Relay feedback auto-tuning (Åström–Hägglund). The loop output switches between two
+//! values around a bias whenever the measurement crosses the setpoint, which makes the loop
+//! oscillate at its ultimate period. The ultimate gain follows from the relay amplitude and
+//! the amplitude of the oscillation, and a tuning rule turns both into PID gains.
+
+pub mod rule;
+
+use core::{ops::Add, fmt::Display};
+
+use num_traits::{NumCast, ToPrimitive, bounds::Bounded};
+
+use units::{Time, Velocity};
+use motor::{SetSpeed, GetSpeed};
+use encoder::{Encoder, Update};
+use servo::{Servo, SetVelocity, Stop};
+use wheel::Wheel;
+
+pub use crate::rule::{UltimateGain, TuningRule, PidGains};
+
+/// A loop the relay can drive, in the units its PID works in
+pub trait RelayPlant: Update {
+    /// Measurement as the PID of the loop sees it
+    fn get_measurement(&mut self) -> f32;
+    /// Loop output, bypassing the PID
+    fn set_output(&mut self, output: f32);
+    /// Hands the loop back to its PID, towards a standstill
+    fn release(&mut self);
+}
+
+/// Speed loop, the measurement is in percent of the maximum speed and the output is the duty
+impl<S, E> RelayPlant for Wheel<S, E>
+where
+    S: SetSpeed + GetSpeed,
+    <S as SetSpeed>::Speed: NumCast + Bounded,
+    <S as GetSpeed>::Speed: NumCast + Add + Copy,
+    <<S as GetSpeed>::Speed as Add>::Output: ToPrimitive + Display,
+    E: Encoder
+{
+    fn get_measurement(&mut self) -> f32 {
+        self.get_speed() / self.max_speed * 100.0
+    }
+
+    fn set_output(&mut self, output: f32) {
+        self.set_duty(output);
+    }
+
+    fn release(&mut self) {
+        self.set_speed(Velocity::ZERO);
+    }
+}
+
+/// Position loop, the measurement is in fractions of the maximum position and the output
+/// in fractions of the maximum speed of the wheel
+impl<S, E> RelayPlant for Servo<S, E>
+where
+    S: SetSpeed + GetSpeed,
+    <S as SetSpeed>::Speed: NumCast + Bounded,
+    <S as GetSpeed>::Speed: NumCast + Add + Copy,
+    <<S as GetSpeed>::Speed as Add>::Output: ToPrimitive + Display,
+    E: Encoder
+{
+    fn get_measurement(&mut self) -> f32 {
+        self.get_position() / self.get_max_position()
+    }
+
+    fn set_output(&mut self, output: f32) {
+        let speed = self.get_wheel().max_speed * output;
+        self.set_velocity(speed);
+    }
+
+    fn release(&mut self) {
+        self.stop();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutotuneState {
+    Idle,
+    Running,
+    Done(UltimateGain),
+    /// The oscillation did not settle in time
+    TimedOut,
+    /// The oscillation is within the hysteresis, the relay amplitude is too small
+    NoOscillation
+}
+
+pub struct RelayAutotune {
+    /// Output swing around the bias
+    pub amplitude: f32,
+    /// Error band the relay does not switch within, it has to be above the measurement noise
+    pub hysteresis: f32,
+    /// Cycles skipped while the oscillation builds up
+    pub settle_cycles: u8,
+    /// Cycles the amplitude and the period are averaged over
+    pub cycles: u8,
+    pub timeout: Time,
+
+    setpoint: f32,
+    bias: f32,
+    state: AutotuneState,
+
+    high: bool,
+    elapsed: Time,
+    last_rise: Option<Time>,
+    last_fall: Time,
+    min: f32,
+    max: f32,
+
+    cycle: u8,
+    period_sum: f32,
+    amplitude_sum: f32
+}
+
+impl RelayAutotune {
+    pub fn new(amplitude: f32, hysteresis: f32, timeout: Time) -> Self {
+        Self {
+            amplitude,
+            hysteresis,
+            settle_cycles: 2,
+            cycles: 4,
+            timeout,
+
+            setpoint: 0.0,
+            bias: 0.0,
+            state: AutotuneState::Idle,
+
+            high: true,
+            elapsed: Time::ZERO,
+            last_rise: None,
+            last_fall: Time::ZERO,
+            min: f32::MAX,
+            max: f32::MIN,
+
+            cycle: 0,
+            period_sum: 0.0,
+            amplitude_sum: 0.0
+        }
+    }
+
+    pub fn get_state(&self) -> AutotuneState {
+        self.state
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state == AutotuneState::Running
+    }
+
+    /// Oscillates the loop around the setpoint, the bias is the output expected to hold it
+    /// there, and is corrected after every cycle
+    pub fn start(&mut self, setpoint: f32, bias: f32) {
+        assert!(self.cycles > 0);
+
+        self.setpoint = setpoint;
+        self.bias = bias;
+        self.state = AutotuneState::Running;
+
+        self.high = true;
+        self.elapsed = Time::ZERO;
+        self.last_rise = None;
+        self.last_fall = Time::ZERO;
+        self.reset_extremes();
+
+        self.cycle = 0;
+        self.period_sum = 0.0;
+        self.amplitude_sum = 0.0;
+    }
+
+    /// Stops driving the loop, without a result
+    pub fn cancel<P: RelayPlant>(&mut self, plant: &mut P) {
+        if self.is_running() {
+            self.state = AutotuneState::Idle;
+            plant.release();
+        }
+    }
+
+    /// Updates the plant, then drives it with the relay while running
+    pub fn update<P: RelayPlant>(&mut self, plant: &mut P, time_delta: Time) {
+        plant.update(time_delta);
+        if !self.is_running() {
+            return;
+        }
+
+        let measurement = plant.get_measurement();
+        match self.next_output(measurement, time_delta) {
+            Some(output) => plant.set_output(output),
+            None => plant.release()
+        }
+    }
+
+    /// Relay output for the measurement, `None` once it is no longer running
+    pub fn next_output(&mut self, measurement: f32, time_delta: Time) -> Option<f32> {
+        if !self.is_running() {
+            return None;
+        }
+
+        self.elapsed += time_delta;
+        if self.elapsed > self.timeout {
+            self.state = AutotuneState::TimedOut;
+            return None;
+        }
+
+        self.min = self.min.min(measurement);
+        self.max = self.max.max(measurement);
+
+        let error = self.setpoint - measurement;
+        if !self.high && error > self.hysteresis {
+            self.high = true;
+            self.on_rise();
+        } else if self.high && error < -self.hysteresis {
+            self.high = false;
+            self.last_fall = self.elapsed;
+        }
+
+        match self.state {
+            AutotuneState::Running => Some(self.bias + if self.high { self.amplitude } else { -self.amplitude }),
+            _ => None
+        }
+    }
+
+    /// A cycle ends when the output switches high again
+    fn on_rise(&mut self) {
+        let last_rise = match self.last_rise.replace(self.elapsed) {
+            Some(last_rise) => last_rise,
+            None => {
+                self.reset_extremes();
+                return;
+            }
+        };
+
+        let period = (self.elapsed - last_rise).to_seconds();
+        let high_time = (self.last_fall - last_rise).to_seconds();
+        let amplitude = (self.max - self.min) / 2.0;
+        self.reset_extremes();
+
+        // an asymmetric oscillation means that the bias does not hold the setpoint
+        self.bias += self.amplitude * (2.0 * high_time - period) / period;
+
+        self.cycle += 1;
+        if self.cycle <= self.settle_cycles {
+            return;
+        }
+
+        self.period_sum += period;
+        self.amplitude_sum += amplitude;
+
+        let measured = self.cycle - self.settle_cycles;
+        if measured < self.cycles {
+            return;
+        }
+
+        let amplitude = self.amplitude_sum / measured as f32;
+        if amplitude <= self.hysteresis {
+            self.state = AutotuneState::NoOscillation;
+            return;
+        }
+
+        // describing function of a relay with hysteresis
+        let ku = 4.0 * self.amplitude / (core::f32::consts::PI * libm::sqrtf(amplitude * amplitude - self.hysteresis * self.hysteresis));
+        self.state = AutotuneState::Done(UltimateGain { ku, tu: self.period_sum / measured as f32 });
+    }
+
+    fn reset_extremes(&mut self) {
+        self.min = f32::MAX;
+        self.max = f32::MIN;
+    }
+}
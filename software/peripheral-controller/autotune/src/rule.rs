@@ -0,0 +1,59 @@
+/// Ultimate gain and period of a loop, where a proportional controller would keep it oscillating
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UltimateGain {
+    pub ku: f32,
+    /// Seconds
+    pub tu: f32
+}
+
+/// Rules for the gains from the ultimate gain and period. All but Tyreus-Luyben assume a loop
+/// that settles by itself, like the wheel speed. A position loop integrates, with their short
+/// integral times it oscillates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TuningRule {
+    ZieglerNichols,
+    /// Without the derivative term
+    ZieglerNicholsPi,
+    SomeOvershoot,
+    NoOvershoot,
+    /// Long integral time, for integrating loops like the servo position
+    TyreusLuyben,
+}
+
+/// Parallel PID gains, `kp * e + ki * ∫e dt + kd * de/dt`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidGains {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32
+}
+
+impl UltimateGain {
+    pub fn gains(&self, rule: TuningRule) -> PidGains {
+        // proportional gain, integral and derivative times in periods
+        let (kp, ti, td) = match rule {
+            TuningRule::ZieglerNichols => (0.6, 0.5, 0.125),
+            TuningRule::ZieglerNicholsPi => (0.45, 1.0 / 1.2, 0.0),
+            TuningRule::SomeOvershoot => (1.0 / 3.0, 0.5, 1.0 / 3.0),
+            TuningRule::NoOvershoot => (0.2, 0.5, 1.0 / 3.0),
+            TuningRule::TyreusLuyben => (1.0 / 2.2, 2.2, 1.0 / 6.3)
+        };
+        let kp = kp * self.ku;
+
+        PidGains {
+            kp,
+            ki: kp / (ti * self.tu),
+            kd: kp * td * self.tu
+        }
+    }
+}
+
+impl PidGains {
+    /// Gains of a `Pid` whose output is added to the loop output every period, like the ones
+    /// of `Wheel` and `Servo`, as `(kp, ki, kd)`. Such a `Pid` is the derivative of the parallel
+    /// one: its `kd` acts on the change of the error like a proportional term, its `kp` on the
+    /// error like an integral one. A derivative term would need a second difference, it is dropped
+    pub fn to_incremental(&self, time_delta_seconds: f32) -> (f32, f32, f32) {
+        (self.ki * time_delta_seconds, 0.0, self.kp)
+    }
+}
@@ -0,0 +1,106 @@
+use units::Time;
+use autotune::{RelayAutotune, AutotuneState, TuningRule, UltimateGain, PidGains};
+
+const TIME_DELTA: Time = Time::from_millis(1.0);
+
+/// Integrator with a dead time, `dy/dt = gain * (u(t - delay) + disturbance)`
+struct DelayedIntegrator {
+    gain: f32,
+    disturbance: f32,
+    delayed: [f32; 50],
+    next: usize,
+    measurement: f32
+}
+
+impl DelayedIntegrator {
+    fn new(gain: f32, disturbance: f32) -> Self {
+        Self { gain, disturbance, delayed: [0.0; 50], next: 0, measurement: 0.0 }
+    }
+
+    fn delay(&self) -> f32 {
+        self.delayed.len() as f32 * TIME_DELTA.to_seconds()
+    }
+
+    fn step(&mut self, output: f32) {
+        let applied = core::mem::replace(&mut self.delayed[self.next], output);
+        self.next = (self.next + 1) % self.delayed.len();
+
+        self.measurement += self.gain * (applied + self.disturbance) * TIME_DELTA.to_seconds();
+    }
+}
+
+fn run(autotune: &mut RelayAutotune, plant: &mut DelayedIntegrator) -> Vec<f32> {
+    let mut outputs = Vec::new();
+    while let Some(output) = autotune.next_output(plant.measurement, TIME_DELTA) {
+        plant.step(output);
+        outputs.push(output);
+    }
+    outputs
+}
+
+fn ultimate_gain(state: AutotuneState) -> UltimateGain {
+    match state {
+        AutotuneState::Done(ultimate_gain) => ultimate_gain,
+        state => panic!("{:?}", state)
+    }
+}
+
+#[test]
+fn measures_the_oscillation() {
+    let mut plant = DelayedIntegrator::new(2.0, 0.0);
+    let mut autotune = RelayAutotune::new(1.0, 0.0, Time::from_seconds(10.0));
+    autotune.start(0.0, 0.0);
+    run(&mut autotune, &mut plant);
+
+    // a triangle wave, the measurement keeps ramping for the dead time after every switch
+    let ultimate_gain = ultimate_gain(autotune.get_state());
+    let delay = plant.delay();
+    assert!((ultimate_gain.tu - 4.0 * delay).abs() < 0.01, "{:?}", ultimate_gain);
+    let amplitude = plant.gain * delay;
+    assert!((ultimate_gain.ku - 4.0 / (core::f32::consts::PI * amplitude)).abs() < 0.05 * ultimate_gain.ku, "{:?}", ultimate_gain);
+}
+
+#[test]
+fn corrects_the_bias() {
+    // a load the bias of 0 does not hold, the oscillation starts lopsided
+    let mut plant = DelayedIntegrator::new(2.0, -0.4);
+    let mut autotune = RelayAutotune::new(1.0, 0.01, Time::from_seconds(10.0));
+    autotune.start(0.0, 0.0);
+    let outputs = run(&mut autotune, &mut plant);
+
+    // symmetric again, the measurement also has to ramp across the hysteresis
+    let ultimate_gain = ultimate_gain(autotune.get_state());
+    let tu = 4.0 * plant.delay() + 4.0 * autotune.hysteresis / plant.gain;
+    assert!((ultimate_gain.tu - tu).abs() < 0.005, "{:?}", ultimate_gain);
+    // the relay ends up switching around the load
+    let last_high = outputs.iter().rev().find(|&&output| output > 0.0).unwrap();
+    assert!((last_high - 1.4).abs() < 0.05, "{}", last_high);
+}
+
+#[test]
+fn times_out() {
+    // the relay cannot overcome the load
+    let mut plant = DelayedIntegrator::new(2.0, -3.0);
+    let mut autotune = RelayAutotune::new(1.0, 0.01, Time::from_seconds(1.0));
+    autotune.start(0.0, 0.0);
+    let outputs = run(&mut autotune, &mut plant);
+
+    assert_eq!(autotune.get_state(), AutotuneState::TimedOut);
+    assert!((outputs.len() as i32 - 1000).abs() <= 1, "{}", outputs.len());
+}
+
+#[test]
+fn rules_scale_the_ultimate_gain() {
+    let ultimate_gain = UltimateGain { ku: 2.0, tu: 0.5 };
+
+    let gains = ultimate_gain.gains(TuningRule::ZieglerNichols);
+    assert!((gains.kp - 1.2).abs() < 1e-6 && (gains.ki - 4.8).abs() < 1e-5 && (gains.kd - 0.075).abs() < 1e-6, "{:?}", gains);
+
+    let gains = ultimate_gain.gains(TuningRule::ZieglerNicholsPi);
+    assert_eq!(gains.kd, 0.0);
+    assert!((gains.ki - 0.9 * 1.2 / 0.5).abs() < 1e-5, "{:?}", gains);
+
+    // the incremental form swaps the proportional and integral gains, per period
+    let (kp, ki, kd) = PidGains { kp: 1.2, ki: 4.8, kd: 0.075 }.to_incremental(0.025);
+    assert!((kp - 0.12).abs() < 1e-6 && ki == 0.0 && kd == 1.2, "{} {} {}", kp, ki, kd);
+}
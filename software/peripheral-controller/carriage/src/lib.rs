@@ -0,0 +1,271 @@
+#![no_std]
+#![feature(trait_alias)]
+
+//! The drawer assembly slides sideways on a rack and pinion, so that the drawer
+//! of the selected bin is centred under the drop zone.
+
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::InputPin;
+
+use units::{Length, Velocity, Time};
+use encoder::{Update, GetPosition};
+use servo::{SetPosition, SetVelocity, Stop, CheckTargetReached, TargetState};
+
+/// An axis that moves the carriage, a `Servo` or a `Stepper`
+pub trait CarriageAxis = SetPosition<Position = Length> + GetPosition<Position = Length> + SetVelocity<Velocity = Velocity> + Stop + CheckTargetReached + Update;
+
+/// Placeholder for carriages without a home switch, which are homed where they stand
+pub enum NoSwitch {}
+
+impl InputPin for NoSwitch {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        match *self {}
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        match *self {}
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarriageState {
+    Idle,
+    /// Searching for the home switch, then returning to it
+    Homing,
+    Moving { bin: u8 },
+    AtBin { bin: u8 },
+    /// Interrupted by `stop` or `halt`
+    Stopped,
+    /// The home switch or the bin was not reached
+    Failed(TargetState)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarriageError {
+    InvalidBin,
+    /// The bin positions are unknown until the carriage is homed
+    NotHomed,
+    /// The carriage is homing
+    Busy
+}
+
+pub struct Carriage<A, SW = NoSwitch, const N: usize = 3>
+where
+    A: CarriageAxis,
+    SW: InputPin
+{
+    axis: A,
+    home_switch: Option<SW>,
+
+    /// Axis positions at which the bins are under the drop zone, relative to the home switch
+    pub bin_positions: [Length; N],
+    /// Signed axis velocity the home switch is searched with
+    pub homing_velocity: Velocity,
+    /// Time the home switch must be reached within
+    pub homing_timeout: Time,
+
+    home: Length,
+    homed: bool,
+    state: CarriageState,
+    elapsed: Time
+}
+
+impl<A, const N: usize> Carriage<A, NoSwitch, N>
+where
+    A: CarriageAxis
+{
+    /// The carriage is homed at the current position of the axis
+    pub fn new(axis: A, bin_positions: [Length; N]) -> Self {
+        let home = axis.get_position();
+
+        Self {
+            axis,
+            home_switch: None,
+
+            bin_positions,
+            homing_velocity: Velocity::ZERO,
+            homing_timeout: Time::ZERO,
+
+            home,
+            homed: true,
+            state: CarriageState::Idle,
+            elapsed: Time::ZERO
+        }
+    }
+}
+
+impl<A, SW, const N: usize> Carriage<A, SW, N>
+where
+    A: CarriageAxis,
+    SW: InputPin
+{
+    /// The switch reads high while the carriage is at home, bins can only be selected after `home`
+    pub fn with_home_switch<S: InputPin>(self, home_switch: S, homing_velocity: Velocity, homing_timeout: Time) -> Carriage<A, S, N> {
+        Carriage {
+            axis: self.axis,
+            home_switch: Some(home_switch),
+
+            bin_positions: self.bin_positions,
+            homing_velocity,
+            homing_timeout,
+
+            home: self.home,
+            homed: false,
+            state: CarriageState::Idle,
+            elapsed: Time::ZERO
+        }
+    }
+
+    pub fn get_axis(&self) -> &A {
+        &self.axis
+    }
+
+    /// E.g. to `poll` a stepper
+    pub fn get_axis_mut(&mut self) -> &mut A {
+        &mut self.axis
+    }
+
+    pub fn get_state(&self) -> CarriageState {
+        self.state
+    }
+
+    pub fn is_homed(&self) -> bool {
+        self.homed
+    }
+
+    /// The bin under the drop zone, once the carriage has stopped there
+    pub fn get_bin(&self) -> Option<u8> {
+        match self.state {
+            CarriageState::AtBin { bin } => Some(bin),
+            _ => None
+        }
+    }
+
+    /// Position of the carriage relative to the home switch
+    pub fn get_position(&self) -> Length {
+        self.axis.get_position() - self.home
+    }
+
+    /// Drives towards the home switch, without a switch the current position becomes home
+    pub fn home(&mut self) {
+        if self.home_switch.is_none() {
+            self.home = self.axis.get_position();
+            self.homed = true;
+            self.state = CarriageState::Idle;
+            return;
+        }
+
+        self.homed = false;
+        self.elapsed = Time::ZERO;
+        self.state = CarriageState::Homing;
+        self.axis.set_velocity(self.homing_velocity);
+    }
+
+    /// Starts moving the bin under the drop zone, the carriage is `AtBin` once it has settled there
+    pub fn select_bin(&mut self, bin: u8) -> Result<(), CarriageError> {
+        if bin as usize >= N {
+            return Err(CarriageError::InvalidBin);
+        }
+        if self.state == CarriageState::Homing {
+            return Err(CarriageError::Busy);
+        }
+        if !self.homed {
+            return Err(CarriageError::NotHomed);
+        }
+
+        self.axis.set_position(self.home + self.bin_positions[bin as usize]);
+        self.state = CarriageState::Moving { bin };
+
+        Ok(())
+    }
+
+    fn is_at_home_switch(&self) -> Result<bool, SW::Error> {
+        match self.home_switch {
+            Some(ref home_switch) => home_switch.is_high(),
+            None => Ok(false)
+        }
+    }
+
+    fn interrupt(&mut self) {
+        match self.state {
+            CarriageState::Homing | CarriageState::Moving { .. } => {
+                self.state = CarriageState::Stopped;
+            },
+            _ => {}
+        }
+    }
+}
+
+impl<A, SW, const N: usize> Update for Carriage<A, SW, N>
+where
+    A: CarriageAxis,
+    SW: InputPin
+{
+    fn update(&mut self, time_delta: Time) {
+        self.axis.update(time_delta);
+
+        match self.state {
+            CarriageState::Homing if !self.homed => {
+                self.elapsed += time_delta;
+
+                match self.is_at_home_switch() {
+                    Ok(true) => {
+                        // the switch position is taken as home, the axis returns to it after the overshoot
+                        self.home = self.axis.get_position();
+                        self.homed = true;
+                        self.axis.set_position(self.home);
+                    },
+                    Ok(false) if self.elapsed < self.homing_timeout => {},
+                    _ => {
+                        self.axis.stop();
+                        self.state = CarriageState::Failed(TargetState::TimedOut);
+                    }
+                }
+            },
+            CarriageState::Homing | CarriageState::Moving { .. } => {
+                self.state = match (self.state, self.axis.get_target_state()) {
+                    (_, TargetState::Moving) => return,
+                    (CarriageState::Moving { bin }, TargetState::Reached) => CarriageState::AtBin { bin },
+                    (_, TargetState::Reached) => CarriageState::Idle,
+                    (_, failure) => CarriageState::Failed(failure)
+                };
+            },
+            _ => {}
+        }
+    }
+}
+
+impl<A, SW, const N: usize> CheckTargetReached for Carriage<A, SW, N>
+where
+    A: CarriageAxis,
+    SW: InputPin
+{
+    fn get_target_state(&self) -> TargetState {
+        match self.state {
+            CarriageState::Homing | CarriageState::Moving { .. } => TargetState::Moving,
+            CarriageState::Failed(failure) => failure,
+            _ => TargetState::Reached
+        }
+    }
+}
+
+impl<A, SW, const N: usize> Stop for Carriage<A, SW, N>
+where
+    A: CarriageAxis,
+    SW: InputPin
+{
+    /// Decelerates wherever the carriage is, a carriage stopped before the home switch has to be homed again
+    fn stop(&mut self) {
+        self.interrupt();
+        self.axis.stop();
+    }
+
+    /// Cuts the axis at once, a stepper may lose steps, so it is better homed again
+    fn halt(&mut self) {
+        self.interrupt();
+        self.axis.halt();
+    }
+}
@@ -0,0 +1,119 @@
+use std::{cell::Cell, convert::Infallible, rc::Rc};
+
+use embedded_hal::digital::v2::InputPin;
+
+use units::{Length, Time};
+use encoder::{Update, GetPosition};
+use servo::{Stop, CheckTargetReached, TargetState};
+use carriage::{Carriage, CarriageState, CarriageError};
+use test_support::{MockAxis, TIME_DELTA, SPEED};
+
+const BINS: [Length; 3] = [Length::ZERO, Length::from_cm(15.0), Length::from_cm(30.0)];
+
+/// Pressed at and below the given position
+struct HomeSwitch(Rc<Cell<Length>>, Length);
+
+impl InputPin for HomeSwitch {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.0.get() <= self.1)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+fn assert_near(position: Length, expected: Length) {
+    assert!((position - expected).abs() < Length::from_mm(0.01), "{:?} {:?}", position, expected);
+}
+
+fn run<T: Update + CheckTargetReached>(carriage: &mut T, seconds: f32) {
+    for _ in 0..(seconds / TIME_DELTA.to_seconds()) as usize {
+        carriage.update(TIME_DELTA);
+    }
+}
+
+#[test]
+fn selects_calibrated_bins() {
+    let mut carriage = Carriage::new(MockAxis::new(Length::from_cm(5.0)), BINS);
+
+    assert_eq!(carriage.select_bin(3), Err(CarriageError::InvalidBin));
+
+    carriage.select_bin(2).unwrap();
+    assert_eq!(carriage.get_state(), CarriageState::Moving { bin: 2 });
+    assert_eq!(carriage.get_bin(), None);
+
+    run(&mut carriage, 2.5);
+    assert_eq!(carriage.get_state(), CarriageState::Moving { bin: 2 });
+
+    run(&mut carriage, 1.0);
+    assert_eq!(carriage.get_state(), CarriageState::AtBin { bin: 2 });
+    assert_eq!(carriage.get_bin(), Some(2));
+    // the positions are relative to where the carriage was created
+    assert_near(carriage.get_axis().get_position(), Length::from_cm(35.0));
+    assert_near(carriage.get_position(), Length::from_cm(30.0));
+
+    carriage.select_bin(1).unwrap();
+    run(&mut carriage, 2.0);
+    assert_eq!(carriage.get_bin(), Some(1));
+}
+
+#[test]
+fn homes_on_the_switch() {
+    let axis = MockAxis::new(Length::from_cm(20.0));
+    let position = axis.position.clone();
+    let bins = [2.0, 17.0, 32.0].map(Length::from_cm);
+    let mut carriage = Carriage::new(axis, bins)
+        .with_home_switch(HomeSwitch(position, Length::from_cm(-3.0)), -SPEED, Time::from_seconds(5.0));
+
+    assert!(!carriage.is_homed());
+    assert_eq!(carriage.select_bin(0), Err(CarriageError::NotHomed));
+
+    carriage.home();
+    assert_eq!(carriage.select_bin(0), Err(CarriageError::Busy));
+
+    run(&mut carriage, 3.0);
+    assert!(carriage.is_homed());
+    assert_eq!(carriage.get_state(), CarriageState::Idle);
+    assert_near(carriage.get_axis().get_position(), Length::from_cm(-3.0));
+
+    carriage.select_bin(1).unwrap();
+    run(&mut carriage, 2.0);
+    assert_eq!(carriage.get_bin(), Some(1));
+    assert_near(carriage.get_axis().get_position(), Length::from_cm(14.0));
+}
+
+#[test]
+fn missing_switch_times_out() {
+    let axis = MockAxis::new(Length::from_cm(20.0));
+    let position = axis.position.clone();
+    let mut carriage = Carriage::new(axis, BINS)
+        .with_home_switch(HomeSwitch(position, Length::from_m(-1.0)), -SPEED, Time::from_seconds(1.0));
+
+    carriage.home();
+    run(&mut carriage, 1.5);
+    assert_eq!(carriage.get_state(), CarriageState::Failed(TargetState::TimedOut));
+    assert!(!carriage.is_homed());
+    assert_eq!(carriage.select_bin(0), Err(CarriageError::NotHomed));
+}
+
+#[test]
+fn stop_and_axis_failure_are_reported() {
+    let mut carriage = Carriage::new(MockAxis::new(Length::ZERO), BINS);
+
+    carriage.select_bin(2).unwrap();
+    run(&mut carriage, 1.0);
+    carriage.stop();
+    assert_eq!(carriage.get_state(), CarriageState::Stopped);
+    run(&mut carriage, 1.0);
+    assert_eq!(carriage.get_state(), CarriageState::Stopped);
+    assert!(carriage.is_target_reached());
+
+    carriage.select_bin(2).unwrap();
+    carriage.get_axis_mut().stuck = true;
+    run(&mut carriage, 0.1);
+    assert_eq!(carriage.get_state(), CarriageState::Failed(TargetState::Stalled));
+    assert_eq!(carriage.get_target_state(), TargetState::Stalled);
+}
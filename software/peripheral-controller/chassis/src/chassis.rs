@@ -1,47 +1,106 @@
+use units::{Length, Angle, Time, Velocity, AngularVelocity, Acceleration, AngularAcceleration};
 use motor::SetSpeed;
-use encoder::{GetPosition, Update};
-use servo::{SetPosition, CheckTargetReached};
+use encoder::{GetPosition, GetVelocity, Update};
+use servo::{SetPosition, SetVelocity, Stop, CheckTargetReached, TargetState};
+use gyro::ReadYawRate;
 
-pub trait ChassisMotor = SetSpeed + GetPosition + SetPosition + CheckTargetReached + Update;
+use crate::heading::{HeadingEstimator, NoGyro};
 
-#[derive(Debug, Default)]
+pub trait ChassisMotor = SetSpeed<Speed = Velocity> + GetPosition<Position = Length> + SetPosition<Position = Length>
+    + SetVelocity<Velocity = Velocity> + Stop + CheckTargetReached + Update;
+
+/// Angular speed grows clockwise
+#[derive(Debug, Default, Clone, Copy)]
 pub struct ChassisSpeed {
-    pub linear: f32,
-    pub angular: f32
+    pub linear: Velocity,
+    pub angular: AngularVelocity
+}
+
+impl ChassisSpeed {
+    pub fn is_zero(&self) -> bool {
+        self.linear == Velocity::ZERO && self.angular == AngularVelocity::ZERO
+    }
+}
+
+/// Limits of the velocity (teleop) mode
+#[derive(Debug, Clone, Copy)]
+pub struct VelocityLimits {
+    pub linear_acceleration: Acceleration,
+    pub angular_acceleration: AngularAcceleration,
+    /// The robot stops if no new velocity is set for this long
+    pub command_timeout: Time
+}
+
+impl Default for VelocityLimits {
+    /// No ramping, half a second timeout
+    fn default() -> Self {
+        Self {
+            linear_acceleration: Acceleration::INFINITY,
+            angular_acceleration: AngularAcceleration::INFINITY,
+            command_timeout: Time::from_seconds(0.5)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct VelocityCommand {
+    target: ChassisSpeed,
+    // ramped towards the target
+    current: ChassisSpeed,
+    // since the last command
+    age: Time
 }
 
+/// Pose in the odometry frame: x is forward at start, y is to the right,
+/// heading grows clockwise (from x towards y)
 #[derive(Debug, Default, Clone, Copy)]
 pub struct ChassisPosition {
-    pub linear: (f32, f32),
-    pub angular: f32
+    pub linear: (Length, Length),
+    pub angular: Angle
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChassisState {
+    Idle,
+    /// Performing an atomic movement
+    Moving,
+    /// In the velocity mode
+    Driving,
+    /// Decelerating after `stop`
+    Stopping,
+    /// The motors are cut after `halt`
+    Halted
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum AtomicMovement {
-    Linear(f32),
-    Angular(f32)
+    Linear(Length),
+    Angular(Angle)
 }
 
 pub trait MoveAtomic {
     fn move_atomic(&mut self, movement: AtomicMovement);
 }
 
-struct ChassisMovement {
-    pub movement: AtomicMovement,
-    pub prev_pos: (f32, f32),
-}
-
-pub struct Chassis<L, R>
+pub struct Chassis<L, R, G = NoGyro>
 where
     L: ChassisMotor,
-    R: ChassisMotor
+    R: ChassisMotor,
+    G: ReadYawRate
 {
-    wheels_distance: f32,
+    wheels_distance: Length,
 
     speed: ChassisSpeed,
+    pub velocity_limits: VelocityLimits,
 
-    current_movement: Option<ChassisMovement>,
+    current_movement: Option<AtomicMovement>,
+    velocity: Option<VelocityCommand>,
+    stopping: bool,
+    halted: bool,
+    measured_speed: ChassisSpeed,
     position: ChassisPosition,
+    prev_wheel_positions: (Length, Length),
+    heading_estimator: Option<HeadingEstimator<G>>,
 
     left: L,
     right: R,
@@ -50,84 +109,310 @@ where
 impl<L, R> Chassis<L, R> 
 where
     L: ChassisMotor,
-    R: ChassisMotor,
-    f32: From<<L as GetPosition>::Position>,
-    f32: From<<R as GetPosition>::Position>
+    R: ChassisMotor
 {
-    pub fn new(left: L, right: R, wheels_distance_cm: f32) -> Self {
-        Self {
-            wheels_distance: wheels_distance_cm,
+    pub fn new(left: L, right: R, wheels_distance: Length) -> Self {
+        let mut chassis = Self {
+            wheels_distance,
 
             speed: ChassisSpeed::default(),
+            velocity_limits: VelocityLimits::default(),
 
             current_movement: None,
+            velocity: None,
+            stopping: false,
+            halted: false,
+            measured_speed: ChassisSpeed::default(),
             position: ChassisPosition::default(),
+            prev_wheel_positions: (Length::ZERO, Length::ZERO),
+            heading_estimator: None,
 
             left,
             right,
+        };
+        chassis.prev_wheel_positions = chassis.get_wheel_positions();
+
+        chassis
+    }
+}
+
+impl<L, R, G> Chassis<L, R, G> 
+where
+    L: ChassisMotor,
+    R: ChassisMotor,
+    G: ReadYawRate
+{
+    /// Uses the gyro to estimate the heading instead of relying on the wheels alone
+    pub fn with_heading_estimator<H: ReadYawRate>(self, heading_estimator: HeadingEstimator<H>) -> Chassis<L, R, H> {
+        Chassis {
+            wheels_distance: self.wheels_distance,
+
+            speed: self.speed,
+            velocity_limits: self.velocity_limits,
+
+            current_movement: self.current_movement,
+            velocity: self.velocity,
+            stopping: self.stopping,
+            halted: self.halted,
+            measured_speed: self.measured_speed,
+            position: self.position,
+            prev_wheel_positions: self.prev_wheel_positions,
+            heading_estimator: Some(heading_estimator),
+
+            left: self.left,
+            right: self.right,
+        }
+    }
+
+    pub fn with_velocity_limits(mut self, velocity_limits: VelocityLimits) -> Self {
+        self.velocity_limits = velocity_limits;
+        self
+    }
+
+    pub fn get_left(&self) -> &L {
+        &self.left
+    }
+
+    pub fn get_left_mut(&mut self) -> &mut L {
+        &mut self.left
+    }
+
+    pub fn get_right(&self) -> &R {
+        &self.right
+    }
+
+    pub fn get_right_mut(&mut self) -> &mut R {
+        &mut self.right
+    }
+
+    /// Velocity measured by the odometry during the last update
+    pub fn get_measured_speed(&self) -> ChassisSpeed {
+        self.measured_speed
+    }
+
+    pub fn get_state(&self) -> ChassisState {
+        if self.halted {
+            ChassisState::Halted
+        } else if self.stopping {
+            ChassisState::Stopping
+        } else if self.velocity.is_some() {
+            ChassisState::Driving
+        } else if self.current_movement.is_some() {
+            ChassisState::Moving
+        } else {
+            ChassisState::Idle
         }
     }
 
-    fn get_wheel_positions(&self)
-        -> (f32, f32) {
-        (self.left.get_position().into(), self.right.get_position().into())
+    fn get_wheel_positions(&self) -> (Length, Length) {
+        (self.left.get_position(), self.right.get_position())
+    }
+
+    fn update_odometry(&mut self, time_delta: Time) {
+        let wheel_positions = self.get_wheel_positions();
+        let (left_delta, right_delta) = (
+            wheel_positions.0 - self.prev_wheel_positions.0, wheel_positions.1 - self.prev_wheel_positions.1
+        );
+        self.prev_wheel_positions = wheel_positions;
+
+        let distance = (left_delta + right_delta) / 2.0;
+        let heading_delta = (left_delta - right_delta).to_angle(self.wheels_distance);
+        let heading_delta = match self.heading_estimator {
+            Some(ref mut estimator) => {
+                let stationary = left_delta == Length::ZERO && right_delta == Length::ZERO;
+                estimator.update(heading_delta, stationary, time_delta)
+            },
+            None => heading_delta
+        };
+        let heading = self.position.angular.to_radians();
+        let turn = heading_delta.to_radians();
+
+        // integrate along the arc, falling back to a straight segment when it is (almost) one
+        let (dx, dy) = if turn.abs() < 1e-6 {
+            (distance * libm::cosf(heading), distance * libm::sinf(heading))
+        } else {
+            let radius = distance / turn;
+            (
+                radius * (libm::sinf(heading + turn) - libm::sinf(heading)),
+                radius * (libm::cosf(heading) - libm::cosf(heading + turn))
+            )
+        };
+
+        if time_delta > Time::ZERO {
+            self.measured_speed = ChassisSpeed {
+                linear: distance / time_delta,
+                angular: heading_delta / time_delta
+            };
+        }
+
+        self.position.linear.0 += dx;
+        self.position.linear.1 += dy;
+        self.position.angular = (self.position.angular + heading_delta).normalized();
     }
 }
 
-impl<L, R> Update for Chassis<L, R> 
+impl<L, R, G> Chassis<L, R, G>
 where
     L: ChassisMotor,
     R: ChassisMotor,
-    f32: From<<L as GetPosition>::Position>,
-    f32: From<<R as GetPosition>::Position>
+    G: ReadYawRate
 {
-    fn update(&mut self, time_delta_seconds: f32) {
-        self.left.update(time_delta_seconds);
-        self.right.update(time_delta_seconds);
+    fn update_velocity(&mut self, time_delta: Time) {
+        let limits = self.velocity_limits;
+        let velocity = match self.velocity {
+            Some(ref mut velocity) => velocity,
+            None => return
+        };
 
-        let wheel_positions = self.get_wheel_positions();
+        velocity.age += time_delta;
+        let timed_out = velocity.age > limits.command_timeout;
+        if timed_out {
+            velocity.target = ChassisSpeed::default();
+        }
 
-        if let Some(ref mut movement) = self.current_movement {
-            let wheels_distance = (
-                (wheel_positions.0 - movement.prev_pos.0), (wheel_positions.1 - movement.prev_pos.1)
-            );
-            movement.prev_pos = wheel_positions;
-
-            match movement.movement {
-                AtomicMovement::Linear(_) => {
-                    self.position.linear.0 += libm::cosf(self.position.angular) * wheels_distance.0;
-                    self.position.linear.1 += libm::sinf(self.position.angular) * wheels_distance.1;
-                },
-                AtomicMovement::Angular(_) => {
-                    // TODO: it assumes that wheels have travelled an equal distance,
-                    //       but it is probably incorrect
-                    self.position.angular += (wheels_distance.0 * 2.0) / self.wheels_distance;
-                },
-            }
+        velocity.current = ChassisSpeed {
+            linear: approach(velocity.current.linear, velocity.target.linear,
+                             limits.linear_acceleration * time_delta),
+            angular: approach(velocity.current.angular, velocity.target.angular,
+                              limits.angular_acceleration * time_delta)
+        };
 
-            if self.is_target_reached() {
-                self.current_movement = None;
-            }
+        // the left wheel is on the outside of a clockwise turn
+        let turn = velocity.current.angular.to_tangential(self.wheels_distance / 2.0);
+        let linear = velocity.current.linear;
+        let stopped = velocity.current.is_zero();
+        self.left.set_velocity(linear + turn);
+        self.right.set_velocity(linear - turn);
+
+        // braked to a standstill after the timeout, the velocity mode is left
+        if timed_out && stopped {
+            self.velocity = None;
+        }
+    }
+}
+
+/// Moves `current` towards `target` by at most `max_step`
+fn approach<Q>(current: Q, target: Q, max_step: Q) -> Q
+where
+    Q: Copy + core::ops::Add<Output = Q> + core::ops::Sub<Output = Q> + core::ops::Neg<Output = Q> + PartialOrd
+{
+    let step = target - current;
+    let step = if step > max_step { max_step } else if step < -max_step { -max_step } else { step };
+
+    current + step
+}
+
+impl<L, R, G> Update for Chassis<L, R, G> 
+where
+    L: ChassisMotor,
+    R: ChassisMotor,
+    G: ReadYawRate
+{
+    fn update(&mut self, time_delta: Time) {
+        self.update_velocity(time_delta);
+
+        self.left.update(time_delta);
+        self.right.update(time_delta);
+
+        self.update_odometry(time_delta);
+
+        if (self.current_movement.is_some() || self.stopping) && self.get_target_state() != TargetState::Moving {
+            self.current_movement = None;
+            self.stopping = false;
         }
     }
 }
 
-impl<L, R> SetSpeed for Chassis<L, R> 
+impl<L, R, G> SetSpeed for Chassis<L, R, G> 
 where
     L: ChassisMotor,
     R: ChassisMotor,
+    G: ReadYawRate,
 {
     type Speed = ChassisSpeed;
 
+    /// Maximal speeds of the position moves and the velocity mode, zero means no limit
     fn set_speed(&mut self, speed: Self::Speed) {
         self.speed = speed;
     }
 }
 
-impl<L, R> GetPosition for Chassis<L, R> 
+impl<L, R, G> SetVelocity for Chassis<L, R, G>
+where
+    L: ChassisMotor,
+    R: ChassisMotor,
+    G: ReadYawRate,
+{
+    type Velocity = ChassisSpeed;
+
+    /// Switches to the velocity mode, any position move is dropped.
+    /// The velocity has to be set again within the command timeout, or the robot stops
+    fn set_velocity(&mut self, velocity: Self::Velocity) {
+        let target = ChassisSpeed {
+            linear: clamp_speed(velocity.linear, self.speed.linear),
+            angular: clamp_speed(velocity.angular, self.speed.angular)
+        };
+        // start from the actual velocity, so that switching from a position move is smooth
+        let current = self.velocity.map_or(self.measured_speed, |velocity| velocity.current);
+
+        self.current_movement = None;
+        self.stopping = false;
+        self.halted = false;
+        self.velocity = Some(VelocityCommand { target, current, age: Time::ZERO });
+    }
+}
+
+impl<L, R, G> Stop for Chassis<L, R, G>
 where
     L: ChassisMotor,
     R: ChassisMotor,
+    G: ReadYawRate,
+{
+    /// Drops the current movement or velocity, the wheels decelerate independently
+    fn stop(&mut self) {
+        if self.halted {
+            return;
+        }
+
+        self.current_movement = None;
+        self.velocity = None;
+        self.stopping = true;
+
+        self.left.stop();
+        self.right.stop();
+    }
+
+    fn halt(&mut self) {
+        self.current_movement = None;
+        self.velocity = None;
+        self.stopping = false;
+        self.halted = true;
+
+        self.left.halt();
+        self.right.halt();
+    }
+}
+
+fn clamp_speed<Q>(speed: Q, max_speed: Q) -> Q
+where
+    Q: Copy + Default + core::ops::Neg<Output = Q> + PartialOrd
+{
+    if max_speed <= Q::default() {
+        speed
+    } else if speed > max_speed {
+        max_speed
+    } else if speed < -max_speed {
+        -max_speed
+    } else {
+        speed
+    }
+}
+
+impl<L, R, G> GetPosition for Chassis<L, R, G> 
+where
+    L: ChassisMotor,
+    R: ChassisMotor,
+    G: ReadYawRate,
 {
     type Position = ChassisPosition;
 
@@ -136,44 +421,69 @@ where
     }
 }
 
-impl<L, R> CheckTargetReached for Chassis<L, R> 
+impl<L, R, G> CheckTargetReached for Chassis<L, R, G> 
 where
     L: ChassisMotor,
     R: ChassisMotor,
-    f32: From<<L as GetPosition>::Position>,
-    f32: From<<R as GetPosition>::Position>
+    G: ReadYawRate
 {
-    fn is_target_reached(&self) -> bool {
-        self.left.is_target_reached() && self.right.is_target_reached()
+    fn get_target_state(&self) -> TargetState {
+        let wheels = self.left.get_target_state().combine(self.right.get_target_state());
+
+        match self.velocity {
+            Some(velocity) if !velocity.target.is_zero() || !velocity.current.is_zero() => TargetState::Moving,
+            _ => wheels
+        }
     }
 }
 
-impl<L, R> MoveAtomic for Chassis<L, R> 
+impl<L, R, G> GetVelocity for Chassis<L, R, G> 
 where
     L: ChassisMotor,
     R: ChassisMotor,
-    f32: From<<L as GetPosition>::Position>,
-    f32: From<<R as GetPosition>::Position>,
-    <L as SetPosition>::Position: From<f32>,
-    <R as SetPosition>::Position: From<f32>
+    G: ReadYawRate
 {
+    type Velocity = Option<ChassisSpeed>;
+
+    /// Velocity currently commanded to the wheels, `None` unless in the velocity mode
+    fn get_velocity(&self) -> Self::Velocity {
+        self.velocity.map(|velocity| velocity.current)
+    }
+}
+
+impl<L, R, G> MoveAtomic for Chassis<L, R, G> 
+where
+    L: ChassisMotor,
+    R: ChassisMotor,
+    G: ReadYawRate
+{
+    /// Leaves the velocity mode, the wheels brake from their current velocity
     fn move_atomic(&mut self, movement: AtomicMovement) {
         let wheel_positions = self.get_wheel_positions();
 
-        self.current_movement = Some(ChassisMovement {
-            movement: movement.clone(),
-            prev_pos: wheel_positions.clone()
-        });
+        self.current_movement = Some(movement);
+        self.velocity = None;
+        self.stopping = false;
+        self.halted = false;
+
+        let wheel_speed = match movement {
+            AtomicMovement::Linear(_) => self.speed.linear,
+            AtomicMovement::Angular(_) => self.speed.angular.to_tangential(self.wheels_distance / 2.0)
+        };
+        if wheel_speed > Velocity::ZERO {
+            self.left.set_speed(wheel_speed);
+            self.right.set_speed(wheel_speed);
+        }
 
         match movement {
             AtomicMovement::Linear(distance) => {
-                self.left.set_position((wheel_positions.0 + distance).into());
-                self.right.set_position((wheel_positions.1 + distance).into());
+                self.left.set_position(wheel_positions.0 + distance);
+                self.right.set_position(wheel_positions.1 + distance);
             },
             AtomicMovement::Angular(angle) => {
-                let increment = angle.to_radians() * (self.wheels_distance / 2.0);
-                self.left.set_position((wheel_positions.0 + increment).into());
-                self.right.set_position((wheel_positions.1 - increment).into());
+                let increment = angle.to_arc(self.wheels_distance / 2.0);
+                self.left.set_position(wheel_positions.0 + increment);
+                self.right.set_position(wheel_positions.1 - increment);
             }
         };
     }
@@ -0,0 +1,72 @@
+use core::convert::Infallible;
+
+use units::{Angle, AngularVelocity, Time};
+use gyro::ReadYawRate;
+
+/// Placeholder gyro for a chassis that relies on wheel odometry alone
+pub enum NoGyro {}
+
+impl ReadYawRate for NoGyro {
+    type Error = Infallible;
+
+    fn read_yaw_rate(&mut self) -> Result<f32, Self::Error> {
+        match *self {}
+    }
+}
+
+/// Complementary filter fusing the gyro yaw rate with the wheel odometry yaw.
+///
+/// Heading increments are blended: the gyro, which does not care about wheel slip,
+/// gets `gyro_weight` and the odometry gets the rest. The gyro bias is tracked while
+/// the wheels are standing still, so that it does not drift.
+/// If the gyro cannot be read, the odometry increment is used as is.
+pub struct HeadingEstimator<G: ReadYawRate> {
+    gyro: G,
+
+    pub gyro_weight: f32,
+    pub bias_time_constant: Time,
+    pub reverse: bool,
+
+    bias: AngularVelocity,
+}
+
+impl<G: ReadYawRate> HeadingEstimator<G> {
+    pub fn new(gyro: G, gyro_weight: f32, bias_time_constant: Time, reverse: bool) -> Self {
+        assert!((0.0..=1.0).contains(&gyro_weight));
+
+        Self {
+            gyro,
+
+            gyro_weight,
+            bias_time_constant,
+            reverse,
+
+            bias: AngularVelocity::ZERO,
+        }
+    }
+
+    pub fn get_bias(&self) -> AngularVelocity {
+        self.bias
+    }
+
+    /// Returns the fused heading increment
+    ///
+    /// `odometry_heading_delta` is the change of the wheel odometry heading since
+    /// the previous call and `stationary` tells whether the wheels have not moved.
+    pub fn update(&mut self, odometry_heading_delta: Angle, stationary: bool, time_delta: Time) -> Angle {
+        match self.gyro.read_yaw_rate() {
+            Ok(rate) => {
+                let rate = AngularVelocity::from_degrees_per_s(if self.reverse { -rate } else { rate });
+
+                if stationary {
+                    let gain = (time_delta / self.bias_time_constant).min(1.0);
+                    self.bias += (rate - self.bias) * gain;
+                }
+
+                let gyro_delta = (rate - self.bias) * time_delta;
+                self.gyro_weight * gyro_delta + (1.0 - self.gyro_weight) * odometry_heading_delta
+            },
+            Err(_) => odometry_heading_delta
+        }
+    }
+}
@@ -2,5 +2,6 @@
 #![feature(trait_alias)]
 
 pub mod chassis;
+pub mod heading;
 pub mod movement_controller;
 
@@ -1,123 +1,349 @@
-use num_traits::float::FloatCore;
+use heapless::Deque;
 
-use crate::chassis::{MoveAtomic, ChassisPosition, AtomicMovement};
+use crate::chassis::{MoveAtomic, ChassisPosition, ChassisSpeed, AtomicMovement};
 
+use units::{Length, Angle, Time};
 use motor::SetSpeed;
-use servo::CheckTargetReached;
-use encoder::{Update, GetPosition};
+use servo::{SetVelocity, Stop, CheckTargetReached, TargetState};
+use encoder::{Update, GetPosition, GetVelocity};
 
 // TODO: this associated type specification should probably not be here
-pub trait MovementControlled = MoveAtomic + Update + GetPosition<Position = ChassisPosition> + CheckTargetReached + SetSpeed;
+pub trait MovementControlled = MoveAtomic + Update + GetPosition<Position = ChassisPosition> + CheckTargetReached + SetSpeed + SetVelocity<Velocity = ChassisSpeed> + GetVelocity<Velocity = Option<ChassisSpeed>> + Stop;
+
+/// Goals closer than this are reached by rotating in place
+const MIN_TRANSLATION: Length = Length::from_mm(1.0);
+
+/// The movement was not queued, because the queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueFull;
 
 pub trait MoveRelative {
+    /// Queues a movement by the given offset in the odometry frame,
+    /// followed by a rotation relative to the direction of the offset.
+    /// The offset is applied to the pose at which the movement starts
     // TODO: it is semantically wrong to use ChassisPosition here
-    fn move_relative(&mut self, movement: ChassisPosition);
+    fn move_relative(&mut self, movement: ChassisPosition) -> Result<(), QueueFull>;
+}
+
+pub trait MoveTo {
+    /// Queues a movement to the given pose in the odometry frame
+    fn move_to(&mut self, goal: ChassisPosition) -> Result<(), QueueFull>;
 }
 
 #[derive(Debug, Clone, Copy)]
-enum MovementStage {
-    InitialRotarion,
+enum MovementCommand {
+    Relative(ChassisPosition),
+    To(ChassisPosition)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementStage {
+    InitialRotation,
     Translation,
     FinalRotation
 }
 
+/// Goal of the movement in progress, in the odometry frame
 struct Movement {
-    pub movement: ChassisPosition,
+    pub goal: ChassisPosition,
     pub stage: MovementStage
 }
 
 impl Movement {
-    pub fn new(movement: ChassisPosition) -> Self {
+    /// Resolves the command against the pose at which it starts
+    pub fn new(command: MovementCommand, current_pos: ChassisPosition) -> Self {
+        let goal = match command {
+            MovementCommand::Relative(offset) => {
+                let (x, y) = offset.linear;
+                let heading = if x.abs().max(y.abs()) < MIN_TRANSLATION {
+                    current_pos.angular
+                } else {
+                    direction(x, y)
+                };
+
+                ChassisPosition {
+                    linear: (current_pos.linear.0 + x, current_pos.linear.1 + y),
+                    angular: (heading + offset.angular).normalized()
+                }
+            },
+            MovementCommand::To(goal) => goal
+        };
+
         Self {
-            movement,
-            stage: MovementStage::InitialRotarion,
+            goal,
+            stage: MovementStage::InitialRotation,
         }
     }
+
+    pub fn get_offset(&self, current_pos: ChassisPosition) -> (Length, Length) {
+        (self.goal.linear.0 - current_pos.linear.0, self.goal.linear.1 - current_pos.linear.1)
+    }
 }
 
-pub struct MovementController<T: MovementControlled> {
+/// Heading of the offset
+fn direction(x: Length, y: Length) -> Angle {
+    Angle::from_radians(libm::atan2f(y.to_m(), x.to_m()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerState {
+    Idle,
+    /// Executing the queued movements
+    Moving,
+    /// In the velocity mode
+    Driving,
+    /// Decelerating after `stop`, the movements are dropped
+    Stopping,
+    /// Standing still after `stop`
+    Stopped,
+    /// The motors are cut after `halt`, the movements are dropped
+    Halted,
+    /// Holding the position after `pause`, the movements are kept
+    Paused,
+    /// The movement could not be finished, the following ones are dropped
+    Failed(TargetState)
+}
+
+/// What ended or suspended the last movement
+#[derive(Debug, Clone, Copy)]
+enum Interruption {
+    Stopped,
+    Halted,
+    Paused,
+    Failed(TargetState)
+}
+
+/// Progress of the movement in progress
+#[derive(Debug, Clone, Copy)]
+pub struct MovementProgress {
+    pub stage: MovementStage,
+    pub goal: ChassisPosition,
+    /// Straight line distance from the current position to the goal
+    pub distance: Length,
+    /// Movements waiting after this one
+    pub queued: usize
+}
+
+/// Executes queued movements one after another, as rotate-translate-rotate sequences.
+/// Every stage is computed from the pose at which it starts, so that the errors
+/// of the previous stages are corrected
+pub struct MovementController<T: MovementControlled, const N: usize = 8> {
     atomic: T,
-    movement: Option<Movement>
+    movement: Option<Movement>,
+    queue: Deque<MovementCommand, N>,
+    interruption: Option<Interruption>,
+    driving: bool
 }
 
-impl<T: MovementControlled> MovementController<T> {
+impl<T: MovementControlled, const N: usize> MovementController<T, N> {
     pub fn new(controlled: T) -> Self {
         Self {
             atomic: controlled,
-            movement: None
+            movement: None,
+            queue: Deque::new(),
+            interruption: None,
+            driving: false
         }
     }
 
+    pub fn get_state(&self) -> ControllerState {
+        match self.interruption {
+            Some(Interruption::Stopped) if self.atomic.get_target_state() == TargetState::Moving => ControllerState::Stopping,
+            Some(Interruption::Stopped) => ControllerState::Stopped,
+            Some(Interruption::Halted) => ControllerState::Halted,
+            Some(Interruption::Paused) => ControllerState::Paused,
+            Some(Interruption::Failed(failure)) => ControllerState::Failed(failure),
+            None if self.driving => ControllerState::Driving,
+            None if self.movement.is_some() || self.atomic.get_target_state() == TargetState::Moving => ControllerState::Moving,
+            None => ControllerState::Idle
+        }
+    }
+
+    pub fn get_controlled(&self) -> &T {
+        &self.atomic
+    }
+
+    pub fn get_controlled_mut(&mut self) -> &mut T {
+        &mut self.atomic
+    }
+
+    /// Decelerates and holds the position, the movements are kept until `resume`
+    pub fn pause(&mut self) {
+        if self.interruption.is_some() {
+            return;
+        }
+
+        self.driving = false;
+        self.interruption = Some(Interruption::Paused);
+        self.atomic.stop();
+    }
+
+    /// Continues the paused movements, the current stage is restarted from the pose reached
+    pub fn resume(&mut self) {
+        if !matches!(self.interruption, Some(Interruption::Paused)) {
+            return;
+        }
+
+        self.interruption = None;
+        if self.movement.is_some() {
+            self.start_stage();
+        } else {
+            self.next_movement();
+        }
+    }
+
+    /// Drops the queued movements, the one in progress is finished
+    pub fn clear_queue(&mut self) {
+        self.queue.clear();
+    }
+
+    /// Number of movements waiting after the one in progress
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn get_progress(&self) -> Option<MovementProgress> {
+        self.movement.as_ref().map(|movement| {
+            let (dx, dy) = movement.get_offset(self.atomic.get_position());
+
+            MovementProgress {
+                stage: movement.stage,
+                goal: movement.goal,
+                distance: Length::from_m(libm::hypotf(dx.to_m(), dy.to_m())),
+                queued: self.queue.len()
+            }
+        })
+    }
+
+    fn enqueue(&mut self, command: MovementCommand) -> Result<(), QueueFull> {
+        self.queue.push_back(command).map_err(|_| QueueFull)?;
+        self.driving = false;
+
+        // a paused plan only goes on after `resume`
+        if matches!(self.interruption, Some(Interruption::Paused)) {
+            return Ok(());
+        }
+        self.interruption = None;
+
+        if self.movement.is_none() {
+            self.next_movement();
+        }
+        Ok(())
+    }
+
+    /// Drops all the movements
+    fn interrupt(&mut self, interruption: Interruption) {
+        self.movement = None;
+        self.queue.clear();
+        self.driving = false;
+        self.interruption = Some(interruption);
+    }
+
+    fn next_movement(&mut self) {
+        self.movement = self.queue.pop_front()
+            .map(|command| Movement::new(command, self.atomic.get_position()));
+        self.start_stage();
+    }
+
     fn next_stage(&mut self) {
-        let mut movement = self.movement.as_mut().unwrap();
-        match movement.stage.clone() {
-            MovementStage::InitialRotarion => {
+        let movement = self.movement.as_mut().unwrap();
+        match movement.stage {
+            MovementStage::InitialRotation => {
                 movement.stage = MovementStage::Translation;
             },
             MovementStage::Translation => {
                 movement.stage = MovementStage::FinalRotation;
             },
             MovementStage::FinalRotation => {
-                self.movement = None;
+                self.next_movement();
+                return;
             }
         }
+        self.start_stage();
     }
 
     fn start_stage(&mut self) {
-        if self.movement.is_none() {
-            return;
-        }
+        let movement = match self.movement {
+            Some(ref movement) => movement,
+            None => return
+        };
 
-        let movement = self.movement.as_mut().unwrap();
-        let (linear, angular) = (movement.movement.linear, movement.movement.angular);
         let current_pos = self.atomic.get_position();
+        let (dx, dy) = movement.get_offset(current_pos);
+        let translates = dx.abs().max(dy.abs()) >= MIN_TRANSLATION;
 
         match movement.stage {
-            MovementStage::InitialRotarion => {
-                let angle = libm::atan2f(
-                    linear.1, linear.0,
-                    );
+            MovementStage::InitialRotation if translates => {
+                let angle = direction(dx, dy);
 
-                self.atomic.move_atomic(AtomicMovement::Angular(angle.to_degrees() - current_pos.angular));
+                self.atomic.move_atomic(AtomicMovement::Angular((angle - current_pos.angular).normalized()));
             },
-            MovementStage::Translation => {
-                let distance = libm::sqrtf(linear.0.powi(2) + linear.1.powi(2));
+            MovementStage::Translation if translates => {
+                // the distance along the current heading, the final rotation takes care of the rest
+                let heading = current_pos.angular.to_radians();
+                let distance = dx * libm::cosf(heading) + dy * libm::sinf(heading);
 
                 self.atomic.move_atomic(AtomicMovement::Linear(distance));
             },
+            MovementStage::InitialRotation | MovementStage::Translation => {
+                self.next_stage();
+            },
             MovementStage::FinalRotation => {
-                self.atomic.move_atomic(AtomicMovement::Angular(angular));
+                let angle = (movement.goal.angular - current_pos.angular).normalized();
+
+                self.atomic.move_atomic(AtomicMovement::Angular(angle));
             }
         }
     }
 }
 
-impl<T: MovementControlled> MoveRelative for MovementController<T> {
-    fn move_relative(&mut self, movement: ChassisPosition) {
-        self.movement = Some(Movement::new(movement))
+impl<T: MovementControlled, const N: usize> MoveRelative for MovementController<T, N> {
+    fn move_relative(&mut self, movement: ChassisPosition) -> Result<(), QueueFull> {
+        self.enqueue(MovementCommand::Relative(movement))
     }
 }
 
-impl<T: MovementControlled> Update for MovementController<T> {
-    fn update(&mut self, time_delta_seconds: f32) {
-        self.atomic.update(time_delta_seconds);
+impl<T: MovementControlled, const N: usize> MoveTo for MovementController<T, N> {
+    fn move_to(&mut self, goal: ChassisPosition) -> Result<(), QueueFull> {
+        self.enqueue(MovementCommand::To(goal))
+    }
+}
 
-        if self.movement.is_some() {
-            if self.atomic.is_target_reached() {
-                self.next_stage();
-                self.start_stage();
+impl<T: MovementControlled, const N: usize> Update for MovementController<T, N> {
+    fn update(&mut self, time_delta: Time) {
+        self.atomic.update(time_delta);
+
+        // the chassis leaves the velocity mode on its own once the commands time out
+        if self.driving && self.atomic.get_velocity().is_none() {
+            self.driving = false;
+        }
+
+        if self.movement.is_some() && self.interruption.is_none() {
+            match self.atomic.get_target_state() {
+                TargetState::Moving => {},
+                TargetState::Reached => {
+                    self.next_stage();
+                },
+                failure => {
+                    // the wheels already hold their position, the rest of the plan is based on this movement
+                    self.interrupt(Interruption::Failed(failure));
+                }
             }
         }
     }
 }
 
-impl<T: MovementControlled> CheckTargetReached for MovementController<T> {
-    fn is_target_reached(&self) -> bool {
-        self.movement.is_none() && self.atomic.is_target_reached()
+impl<T: MovementControlled, const N: usize> CheckTargetReached for MovementController<T, N> {
+    fn get_target_state(&self) -> TargetState {
+        match self.interruption {
+            Some(Interruption::Failed(failure)) => failure,
+            None if self.movement.is_some() => TargetState::Moving,
+            _ => self.atomic.get_target_state()
+        }
     }
 }
 
-impl<T: MovementControlled> GetPosition for MovementController<T> {
+impl<T: MovementControlled, const N: usize> GetPosition for MovementController<T, N> {
     type Position = T::Position;
 
     fn get_position(&self) -> Self::Position {
@@ -125,10 +351,41 @@ impl<T: MovementControlled> GetPosition for MovementController<T> {
     }
 }
 
-impl<T: MovementControlled> SetSpeed for MovementController<T> {
+impl<T: MovementControlled, const N: usize> SetSpeed for MovementController<T, N> {
     type Speed = T::Speed;
 
     fn set_speed(&mut self, speed: Self::Speed) {
         self.atomic.set_speed(speed);
     }
 }
+
+impl<T: MovementControlled, const N: usize> SetVelocity for MovementController<T, N> {
+    type Velocity = ChassisSpeed;
+
+    /// Drops all the movements and drives at the given velocity
+    fn set_velocity(&mut self, velocity: Self::Velocity) {
+        self.movement = None;
+        self.queue.clear();
+        self.interruption = None;
+        self.driving = true;
+        self.atomic.set_velocity(velocity);
+    }
+}
+
+impl<T: MovementControlled, const N: usize> Stop for MovementController<T, N> {
+    /// Drops all the movements and decelerates to a standstill
+    fn stop(&mut self) {
+        if matches!(self.interruption, Some(Interruption::Halted)) {
+            return;
+        }
+
+        self.interrupt(Interruption::Stopped);
+        self.atomic.stop();
+    }
+
+    /// Drops all the movements and cuts the motors
+    fn halt(&mut self) {
+        self.interrupt(Interruption::Halted);
+        self.atomic.halt();
+    }
+}
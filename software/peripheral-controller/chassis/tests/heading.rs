@@ -0,0 +1,85 @@
+use units::{Angle, Time};
+use gyro::ReadYawRate;
+use chassis::heading::HeadingEstimator;
+
+const TIME_DELTA: Time = Time::from_seconds(0.025);
+
+/// Gyro replaying a synthetic rate, `None` simulates a bus error
+struct SyntheticGyro {
+    rate: Option<f32>
+}
+
+impl ReadYawRate for SyntheticGyro {
+    type Error = ();
+
+    fn read_yaw_rate(&mut self) -> Result<f32, Self::Error> {
+        self.rate.ok_or(())
+    }
+}
+
+fn estimator(rate: Option<f32>) -> HeadingEstimator<SyntheticGyro> {
+    HeadingEstimator::new(SyntheticGyro { rate }, 0.98, Time::from_seconds(1.0), false)
+}
+
+/// Fused heading increment for an odometry increment, in degrees
+fn update(estimator: &mut HeadingEstimator<SyntheticGyro>, odometry_heading_delta: f32, stationary: bool) -> f32 {
+    estimator.update(Angle::from_degrees(odometry_heading_delta), stationary, TIME_DELTA).to_degrees()
+}
+
+#[test]
+fn follows_gyro_when_wheels_slip() {
+    let mut estimator = estimator(Some(0.0));
+
+    // wheels report a 90 degree turn over a second while the robot does not rotate
+    let mut heading = 0.0;
+    for _ in 0..40 {
+        heading += update(&mut estimator, 90.0 / 40.0, false);
+    }
+
+    assert!(heading.abs() < 90.0 * 0.05, "heading {}", heading);
+}
+
+#[test]
+fn tracks_turn_reported_by_both() {
+    let mut estimator = estimator(Some(45.0));
+
+    let mut heading = 0.0;
+    for _ in 0..80 {
+        heading += update(&mut estimator, 45.0 * TIME_DELTA.to_seconds(), false);
+    }
+
+    assert!((heading - 90.0).abs() < 1e-3, "heading {}", heading);
+}
+
+#[test]
+fn learns_bias_while_stationary() {
+    let mut estimator = estimator(Some(2.0));
+
+    for _ in 0..400 {
+        update(&mut estimator, 0.0, true);
+    }
+    let bias = estimator.get_bias().to_degrees_per_s();
+    assert!((bias - 2.0).abs() < 1e-3, "bias {}", bias);
+
+    // once the bias is known, a biased gyro does not make a still robot drift
+    let mut heading = 0.0;
+    for _ in 0..400 {
+        heading += update(&mut estimator, 0.0, false);
+    }
+    assert!(heading.abs() < 0.1, "heading {}", heading);
+}
+
+#[test]
+fn falls_back_to_odometry_without_gyro() {
+    let mut estimator = estimator(None);
+
+    assert_eq!(update(&mut estimator, 1.5, false), 1.5);
+}
+
+#[test]
+fn reverse_flips_gyro_rate() {
+    let mut estimator = HeadingEstimator::new(SyntheticGyro { rate: Some(-40.0) }, 1.0, Time::from_seconds(1.0), true);
+
+    let delta = update(&mut estimator, 0.0, false);
+    assert!((delta - 1.0).abs() < 1e-6, "delta {}", delta);
+}
@@ -0,0 +1,289 @@
+use std::{cell::RefCell, rc::Rc};
+
+use units::{Length, Angle, Time, Velocity, AngularVelocity};
+use motor::SetSpeed;
+use encoder::{Update, GetPosition, GetVelocity};
+use servo::{SetVelocity, Stop, CheckTargetReached, TargetState};
+use chassis::{
+    chassis::{ChassisPosition, ChassisSpeed, MoveAtomic, AtomicMovement},
+    movement_controller::{MovementController, MoveRelative, MoveTo, MovementStage, ControllerState, QueueFull}
+};
+
+const TIME_DELTA: Time = Time::from_seconds(0.025);
+const EPSILON: f32 = 1e-3;
+
+/// Chassis which performs every atomic movement exactly, taking one update.
+/// A stopped movement is only done halfway, a zero velocity leaves the velocity mode on the next update
+struct MockChassis {
+    position: ChassisPosition,
+    state: TargetState,
+    velocity: Option<ChassisSpeed>,
+    movements: Rc<RefCell<Vec<AtomicMovement>>>
+}
+
+impl MockChassis {
+    fn new() -> Self {
+        Self { position: ChassisPosition::default(), state: TargetState::Reached, velocity: None, movements: Rc::default() }
+    }
+}
+
+impl MoveAtomic for MockChassis {
+    fn move_atomic(&mut self, movement: AtomicMovement) {
+        match movement {
+            AtomicMovement::Linear(distance) => {
+                let heading = self.position.angular.to_radians();
+                self.position.linear.0 += distance * heading.cos();
+                self.position.linear.1 += distance * heading.sin();
+            },
+            AtomicMovement::Angular(angle) => {
+                self.position.angular = (self.position.angular + angle).normalized();
+            }
+        }
+
+        self.movements.borrow_mut().push(movement);
+        self.state = TargetState::Moving;
+        self.velocity = None;
+    }
+}
+
+impl Stop for MockChassis {
+    fn stop(&mut self) {
+        if self.state != TargetState::Moving {
+            return;
+        }
+
+        let movement = self.movements.borrow_mut().pop().unwrap();
+        let half = match movement {
+            AtomicMovement::Linear(distance) => AtomicMovement::Linear(-distance / 2.0),
+            AtomicMovement::Angular(angle) => AtomicMovement::Angular(-angle / 2.0)
+        };
+        self.move_atomic(half);
+        self.movements.borrow_mut().pop();
+    }
+
+    fn halt(&mut self) {
+        self.state = TargetState::Reached;
+    }
+}
+
+impl Update for MockChassis {
+    fn update(&mut self, _time_delta: Time) {
+        if self.state == TargetState::Moving {
+            self.state = TargetState::Reached;
+        }
+        if self.velocity.is_some_and(|velocity| velocity.is_zero()) {
+            self.velocity = None;
+        }
+    }
+}
+
+impl GetPosition for MockChassis {
+    type Position = ChassisPosition;
+
+    fn get_position(&self) -> Self::Position {
+        self.position
+    }
+}
+
+impl CheckTargetReached for MockChassis {
+    fn get_target_state(&self) -> TargetState {
+        self.state
+    }
+}
+
+impl SetSpeed for MockChassis {
+    type Speed = ChassisSpeed;
+
+    fn set_speed(&mut self, _speed: Self::Speed) {}
+}
+
+impl SetVelocity for MockChassis {
+    type Velocity = ChassisSpeed;
+
+    fn set_velocity(&mut self, velocity: Self::Velocity) {
+        self.velocity = Some(velocity);
+    }
+}
+
+impl GetVelocity for MockChassis {
+    type Velocity = Option<ChassisSpeed>;
+
+    fn get_velocity(&self) -> Self::Velocity {
+        self.velocity
+    }
+}
+
+type Controller = MovementController<MockChassis, 4>;
+
+fn controller() -> (Controller, Rc<RefCell<Vec<AtomicMovement>>>) {
+    let chassis = MockChassis::new();
+    let movements = chassis.movements.clone();
+    (Controller::new(chassis), movements)
+}
+
+fn run(controller: &mut Controller) {
+    for _ in 0..100 {
+        controller.update(TIME_DELTA);
+        if controller.is_target_reached() {
+            return;
+        }
+    }
+    panic!("movement is not finished");
+}
+
+/// cm and degrees
+fn assert_pose(controller: &Controller, x: f32, y: f32, angular: f32) {
+    let position = controller.get_position();
+    assert!((position.linear.0.to_cm() - x).abs() < EPSILON, "{:?}", position);
+    assert!((position.linear.1.to_cm() - y).abs() < EPSILON, "{:?}", position);
+    assert!((position.angular - Angle::from_degrees(angular)).normalized().to_degrees().abs() < EPSILON, "{:?}", position);
+}
+
+/// cm and degrees
+fn pose(x: f32, y: f32, angular: f32) -> ChassisPosition {
+    ChassisPosition { linear: (Length::from_cm(x), Length::from_cm(y)), angular: Angle::from_degrees(angular) }
+}
+
+#[test]
+fn relative_move_rotates_first() {
+    let (mut controller, _) = controller();
+
+    controller.move_relative(pose(0.0, 20.0, 45.0)).unwrap();
+    assert_eq!(controller.get_progress().unwrap().stage, MovementStage::InitialRotation);
+    run(&mut controller);
+
+    assert_pose(&controller, 0.0, 20.0, 135.0);
+}
+
+#[test]
+fn relative_moves_are_queued() {
+    let (mut controller, _) = controller();
+
+    controller.move_relative(pose(10.0, 0.0, 0.0)).unwrap();
+    controller.move_relative(pose(0.0, 10.0, 0.0)).unwrap();
+    controller.move_relative(pose(-10.0, 0.0, 0.0)).unwrap();
+    assert_eq!(controller.queue_len(), 2);
+
+    run(&mut controller);
+    assert_eq!(controller.queue_len(), 0);
+    assert!(controller.get_progress().is_none());
+    assert_pose(&controller, 0.0, 10.0, 180.0);
+}
+
+#[test]
+fn move_to_is_absolute() {
+    let (mut controller, movements) = controller();
+
+    controller.move_to(pose(30.0, -40.0, 90.0)).unwrap();
+    let progress = controller.get_progress().unwrap();
+    assert!((progress.distance.to_cm() - 50.0).abs() < EPSILON);
+
+    controller.move_to(pose(30.0, -40.0, 90.0)).unwrap();
+    run(&mut controller);
+    assert_pose(&controller, 30.0, -40.0, 90.0);
+
+    // a goal at the current position only rotates
+    movements.borrow_mut().clear();
+    controller.move_to(pose(30.0, -40.0, -90.0)).unwrap();
+    run(&mut controller);
+    assert_eq!(movements.borrow().len(), 1);
+    assert_pose(&controller, 30.0, -40.0, -90.0);
+}
+
+#[test]
+fn full_queue_is_reported() {
+    let (mut controller, _) = controller();
+
+    // one in progress and four queued
+    for _ in 0..5 {
+        controller.move_relative(pose(1.0, 0.0, 0.0)).unwrap();
+    }
+    assert_eq!(controller.move_relative(pose(1.0, 0.0, 0.0)), Err(QueueFull));
+    assert_eq!(controller.move_to(pose(1.0, 0.0, 0.0)), Err(QueueFull));
+
+    controller.clear_queue();
+    assert_eq!(controller.queue_len(), 0);
+    run(&mut controller);
+    assert_pose(&controller, 1.0, 0.0, 0.0);
+}
+
+#[test]
+fn pause_keeps_the_plan() {
+    let (mut controller, _) = controller();
+
+    controller.move_to(pose(20.0, 0.0, 0.0)).unwrap();
+    controller.move_to(pose(20.0, 20.0, 90.0)).unwrap();
+    // the initial rotation is zero, so the translation is in progress after one update
+    controller.update(TIME_DELTA);
+    assert_eq!(controller.get_progress().unwrap().stage, MovementStage::Translation);
+
+    controller.pause();
+    assert_eq!(controller.get_state(), ControllerState::Paused);
+    for _ in 0..10 {
+        controller.update(TIME_DELTA);
+    }
+    assert_pose(&controller, 10.0, 0.0, 0.0);
+    assert_eq!(controller.get_state(), ControllerState::Paused);
+    assert_eq!(controller.queue_len(), 1);
+
+    controller.resume();
+    assert_eq!(controller.get_state(), ControllerState::Moving);
+    run(&mut controller);
+    assert_pose(&controller, 20.0, 20.0, 90.0);
+    assert_eq!(controller.get_state(), ControllerState::Idle);
+}
+
+#[test]
+fn stop_drops_the_plan() {
+    let (mut controller, _) = controller();
+
+    controller.move_to(pose(20.0, 0.0, 0.0)).unwrap();
+    controller.move_to(pose(20.0, 20.0, 0.0)).unwrap();
+    controller.update(TIME_DELTA);
+
+    controller.stop();
+    assert_eq!(controller.get_state(), ControllerState::Stopping);
+    assert!(controller.get_progress().is_none());
+    assert_eq!(controller.queue_len(), 0);
+
+    controller.update(TIME_DELTA);
+    assert_eq!(controller.get_state(), ControllerState::Stopped);
+    assert_pose(&controller, 10.0, 0.0, 0.0);
+
+    // resuming is only possible after a pause
+    controller.resume();
+    assert_eq!(controller.get_state(), ControllerState::Stopped);
+
+    controller.move_relative(pose(5.0, 0.0, 0.0)).unwrap();
+    assert_eq!(controller.get_state(), ControllerState::Moving);
+}
+
+#[test]
+fn halt_is_reported() {
+    let (mut controller, _) = controller();
+
+    controller.move_relative(pose(10.0, 10.0, 0.0)).unwrap();
+    controller.halt();
+
+    assert_eq!(controller.get_state(), ControllerState::Halted);
+    assert_eq!(controller.get_target_state(), TargetState::Reached);
+
+    // neither a stop nor a pause overrides the halt
+    controller.stop();
+    controller.pause();
+    assert_eq!(controller.get_state(), ControllerState::Halted);
+}
+
+#[test]
+fn driving_ends_with_the_velocity_mode() {
+    let (mut controller, _) = controller();
+
+    controller.set_velocity(ChassisSpeed { linear: Velocity::from_cm_per_s(10.0), angular: AngularVelocity::ZERO });
+    controller.update(TIME_DELTA);
+    assert_eq!(controller.get_state(), ControllerState::Driving);
+
+    // the chassis braked to a standstill and left the velocity mode, as after a command timeout
+    controller.set_velocity(ChassisSpeed::default());
+    controller.update(TIME_DELTA);
+    assert_eq!(controller.get_state(), ControllerState::Idle);
+}
@@ -0,0 +1,107 @@
+use units::{Length, Angle, Time};
+use encoder::{Update, GetPosition};
+use chassis::chassis::{Chassis, MoveAtomic, AtomicMovement};
+use test_support::MockWheel;
+
+const WHEELS_DISTANCE: Length = Length::from_cm(20.0);
+const TIME_DELTA: Time = Time::from_seconds(0.025);
+const EPSILON: f32 = 1e-3;
+
+fn chassis() -> (Chassis<MockWheel, MockWheel>, MockWheel, MockWheel) {
+    let (left, right) = (MockWheel::default(), MockWheel::default());
+    (Chassis::new(left.clone(), right.clone(), WHEELS_DISTANCE), left, right)
+}
+
+fn drive(chassis: &mut Chassis<MockWheel, MockWheel>, left: &MockWheel, right: &MockWheel,
+         left_distance: Length, right_distance: Length, ticks: u32) {
+    for _ in 0..ticks {
+        left.advance(left_distance / ticks as f32);
+        right.advance(right_distance / ticks as f32);
+        chassis.update(TIME_DELTA);
+    }
+}
+
+fn cm(centimeters: f32) -> Length {
+    Length::from_cm(centimeters)
+}
+
+/// Distance each wheel travels while turning in place by the angle, in degrees
+fn turn(degrees: f32) -> Length {
+    Angle::from_degrees(degrees).to_arc(WHEELS_DISTANCE / 2.0)
+}
+
+/// cm and degrees
+fn assert_pose(chassis: &Chassis<MockWheel, MockWheel>, x: f32, y: f32, angular: f32) {
+    let position = chassis.get_position();
+    assert!((position.linear.0.to_cm() - x).abs() < EPSILON, "{:?}", position);
+    assert!((position.linear.1.to_cm() - y).abs() < EPSILON, "{:?}", position);
+    assert!((position.angular - Angle::from_degrees(angular)).normalized().to_degrees().abs() < EPSILON, "{:?}", position);
+}
+
+#[test]
+fn straight_line() {
+    let (mut chassis, left, right) = chassis();
+
+    drive(&mut chassis, &left, &right, cm(50.0), cm(50.0), 10);
+
+    assert_pose(&chassis, 50.0, 0.0, 0.0);
+}
+
+#[test]
+fn odometry_runs_without_movement() {
+    let (mut chassis, left, right) = chassis();
+
+    drive(&mut chassis, &left, &right, cm(-12.0), cm(-12.0), 3);
+
+    assert_pose(&chassis, -12.0, 0.0, 0.0);
+}
+
+#[test]
+fn turn_in_place() {
+    let (mut chassis, left, right) = chassis();
+    let quarter_turn = turn(90.0);
+
+    chassis.move_atomic(AtomicMovement::Angular(Angle::from_degrees(90.0)));
+    drive(&mut chassis, &left, &right, quarter_turn, -quarter_turn, 7);
+
+    assert_pose(&chassis, 0.0, 0.0, 90.0);
+}
+
+#[test]
+fn heading_wraps_around() {
+    let (mut chassis, left, right) = chassis();
+    let half_turn = turn(180.0);
+
+    drive(&mut chassis, &left, &right, -half_turn * 1.5, half_turn * 1.5, 9);
+
+    assert_pose(&chassis, 0.0, 0.0, 90.0);
+}
+
+#[test]
+fn turn_then_straight_line() {
+    let (mut chassis, left, right) = chassis();
+    let quarter_turn = turn(90.0);
+
+    drive(&mut chassis, &left, &right, -quarter_turn, quarter_turn, 5);
+    drive(&mut chassis, &left, &right, cm(30.0), cm(30.0), 5);
+
+    assert_pose(&chassis, 0.0, -30.0, -90.0);
+}
+
+#[test]
+fn quarter_circle_arc() {
+    let (mut chassis, left, right) = chassis();
+    let radius = 40.0;
+    let quarter = core::f32::consts::FRAC_PI_2;
+
+    // right-hand arc around a centre at (0, radius)
+    let left_distance = cm(quarter * (radius + WHEELS_DISTANCE.to_cm() / 2.0));
+    let right_distance = cm(quarter * (radius - WHEELS_DISTANCE.to_cm() / 2.0));
+
+    // the arc is exact per tick, so a single tick gives the same pose as many
+    drive(&mut chassis, &left, &right, left_distance, right_distance, 1);
+    assert_pose(&chassis, radius, radius, 90.0);
+
+    drive(&mut chassis, &left, &right, left_distance, right_distance, 13);
+    assert_pose(&chassis, 0.0, 2.0 * radius, 180.0);
+}
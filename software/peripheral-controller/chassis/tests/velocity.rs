@@ -0,0 +1,163 @@
+use units::{Length, Velocity, Acceleration, AngularVelocity, AngularAcceleration, Time};
+use motor::SetSpeed;
+use encoder::{Update, GetPosition, GetVelocity};
+use servo::{SetVelocity, Stop, CheckTargetReached, TargetState};
+use chassis::chassis::{Chassis, ChassisSpeed, ChassisState, VelocityLimits, MoveAtomic, AtomicMovement};
+use test_support::MockWheel;
+
+const WHEELS_DISTANCE: Length = Length::from_cm(20.0);
+const TIME_DELTA: Time = Time::from_seconds(0.025);
+const EPSILON: f32 = 1e-3;
+
+const LIMITS: VelocityLimits = VelocityLimits {
+    linear_acceleration: Acceleration::from_cm_per_s2(40.0),
+    angular_acceleration: AngularAcceleration::from_degrees_per_s2(180.0),
+    command_timeout: Time::from_seconds(0.2)
+};
+
+fn chassis() -> (Chassis<MockWheel, MockWheel>, MockWheel, MockWheel) {
+    let (left, right) = (MockWheel::default(), MockWheel::default());
+    let chassis = Chassis::new(left.clone(), right.clone(), WHEELS_DISTANCE).with_velocity_limits(LIMITS);
+    (chassis, left, right)
+}
+
+/// cm/s and degrees/s
+fn speed(linear: f32, angular: f32) -> ChassisSpeed {
+    ChassisSpeed { linear: Velocity::from_cm_per_s(linear), angular: AngularVelocity::from_degrees_per_s(angular) }
+}
+
+fn run(chassis: &mut Chassis<MockWheel, MockWheel>, ticks: u32) {
+    for _ in 0..ticks {
+        chassis.update(TIME_DELTA);
+    }
+}
+
+#[test]
+fn velocity_is_ramped() {
+    let (mut chassis, left, right) = chassis();
+
+    chassis.set_velocity(speed(20.0, 0.0));
+    assert_eq!(chassis.get_target_state(), TargetState::Moving);
+
+    chassis.update(TIME_DELTA);
+    assert!((left.velocity() - 1.0).abs() < EPSILON);
+    assert!((right.velocity() - 1.0).abs() < EPSILON);
+
+    // 0.5 s to reach the velocity, within the command timeout
+    for _ in 0..19 {
+        chassis.set_velocity(speed(20.0, 0.0));
+        chassis.update(TIME_DELTA);
+    }
+    assert!((chassis.get_velocity().unwrap().linear.to_cm_per_s() - 20.0).abs() < EPSILON);
+    assert!((chassis.get_measured_speed().linear.to_cm_per_s() - 20.0).abs() < EPSILON);
+}
+
+#[test]
+fn turning_splits_wheel_speeds() {
+    let (mut chassis, left, right) = chassis();
+
+    // the angular speed is reached within a tick
+    chassis.velocity_limits.angular_acceleration = AngularAcceleration::INFINITY;
+    chassis.velocity_limits.linear_acceleration = Acceleration::INFINITY;
+    chassis.set_velocity(speed(10.0, 90.0));
+    chassis.update(TIME_DELTA);
+
+    let turn = core::f32::consts::FRAC_PI_2 * WHEELS_DISTANCE.to_cm() / 2.0;
+    assert!((left.velocity() - (10.0 + turn)).abs() < EPSILON);
+    assert!((right.velocity() - (10.0 - turn)).abs() < EPSILON);
+    assert!((chassis.get_measured_speed().angular.to_degrees_per_s() - 90.0).abs() < EPSILON);
+}
+
+#[test]
+fn velocity_is_limited_by_chassis_speed() {
+    let (mut chassis, left, _right) = chassis();
+
+    chassis.velocity_limits.linear_acceleration = Acceleration::INFINITY;
+    chassis.set_speed(speed(15.0, 60.0));
+    chassis.set_velocity(speed(-30.0, 0.0));
+    chassis.update(TIME_DELTA);
+
+    assert!((left.velocity() + 15.0).abs() < EPSILON);
+}
+
+#[test]
+fn stops_after_command_timeout() {
+    let (mut chassis, left, right) = chassis();
+
+    chassis.set_velocity(speed(10.0, 0.0));
+    run(&mut chassis, 6);
+    // refreshing the command keeps the robot going
+    chassis.set_velocity(speed(10.0, 0.0));
+    run(&mut chassis, 6);
+    assert!((chassis.get_velocity().unwrap().linear.to_cm_per_s() - 10.0).abs() < EPSILON);
+
+    // after the timeout the robot brakes with the same acceleration limit
+    run(&mut chassis, 3 + 10);
+    assert_eq!(left.velocity.get(), Some(Velocity::ZERO));
+    assert_eq!(right.velocity.get(), Some(Velocity::ZERO));
+    assert_eq!(chassis.get_target_state(), TargetState::Reached);
+
+    // standing still, the velocity mode is left
+    assert!(chassis.get_velocity().is_none());
+    assert_eq!(chassis.get_state(), ChassisState::Idle);
+}
+
+#[test]
+fn position_move_leaves_velocity_mode() {
+    let (mut chassis, left, _right) = chassis();
+
+    chassis.set_velocity(speed(10.0, 0.0));
+    run(&mut chassis, 10);
+
+    chassis.set_speed(speed(25.0, 0.0));
+    let position = left.get_position();
+    chassis.move_atomic(AtomicMovement::Linear(Length::from_cm(5.0)));
+
+    assert!(chassis.get_velocity().is_none());
+    assert_eq!(left.velocity.get(), None);
+    assert_eq!(left.max_speed.get(), Velocity::from_cm_per_s(25.0));
+    assert!(((left.target.get().unwrap() - position).to_cm() - 5.0).abs() < EPSILON);
+}
+
+#[test]
+fn velocity_starts_from_measured_speed() {
+    let (mut chassis, left, right) = chassis();
+
+    // a position move leaves the chassis going at 20 cm/s
+    chassis.move_atomic(AtomicMovement::Linear(Length::ZERO));
+    left.position.set(Length::from_cm(0.5));
+    right.position.set(Length::from_cm(0.5));
+    chassis.update(TIME_DELTA);
+
+    chassis.set_velocity(speed(20.0, 0.0));
+    chassis.update(TIME_DELTA);
+    assert!((left.velocity() - 20.0).abs() < EPSILON);
+}
+
+#[test]
+fn stop_and_halt_are_reported() {
+    let (mut chassis, left, _right) = chassis();
+    assert_eq!(chassis.get_state(), ChassisState::Idle);
+
+    chassis.set_velocity(speed(10.0, 0.0));
+    chassis.update(TIME_DELTA);
+    assert_eq!(chassis.get_state(), ChassisState::Driving);
+
+    chassis.stop();
+    assert_eq!(chassis.get_state(), ChassisState::Stopping);
+    assert!(chassis.get_velocity().is_none());
+    assert_eq!(left.velocity.get(), None);
+    chassis.update(TIME_DELTA);
+    assert_eq!(chassis.get_state(), ChassisState::Idle);
+
+    chassis.move_atomic(AtomicMovement::Linear(Length::from_cm(5.0)));
+    assert_eq!(chassis.get_state(), ChassisState::Moving);
+    chassis.halt();
+    assert_eq!(chassis.get_state(), ChassisState::Halted);
+
+    // a stop does not bring back the control of halted motors
+    chassis.stop();
+    assert_eq!(chassis.get_state(), ChassisState::Halted);
+    chassis.set_velocity(speed(10.0, 0.0));
+    assert_eq!(chassis.get_state(), ChassisState::Driving);
+}
@@ -1,10 +1,19 @@
 #![no_std]
 
+//! Drawers are raised (dumped) and lowered by lead screws. All the drivers share
+//! the direction inputs, each drawer has its own enable, so only one drawer can move at a time.
+
+use core::convert::Infallible;
+
 use embedded_hal::digital::v2::OutputPin;
 
-use motor::SetDirection;
+use units::Time;
+use motor::{SetDirection, RotationDirection};
+use encoder::Update;
 
 pub trait DrawerEnableControl {
+    const COUNT: u8;
+
     fn disable(&mut self, id: u8);
     fn enable(&mut self, id: u8);
 }
@@ -16,6 +25,8 @@ where
     B: OutputPin,
     C: OutputPin
 {
+    const COUNT: u8 = 3;
+
     fn disable(&mut self, id: u8) {
         match id {
             0 => self.0.set_low().ok(),
@@ -35,13 +46,69 @@ where
     }
 }
 
-pub struct Drawers<D, ENS>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawerAction {
+    /// Lifts the drawer above the back wall, so that it is emptied
+    Raise,
+    Lower
+}
+
+/// Limit switches at the ends of the drawer travel
+pub trait DrawerLimits {
+    type Error;
+
+    /// Whether the drawer has reached the end of the given action
+    fn is_at_limit(&mut self, id: u8, action: DrawerAction) -> Result<bool, Self::Error>;
+}
+
+/// Placeholder for drawers without limit switches, which are moved for a fixed time
+pub enum NoLimits {}
+
+impl DrawerLimits for NoLimits {
+    type Error = Infallible;
+
+    fn is_at_limit(&mut self, _id: u8, _action: DrawerAction) -> Result<bool, Self::Error> {
+        match *self {}
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawersState {
+    Idle,
+    Moving { id: u8, action: DrawerAction },
+    Done { id: u8, action: DrawerAction },
+    /// Interrupted by `stop`
+    Stopped { id: u8, action: DrawerAction },
+    /// The limit switch was not reached in time, or could not be read
+    TimedOut { id: u8, action: DrawerAction }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawersError {
+    InvalidDrawer,
+    /// Another drawer is moving
+    Busy
+}
+
+pub struct Drawers<D, ENS, L = NoLimits>
 where
     D: SetDirection,
-    ENS: DrawerEnableControl
+    ENS: DrawerEnableControl,
+    L: DrawerLimits
 {
     direction: D,
-    enables: ENS
+    enables: ENS,
+    limits: Option<L>,
+
+    /// Time a full travel takes, the drawer is stopped after it if there are no limit switches
+    pub travel_time: Time,
+    /// Time the limit switch must be reached within
+    pub timeout: Time,
+    /// Drawers are raised clockwise, unless reversed
+    pub reverse: bool,
+
+    state: DrawersState,
+    elapsed: Time
 }
 
 impl<D, ENS> Drawers<D, ENS>
@@ -49,10 +116,124 @@ where
     D: SetDirection,
     ENS: DrawerEnableControl
 {
-    pub fn new(direction: D, enables: ENS) -> Self {
-        Self {
+    pub fn new(direction: D, enables: ENS, travel_time: Time, timeout: Time, reverse: bool) -> Self {
+        let mut drawers = Self {
             direction,
-            enables
+            enables,
+            limits: None,
+
+            travel_time,
+            timeout,
+            reverse,
+
+            state: DrawersState::Idle,
+            elapsed: Time::ZERO
+        };
+        drawers.cut();
+
+        drawers
+    }
+}
+
+impl<D, ENS, L> Drawers<D, ENS, L>
+where
+    D: SetDirection,
+    ENS: DrawerEnableControl,
+    L: DrawerLimits
+{
+    /// Stops the drawers on the limit switches instead of after the travel time
+    pub fn with_limits<M: DrawerLimits>(self, limits: M) -> Drawers<D, ENS, M> {
+        Drawers {
+            direction: self.direction,
+            enables: self.enables,
+            limits: Some(limits),
+
+            travel_time: self.travel_time,
+            timeout: self.timeout,
+            reverse: self.reverse,
+
+            state: self.state,
+            elapsed: self.elapsed
+        }
+    }
+
+    pub fn get_state(&self) -> DrawersState {
+        self.state
+    }
+
+    pub fn is_busy(&self) -> bool {
+        matches!(self.state, DrawersState::Moving { .. })
+    }
+
+    /// Starts moving the drawer, the previous action of the same drawer may be overridden
+    pub fn raise(&mut self, id: u8) -> Result<(), DrawersError> {
+        self.start(id, DrawerAction::Raise)
+    }
+
+    pub fn lower(&mut self, id: u8) -> Result<(), DrawersError> {
+        self.start(id, DrawerAction::Lower)
+    }
+
+    /// Stops the moving drawer, wherever it is
+    pub fn stop(&mut self) {
+        self.cut();
+        if let DrawersState::Moving { id, action } = self.state {
+            self.state = DrawersState::Stopped { id, action };
+        }
+    }
+
+    fn start(&mut self, id: u8, action: DrawerAction) -> Result<(), DrawersError> {
+        if id >= ENS::COUNT {
+            return Err(DrawersError::InvalidDrawer);
+        }
+        match self.state {
+            DrawersState::Moving { id: moving, .. } if moving != id => return Err(DrawersError::Busy),
+            _ => {}
         }
+
+        self.cut();
+
+        let raise = (action == DrawerAction::Raise) != self.reverse;
+        self.direction.set_direction(if raise { RotationDirection::Clockwise } else { RotationDirection::Counterclockwise });
+        self.enables.enable(id);
+
+        self.state = DrawersState::Moving { id, action };
+        self.elapsed = Time::ZERO;
+
+        Ok(())
+    }
+
+    /// Disables every driver, so that at most one is ever enabled
+    fn cut(&mut self) {
+        self.direction.set_direction(RotationDirection::None);
+        for id in 0..ENS::COUNT {
+            self.enables.disable(id);
+        }
+    }
+}
+
+impl<D, ENS, L> Update for Drawers<D, ENS, L>
+where
+    D: SetDirection,
+    ENS: DrawerEnableControl,
+    L: DrawerLimits
+{
+    fn update(&mut self, time_delta: Time) {
+        let (id, action) = match self.state {
+            DrawersState::Moving { id, action } => (id, action),
+            _ => return
+        };
+        self.elapsed += time_delta;
+
+        self.state = match self.limits {
+            Some(ref mut limits) => match limits.is_at_limit(id, action) {
+                Ok(true) => DrawersState::Done { id, action },
+                Ok(false) if self.elapsed < self.timeout => return,
+                _ => DrawersState::TimedOut { id, action }
+            },
+            None if self.elapsed < self.travel_time => return,
+            None => DrawersState::Done { id, action }
+        };
+        self.cut();
     }
 }
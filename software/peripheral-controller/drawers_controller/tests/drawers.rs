@@ -0,0 +1,129 @@
+use std::{cell::RefCell, convert::Infallible, rc::Rc};
+
+use embedded_hal::digital::v2::OutputPin;
+
+use units::Time;
+use motor::{SetDirection, RotationDirection};
+use encoder::Update;
+use drawers_controller::{Drawers, DrawerLimits, DrawerAction, DrawersState, DrawersError};
+
+const TIME_DELTA: Time = Time::from_seconds(0.1);
+
+#[derive(Default)]
+struct Pins {
+    direction: Option<RotationDirection>,
+    enabled: [bool; 3],
+    at_limit: Option<(u8, DrawerAction)>
+}
+
+type SharedPins = Rc<RefCell<Pins>>;
+
+struct Direction(SharedPins);
+struct Enable(SharedPins, usize);
+struct Limits(SharedPins);
+
+impl SetDirection for Direction {
+    fn set_direction(&mut self, direction: RotationDirection) {
+        self.0.borrow_mut().direction = Some(direction);
+    }
+}
+
+impl OutputPin for Enable {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.borrow_mut().enabled[self.1] = false;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let mut pins = self.0.borrow_mut();
+        pins.enabled[self.1] = true;
+        assert_eq!(pins.enabled.iter().filter(|enabled| **enabled).count(), 1, "more than one drawer is enabled");
+        Ok(())
+    }
+}
+
+impl DrawerLimits for Limits {
+    type Error = Infallible;
+
+    fn is_at_limit(&mut self, id: u8, action: DrawerAction) -> Result<bool, Self::Error> {
+        Ok(self.0.borrow().at_limit == Some((id, action)))
+    }
+}
+
+type TestDrawers = Drawers<Direction, (Enable, Enable, Enable)>;
+
+fn drawers() -> (TestDrawers, SharedPins) {
+    let pins = SharedPins::default();
+    let enables = (Enable(pins.clone(), 0), Enable(pins.clone(), 1), Enable(pins.clone(), 2));
+    (Drawers::new(Direction(pins.clone()), enables, Time::from_seconds(1.0), Time::from_seconds(2.0), false), pins)
+}
+
+fn run(drawers: &mut impl Update, seconds: f32) {
+    for _ in 0..(seconds / TIME_DELTA.to_seconds()).round() as u32 {
+        drawers.update(TIME_DELTA);
+    }
+}
+
+#[test]
+fn drawer_moves_for_travel_time() {
+    let (mut drawers, pins) = drawers();
+    assert_eq!(pins.borrow().direction, Some(RotationDirection::None));
+
+    drawers.raise(1).unwrap();
+    assert_eq!(pins.borrow().enabled, [false, true, false]);
+    assert_eq!(pins.borrow().direction, Some(RotationDirection::Clockwise));
+
+    run(&mut drawers, 0.5);
+    assert_eq!(drawers.get_state(), DrawersState::Moving { id: 1, action: DrawerAction::Raise });
+
+    run(&mut drawers, 0.6);
+    assert_eq!(drawers.get_state(), DrawersState::Done { id: 1, action: DrawerAction::Raise });
+    assert_eq!(pins.borrow().enabled, [false; 3]);
+    assert_eq!(pins.borrow().direction, Some(RotationDirection::None));
+
+    drawers.lower(1).unwrap();
+    assert_eq!(pins.borrow().direction, Some(RotationDirection::Counterclockwise));
+}
+
+#[test]
+fn one_drawer_at_a_time() {
+    let (mut drawers, pins) = drawers();
+
+    drawers.raise(0).unwrap();
+    assert_eq!(drawers.raise(2), Err(DrawersError::Busy));
+    assert_eq!(drawers.lower(3), Err(DrawersError::InvalidDrawer));
+
+    // the same drawer may be sent back at once
+    drawers.lower(0).unwrap();
+    assert_eq!(pins.borrow().enabled, [true, false, false]);
+
+    drawers.stop();
+    assert_eq!(drawers.get_state(), DrawersState::Stopped { id: 0, action: DrawerAction::Lower });
+    assert_eq!(pins.borrow().enabled, [false; 3]);
+
+    drawers.raise(2).unwrap();
+    assert_eq!(pins.borrow().enabled, [false, false, true]);
+}
+
+#[test]
+fn limit_switch_ends_the_movement() {
+    let (drawers, pins) = drawers();
+    let mut drawers = drawers.with_limits(Limits(pins.clone()));
+
+    drawers.lower(2).unwrap();
+    // with the switches the travel time no longer matters
+    run(&mut drawers, 1.5);
+    assert!(drawers.is_busy());
+
+    pins.borrow_mut().at_limit = Some((2, DrawerAction::Lower));
+    drawers.update(TIME_DELTA);
+    assert_eq!(drawers.get_state(), DrawersState::Done { id: 2, action: DrawerAction::Lower });
+    assert_eq!(pins.borrow().enabled, [false; 3]);
+
+    drawers.raise(2).unwrap();
+    run(&mut drawers, 2.0);
+    assert_eq!(drawers.get_state(), DrawersState::TimedOut { id: 2, action: DrawerAction::Raise });
+    assert_eq!(pins.borrow().enabled, [false; 3]);
+}
@@ -2,8 +2,10 @@
 #![feature(trait_alias)]
 #![feature(associated_type_defaults)]
 
+use units::{Time, Angle, AngularVelocity};
+
 pub trait Update {
-    fn update(&mut self, time_delta_seconds: f32);
+    fn update(&mut self, time_delta: Time);
 }
 
 pub trait GetPosition {
@@ -13,8 +15,10 @@ pub trait GetPosition {
 }
 
 pub trait GetVelocity {
-    fn get_velocity(&self) -> f32;
-}
+    type Velocity = f32;
 
-pub trait Encoder = Update + GetPosition + GetVelocity;
+    fn get_velocity(&self) -> Self::Velocity;
+}
 
+/// Measures the rotation of a shaft
+pub trait Encoder = Update + GetPosition<Position = Angle> + GetVelocity<Velocity = AngularVelocity>;
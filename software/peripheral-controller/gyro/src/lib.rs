@@ -0,0 +1,8 @@
+#![no_std]
+
+pub trait ReadYawRate {
+    type Error;
+
+    /// Angular velocity around the vertical axis, in degrees per second
+    fn read_yaw_rate(&mut self) -> Result<f32, Self::Error>;
+}
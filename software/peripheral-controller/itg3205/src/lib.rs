@@ -13,6 +13,8 @@ use hal::blocking::{i2c::{Write, WriteRead}, delay::DelayMs};
 
 use micromath::vector::{F32x3, I16x3};
 
+use gyro::ReadYawRate;
+
 pub const ADDRESS: u8 = 0x68;
 
 const LSB_DEG: f32 = 14.375; // TODO: figure out what is this
@@ -89,3 +91,14 @@ where
     }
 }
 
+impl<I2C, E> ReadYawRate for Itg3205<I2C>
+where
+    I2C: WriteRead<Error = E> + Write<Error = E>,
+    E: Debug,
+{
+    type Error = E;
+
+    fn read_yaw_rate(&mut self) -> Result<f32, Self::Error> {
+        Ok(self.read()?.z)
+    }
+}
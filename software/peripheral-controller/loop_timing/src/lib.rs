@@ -0,0 +1,68 @@
+#![no_std]
+
+//! Statistics of the measured control loop period, reported over the telemetry
+
+use units::Time;
+
+/// A period longer than this many nominal ones has missed its deadline
+const OVERRUN_FACTOR: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopTimingReport {
+    pub min: Time,
+    pub max: Time,
+    pub mean: Time,
+    pub overruns: u8
+}
+
+pub struct LoopTiming {
+    /// Nominal period
+    pub period: Time,
+
+    min: Time,
+    max: Time,
+    sum: Time,
+    count: u32,
+    overruns: u8
+}
+
+impl LoopTiming {
+    pub fn new(period: Time) -> Self {
+        Self {
+            period,
+
+            min: Time::INFINITY,
+            max: Time::ZERO,
+            sum: Time::ZERO,
+            count: 0,
+            overruns: 0
+        }
+    }
+
+    pub fn record(&mut self, period: Time) {
+        self.min = self.min.min(period);
+        self.max = self.max.max(period);
+        self.sum += period;
+        self.count += 1;
+
+        if period > self.period * OVERRUN_FACTOR {
+            self.overruns = self.overruns.saturating_add(1);
+        }
+    }
+
+    /// Statistics since the last report, all zero without any period recorded
+    pub fn take_report(&mut self) -> LoopTimingReport {
+        let report = match self.count {
+            0 => LoopTimingReport { min: Time::ZERO, max: Time::ZERO, mean: Time::ZERO, overruns: 0 },
+            count => LoopTimingReport {
+                min: self.min,
+                max: self.max,
+                mean: self.sum / count as f32,
+                overruns: self.overruns
+            }
+        };
+        *self = Self::new(self.period);
+
+        report
+    }
+}
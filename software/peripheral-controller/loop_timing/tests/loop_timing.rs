@@ -0,0 +1,68 @@
+use units::Time;
+use loop_timing::{LoopTiming, LoopTimingReport};
+
+const PERIOD: Time = Time::from_millis(25.0);
+
+/// Compares the report in milliseconds
+fn assert_report(report: LoopTimingReport, (min, max, mean, overruns): (f32, f32, f32, u8)) {
+    assert!((report.min.to_millis() - min).abs() < 1e-3, "{:?}", report);
+    assert!((report.max.to_millis() - max).abs() < 1e-3, "{:?}", report);
+    assert!((report.mean.to_millis() - mean).abs() < 1e-3, "{:?}", report);
+    assert_eq!(report.overruns, overruns);
+}
+
+#[test]
+fn empty_report_is_zero() {
+    let mut timing = LoopTiming::new(PERIOD);
+
+    assert_eq!(timing.take_report(), LoopTimingReport { min: Time::ZERO, max: Time::ZERO, mean: Time::ZERO, overruns: 0 });
+}
+
+#[test]
+fn first_sample_is_min_max_and_mean() {
+    let mut timing = LoopTiming::new(PERIOD);
+    timing.record(Time::from_millis(26.0));
+
+    assert_report(timing.take_report(), (26.0, 26.0, 26.0, 0));
+}
+
+#[test]
+fn tracks_min_max_and_mean() {
+    let mut timing = LoopTiming::new(PERIOD);
+    for millis in [25.0, 24.0, 27.0, 24.0] {
+        timing.record(Time::from_millis(millis));
+    }
+
+    assert_report(timing.take_report(), (24.0, 27.0, 25.0, 0));
+}
+
+#[test]
+fn counts_overruns() {
+    let mut timing = LoopTiming::new(PERIOD);
+    // 1.5 periods is still on time
+    for millis in [25.0, 37.5, 38.0, 25.0, 60.0] {
+        timing.record(Time::from_millis(millis));
+    }
+
+    assert_report(timing.take_report(), (25.0, 60.0, 37.1, 2));
+}
+
+#[test]
+fn overruns_saturate() {
+    let mut timing = LoopTiming::new(PERIOD);
+    for _ in 0..300 {
+        timing.record(Time::from_millis(50.0));
+    }
+
+    assert_eq!(timing.take_report().overruns, u8::MAX);
+}
+
+#[test]
+fn report_starts_over() {
+    let mut timing = LoopTiming::new(PERIOD);
+    timing.record(Time::from_millis(40.0));
+    timing.take_report();
+
+    timing.record(Time::from_millis(20.0));
+    assert_report(timing.take_report(), (20.0, 20.0, 20.0, 0));
+}
@@ -1,7 +1,8 @@
 #![no_main]
 #![no_std]
 
-use panic_probe as _;
+mod safety;
+mod tuning;
 
 macro_rules! wheel_alias {
     ($name:ident, $dir_1_pin:ident, $dir_2_pin:ident, $pwm_timer:ident, $pwm_chan:ident, $qei_pin_1:ident, $qei_pin_2:ident, $qei_tim:ident, $qei_af:literal) => {
@@ -34,41 +35,49 @@ macro_rules! wheel_alias {
 
 #[rtic::app(device = stm32f4xx_hal::pac, peripherals = true, dispatchers = [USART1])]
 mod app {
-    use core::fmt::Write;
-
     use rtt_target::{rtt_init, set_print_channel, rprintln};
+    use embedded_hal::watchdog::{Watchdog, WatchdogEnable};
 
     use stm32f4xx_hal::{
         prelude::*,
         pac, pac::{TIM1, TIM3, TIM5, USART2, I2C1},
         gpio::{
             gpioa::{PA0, PA1, PA12},
-            gpiob::{PB3, PB4, PB5, PB10, PB6, PB8, PB9},
+            gpiob::{PB3, PB4, PB5, PB10, PB6, PB8, PB9, PB13},
             gpioc::{PC5, PC6, PC7, PC8, PC9},
             Output, PushPull, Alternate, OpenDrain
         },
         delay::Delay,
+        watchdog::IndependentWatchdog,
+        time::MilliSeconds,
         timer::{monotonic::MonoTimer, Timer},
         pwm::{PwmChannel, C1, C2},
         qei::Qei,
-        serial, serial::Serial, i2c, i2c::I2c
+        serial, serial::{Serial, config::Config as SerialConfig}, i2c, i2c::I2c
     };
     use shared_bus_rtic::SharedBus;
 
     use pid::Pid;
     use adxl343::{Adxl343, accelerometer::Accelerometer};
 
+    use units::{Length, Angle, Time, Velocity, AngularVelocity, Acceleration, AngularAcceleration};
     use motor::{Motor, SetSpeed};
     use dc_motor::{TwoPinSetDirection, PwmSetSpeed};
     use encoder::*;
     use rotary_encoder::RotaryEncoder;
     use wheel::Wheel;
-    use servo::Servo;
-    use chassis::{chassis::{Chassis, ChassisPosition, ChassisSpeed}, movement_controller::{MovementController, MoveRelative}};
+    use servo::{Servo, CompletionCriteria, SetVelocity, Stop, TargetState};
+    use chassis::{chassis::{Chassis, ChassisPosition, ChassisSpeed, VelocityLimits}, heading::HeadingEstimator, movement_controller::{MovementController, MoveRelative, MoveTo, MovementStage, ControllerState}};
     use itg3205::Itg3205;
-    use drawers_controller::Drawers;
+    use drawers_controller::{Drawers, DrawersState, DrawersError, DrawerAction};
+    use sequencer::{Sequencer, SequencerState, SortFailure, StepTimeouts, NoClaw, NoCarriage};
+    use autotune::{RelayAutotune, RelayPlant};
+    use loop_timing::LoopTiming;
+    use crate::{safety, tuning::Tuning};
+    use protocol::{Command, Reply, Param, ErrorCode, Stage, MotionState, ActionStatus, AutotuneTarget, FrameDecoder, encode_frame, MAX_FRAME_LEN, link::{LinkSupervisor, LinkLost}};
 
     type OutPP = Output<PushPull>;
+    type Instant = fugit::TimerInstantU32<1_000_000>;
 
      #[monotonic(binds = TIM2, default = true)]
     type MicrosecMono = MonoTimer<pac::TIM2, 1_000_000>;
@@ -94,7 +103,7 @@ mod app {
         pub type AccelerometerT = Adxl343<i2c_bus::BusT>;
         pub type GyroT = Itg3205<i2c_bus::BusT>;
 
-        pub struct Gy85(pub AccelerometerT, pub GyroT);
+        pub struct Gy85(pub AccelerometerT);
     }
 
     mod drawers {
@@ -106,32 +115,93 @@ mod app {
     }
 
     type SerialT = serial::Tx<USART2>;
+    type SerialRxT = serial::Rx<USART2>;
 
-    const WHEEL_RADIUS: f32 = 37.0;
+    const WHEEL_RADIUS: Length = Length::from_mm(37.0);
     const WHEEL_MIN_SPEED_PERCENT: u8 = 25;
-    const WHEEL_MAX_ROTARY_SPEED: f32 =  1.4;
+    const WHEEL_MAX_ROTARY_SPEED: AngularVelocity = AngularVelocity::from_rps(1.4);
     const WHEEL_ENCODER_PPR: f32 = 1440.0;
 
-    const SERVO_MAX_DISTANCE: f32 = 20_00.0;
-    const SERVO_MAX_TARRGET_DISTANCE: f32 = 1.0;
-
-    const WHEELS_DISTANCE: f32 = 17.0;
+    /// The servo and velocity constants were tuned when the wheel travel was counted in wheel radii
+    /// per revolution, without the 2 pi, so one of those units is 2 pi mm
+    const TUNED_UNIT_MM: f32 = 2.0 * core::f32::consts::PI;
+    /// The default linear speed, as a part of the top speed of the wheels, which was 45 of 51.8 units/s
+    const CRUISE_SPEED_RATIO: f32 = 45.0 / 51.8;
+    const CRUISE_ANGULAR_SPEED: AngularVelocity = AngularVelocity::from_degrees_per_s(60.0);
+
+    const SERVO_MAX_DISTANCE: Length = Length::from_mm(2000.0 * TUNED_UNIT_MM);
+    const SERVO_MAX_TARRGET_DISTANCE: Length = Length::from_mm(1.0 * TUNED_UNIT_MM);
+    const SERVO_MAX_ACCELERATION: Acceleration = Acceleration::from_m_per_s2(30.0 * TUNED_UNIT_MM / 1000.0);
+    const SERVO_SETTLED_SPEED: Velocity = Velocity::from_m_per_s(0.5 * TUNED_UNIT_MM / 1000.0);
+    const SERVO_SETTLE_TIME: Time = Time::from_seconds(0.1);
+    const SERVO_TIMEOUT: Time = Time::from_seconds(2.0);
+    const SERVO_STALL_SPEED: Velocity = Velocity::from_m_per_s(10.0 * TUNED_UNIT_MM / 1000.0);
+    const SERVO_STALL_TIME: Time = Time::from_seconds(0.5);
+
+    const WHEELS_DISTANCE: Length = Length::from_cm(17.0);
+
+    const VELOCITY_LINEAR_ACCELERATION: Acceleration = Acceleration::from_m_per_s2(30.0 * TUNED_UNIT_MM / 1000.0);
+    const VELOCITY_ANGULAR_ACCELERATION: AngularAcceleration = AngularAcceleration::from_degrees_per_s2(90.0);
+    const VELOCITY_COMMAND_TIMEOUT: Time = Time::from_seconds(0.5);
+
+    const UPDATE_PERIOD_MICROS: u32 = 25_000;
+    const UPDATE_PERIOD: Time = Time::from_millis(UPDATE_PERIOD_MICROS as f32 / 1000.0);
+    /// Loop timing telemetry is sent every this many position reports
+    const LOOP_TIMING_REPORT_INTERVAL: u8 = 40;
+
+    /// The controller is reset when the control loop stalls for longer
+    const WATCHDOG_TIMEOUT_MS: u32 = 250;
+
+    /// Everything is stopped when the host is silent for longer
+    const LINK_TIMEOUT: Time = Time::from_seconds(0.5);
+
+    const DRAWER_TRAVEL_TIME: Time = Time::from_seconds(3.0);
+    const DRAWER_TIMEOUT: Time = Time::from_seconds(5.0);
+    /// A bin per drawer
+    const BIN_COUNT: usize = 3;
+
+    const GYRO_WEIGHT: f32 = 0.98;
+    const GYRO_BIAS_TIME_CONSTANT: Time = Time::from_seconds(5.0);
+
+    /// The wheel speed loop is oscillated around this percent of the maximum speed,
+    /// with the duty switching by the amplitude around the bias, percent
+    const WHEEL_AUTOTUNE_SETPOINT: f32 = 40.0;
+    const WHEEL_AUTOTUNE_BIAS: f32 = 55.0;
+    const WHEEL_AUTOTUNE_AMPLITUDE: f32 = 20.0;
+    const WHEEL_AUTOTUNE_HYSTERESIS: f32 = 3.0;
+    /// The servo position loop is oscillated around where it stands, in fractions of
+    /// the maximum position and of the maximum wheel speed
+    const SERVO_AUTOTUNE_AMPLITUDE: f32 = 0.3;
+    const SERVO_AUTOTUNE_HYSTERESIS: f32 = 0.0005;
+    const AUTOTUNE_TIMEOUT: Time = Time::from_seconds(30.0);
+
+    type ChassisT = MovementController<
+        Chassis<
+            Servo<left_wheel::MotorT, left_wheel::EncoderT>,
+            Servo<right_wheel::MotorT, right_wheel::EncoderT>,
+            gy85::GyroT
+        >>;
+
+    /// The claw and the carriage have no pins assigned yet, `Sort` is refused until they replace the placeholders
+    type SequencerT = Sequencer<NoClaw, NoCarriage<BIN_COUNT>, drawers::DrawersT>;
 
     #[shared]
     struct Shared {
-        chassis: MovementController<
-            Chassis<
-                Servo<left_wheel::MotorT, left_wheel::EncoderT>,
-                Servo<right_wheel::MotorT, right_wheel::EncoderT>
-            >>,
+        chassis: ChassisT,
+        sequencer: SequencerT,
+        link: LinkSupervisor,
+        timing: LoopTiming,
+        tuning: Tuning,
         serial: SerialT,
         gy85: gy85::Gy85
     }
 
     #[local]
     struct Local {
-        x: f32,
-        y: f32
+        serial_rx: SerialRxT,
+        decoder: FrameDecoder,
+        speed: ChassisSpeed,
+        watchdog: IndependentWatchdog
     }
 
     #[init]
@@ -153,6 +223,9 @@ mod app {
             };
         set_print_channel(channels.up.0);
 
+        let reset_cause = safety::take_reset_cause(&ctx.device.RCC);
+        rprintln!("reset cause: {:?}", reset_cause);
+
         let rcc = ctx.device.RCC.constrain();
         let clocks = rcc.cfgr.sysclk(84.mhz()).freeze();
 
@@ -160,8 +233,11 @@ mod app {
         let gpiob = ctx.device.GPIOB.split();
         let gpioc = ctx.device.GPIOC.split();
 
-        let tx_pin = gpioa.pa2.into_alternate();
-        let mut serial = Serial::tx(ctx.device.USART2, tx_pin, 115200.bps(), &clocks).unwrap();
+        let (tx_pin, rx_pin) = (gpioa.pa2.into_alternate(), gpioa.pa3.into_alternate());
+        let mut serial = Serial::new(ctx.device.USART2, (tx_pin, rx_pin),
+                                     SerialConfig::default().baudrate(115200.bps()), &clocks).unwrap();
+        serial.listen(serial::Event::Rxne);
+        let (serial, serial_rx) = serial.split();
 
         let mut delay = Delay::new(ctx.core.SYST, &clocks);
 
@@ -195,6 +271,14 @@ mod app {
                                1.0, 0.1, 1.0,
                                1.0,
                                0.0);
+            let completion_criteria = CompletionCriteria {
+                position_tolerance: SERVO_MAX_TARRGET_DISTANCE,
+                velocity_tolerance: SERVO_SETTLED_SPEED,
+                settle_time: SERVO_SETTLE_TIME,
+                timeout: SERVO_TIMEOUT,
+                stall_speed: SERVO_STALL_SPEED,
+                stall_time: SERVO_STALL_TIME
+            };
 
             ({
                 let (in_1, in_2) = (gpiob.pb10.into_push_pull_output(), gpiob.pb3.into_push_pull_output());
@@ -206,11 +290,11 @@ mod app {
                 let encoder_pins = (gpiob.pb4.into_alternate(), gpiob.pb5.into_alternate());
                 let encoder_timer = ctx.device.TIM3;
                 let qei = Qei::new(encoder_timer, encoder_pins);
-                let encoder = RotaryEncoder::new(qei, WHEEL_ENCODER_PPR, true);
+                let encoder = RotaryEncoder::new(qei, WHEEL_ENCODER_PPR, false);
 
                 let wheel = Wheel::new(motor, encoder, speed_pid.clone(), WHEEL_MAX_ROTARY_SPEED, WHEEL_RADIUS);
 
-                Servo::new(wheel, position_pid, SERVO_MAX_DISTANCE, SERVO_MAX_TARRGET_DISTANCE)
+                Servo::new(wheel, position_pid, SERVO_MAX_DISTANCE, completion_criteria, SERVO_MAX_ACCELERATION)
             },
             {
                 let (in_1, in_2) = (gpioc.pc7.into_push_pull_output(), gpiob.pb6.into_push_pull_output());
@@ -222,78 +306,409 @@ mod app {
                 let encoder_pins = (gpioa.pa0.into_alternate(), gpioa.pa1.into_alternate());
                 let encoder_timer = ctx.device.TIM5;
                 let qei = Qei::new(encoder_timer, encoder_pins);
-                let encoder = RotaryEncoder::new(qei, WHEEL_ENCODER_PPR, true);
+                let encoder = RotaryEncoder::new(qei, WHEEL_ENCODER_PPR, false);
 
                 let wheel = Wheel::new(motor, encoder, speed_pid.clone(), WHEEL_MAX_ROTARY_SPEED, WHEEL_RADIUS);
 
-                Servo::new(wheel, position_pid, SERVO_MAX_DISTANCE, SERVO_MAX_TARRGET_DISTANCE)
+                Servo::new(wheel, position_pid, SERVO_MAX_DISTANCE, completion_criteria, SERVO_MAX_ACCELERATION)
             })
         };
 
-        let chassis = Chassis::new(left_wheel, right_wheel, WHEELS_DISTANCE);
+        // the gyro z axis points up, so its rate grows counterclockwise while the heading grows clockwise
+        let heading_estimator = HeadingEstimator::new(gyro, GYRO_WEIGHT, GYRO_BIAS_TIME_CONSTANT, true);
+        let velocity_limits = VelocityLimits {
+            linear_acceleration: VELOCITY_LINEAR_ACCELERATION,
+            angular_acceleration: VELOCITY_ANGULAR_ACCELERATION,
+            command_timeout: VELOCITY_COMMAND_TIMEOUT
+        };
+        let chassis = Chassis::new(left_wheel, right_wheel, WHEELS_DISTANCE)
+            .with_velocity_limits(velocity_limits)
+            .with_heading_estimator(heading_estimator);
         let mut chassis = MovementController::new(chassis);
-        chassis.set_speed(ChassisSpeed { linear: 45.0, angular: 60.0 } );
+        let speed = ChassisSpeed {
+            linear: WHEEL_MAX_ROTARY_SPEED.to_tangential(WHEEL_RADIUS) * CRUISE_SPEED_RATIO,
+            angular: CRUISE_ANGULAR_SPEED
+        };
+        chassis.set_speed(speed);
+
+        // there are no limit switches on the drawers yet, so they are moved for a fixed time
+        let drawers = {
+            let direction = TwoPinSetDirection::new(gpiob.pb13.into_push_pull_output(), gpioc.pc8.into_push_pull_output());
+            let enables = (
+                gpioc.pc6.into_push_pull_output(),
+                gpioc.pc5.into_push_pull_output(),
+                gpioa.pa12.into_push_pull_output()
+            );
+
+            Drawers::new(direction, enables, DRAWER_TRAVEL_TIME, DRAWER_TIMEOUT, false)
+        };
+        let sequencer = Sequencer::new(NoClaw, NoCarriage, drawers, StepTimeouts::default());
 
         let mono = Timer::new(ctx.device.TIM2, &clocks).monotonic();
 
+        // started last, the calibration above takes longer than the timeout
+        let mut watchdog = IndependentWatchdog::new(ctx.device.IWDG);
+        watchdog.stop_on_debug(&ctx.device.DBGMCU, true);
+        watchdog.start(MilliSeconds(WATCHDOG_TIMEOUT_MS));
+
         updater::spawn().ok();
-        position_updater::spawn().ok();
         printer::spawn().ok();
 
         (
             Shared {
                 serial,
                 chassis,
-                gy85: gy85::Gy85(accel, gyro)
+                sequencer,
+                link: LinkSupervisor::new(LINK_TIMEOUT),
+                timing: LoopTiming::new(UPDATE_PERIOD),
+                tuning: Tuning::new(UPDATE_PERIOD),
+                gy85: gy85::Gy85(accel)
             },
             Local {
-                x: 0.0, y: 0.0
+                serial_rx,
+                decoder: FrameDecoder::new(),
+                speed,
+                watchdog
             },
             init::Monotonics(mono),
         )
     }
 
-    #[task(shared = [serial, chassis])]
+    fn send_reply(serial: &mut SerialT, sequence: u8, reply: Reply) {
+        let mut frame = [0_u8; MAX_FRAME_LEN];
+        if let Ok(len) = encode_frame(sequence, &reply, &mut frame) {
+            serial.bwrite_all(&frame[..len]).ok();
+        }
+    }
+
+    #[task(shared = [serial, chassis, timing, tuning], local = [reports: u8 = 0])]
     fn printer(cx: printer::Context){
         let serial = cx.shared.serial;
         let chassis = cx.shared.chassis;
+        let timing = cx.shared.timing;
+        let tuning = cx.shared.tuning;
+        let reports = cx.local.reports;
 
-        (serial, chassis).lock(|serial, chassis| {
+        (serial, chassis, timing, tuning).lock(|serial, chassis, timing, tuning| {
             let position = chassis.get_position();
             rprintln!("{:?}", position);
-            writeln!(serial, "{} {} {}", position.linear.0, position.linear.1, position.angular).unwrap();
+            send_reply(serial, 0, Reply::Position {
+                x: position.linear.0.to_cm(), y: position.linear.1.to_cm(), angle: position.angular.to_degrees()
+            });
+
+            *reports += 1;
+            if *reports == LOOP_TIMING_REPORT_INTERVAL {
+                *reports = 0;
+
+                let report = timing.take_report();
+                send_reply(serial, 0, Reply::LoopTiming {
+                    min: report.min.to_seconds(), max: report.max.to_seconds(), mean: report.mean.to_seconds(), overruns: report.overruns
+                });
+            }
+
+            if let Some(reply) = tuning.take_finished() {
+                send_reply(serial, 0, reply);
+            }
         });
 
         printer::spawn_after(25.millis()).ok();
     }
 
-    #[task(shared = [chassis], local = [x, y])]
-    fn position_updater(mut cx: position_updater::Context) {
-        let (x, y) = (cx.local.x, cx.local.y);
-        let new_position = ChassisPosition {
-            linear: (*x * 200.0, *y * 200.0),
-            angular: 90.0
+    #[task(binds = USART2, shared = [serial, link], local = [serial_rx, decoder])]
+    fn receiver(mut cx: receiver::Context) {
+        while let Ok(byte) = cx.local.serial_rx.read() {
+            let reply = match cx.local.decoder.push::<Command>(byte) {
+                Some(Ok((sequence, command))) => match cx.shared.link.lock(|link| link.feed()) {
+                    Ok(()) => handle_command::spawn(sequence, command).err()
+                        .map(|_| (sequence, Reply::Error(ErrorCode::Busy))),
+                    Err(LinkLost) => Some((sequence, Reply::Error(ErrorCode::LinkLost)))
+                },
+                Some(Err(_)) => Some((0, Reply::Error(ErrorCode::InvalidFrame))),
+                None => None
+            };
+
+            if let Some((sequence, reply)) = reply {
+                cx.shared.serial.lock(|serial| send_reply(serial, sequence, reply));
+            }
+        }
+    }
+
+    fn drawers_reply(result: Result<(), DrawersError>) -> Reply {
+        match result {
+            Ok(()) => Reply::Ack,
+            Err(DrawersError::InvalidDrawer) => Reply::Error(ErrorCode::InvalidArgument),
+            Err(DrawersError::Busy) => Reply::Error(ErrorCode::Busy)
+        }
+    }
+
+    fn drawers_state_reply(state: DrawersState) -> Reply {
+        let (id, action, status) = match state {
+            DrawersState::Idle => (0, DrawerAction::Lower, ActionStatus::Idle),
+            DrawersState::Moving { id, action } => (id, action, ActionStatus::Moving),
+            DrawersState::Done { id, action } => (id, action, ActionStatus::Done),
+            DrawersState::Stopped { id, action } => (id, action, ActionStatus::Stopped),
+            DrawersState::TimedOut { id, action } => (id, action, ActionStatus::TimedOut),
         };
 
-        *x += 0.1; *y += 0.1;
-        if *x >= 1.0 { *x = 0.0; }
-        if *y >= 1.0 { *y = 0.0; }
+        Reply::DrawerState { id, open: action == DrawerAction::Raise, status }
+    }
 
-        cx.shared.chassis.lock(|chassis| {
-            chassis.move_relative(new_position);
-        });
+    fn sort_step(step: sequencer::SortStep) -> protocol::SortStep {
+        match step {
+            sequencer::SortStep::Grab => protocol::SortStep::Grab,
+            sequencer::SortStep::Lift => protocol::SortStep::Lift,
+            sequencer::SortStep::SelectBin => protocol::SortStep::SelectBin,
+            sequencer::SortStep::Dump => protocol::SortStep::Dump,
+            sequencer::SortStep::Return => protocol::SortStep::Return,
+        }
+    }
+
+    fn sort_state_reply(state: SequencerState) -> Reply {
+        let (bin, step, status) = match state {
+            SequencerState::Idle => (0, protocol::SortStep::Idle, ActionStatus::Idle),
+            SequencerState::Running { bin, step } => (bin, sort_step(step), ActionStatus::Moving),
+            SequencerState::Done { bin } => (bin, protocol::SortStep::Return, ActionStatus::Done),
+            SequencerState::Stopped { bin, step } => (bin, sort_step(step), ActionStatus::Stopped),
+            SequencerState::Failed { bin, step, failure: SortFailure::TimedOut } => (bin, sort_step(step), ActionStatus::TimedOut),
+            SequencerState::Failed { bin, step, .. } => (bin, sort_step(step), ActionStatus::Failed),
+        };
+
+        Reply::SortState { bin, step, status }
+    }
+
+    /// The protocol carries lengths in cm and angles in degrees
+    fn chassis_position(x: f32, y: f32, angle: f32) -> ChassisPosition {
+        ChassisPosition { linear: (Length::from_cm(x), Length::from_cm(y)), angular: Angle::from_degrees(angle) }
+    }
+
+    /// The protocol carries speeds in cm/s and degrees/s
+    fn chassis_speed(linear: f32, angular: f32) -> ChassisSpeed {
+        ChassisSpeed { linear: Velocity::from_cm_per_s(linear), angular: AngularVelocity::from_degrees_per_s(angular) }
+    }
 
-        position_updater::spawn_after(15_000.millis()).ok();
+    /// Starts the relay on one loop, the chassis is halted first, so that the other loops hold still
+    fn start_tuning(chassis: &mut ChassisT, tuning: &mut Tuning, target: AutotuneTarget, rule: protocol::TuningRule) {
+        chassis.halt();
+
+        let wheel_relay = RelayAutotune::new(WHEEL_AUTOTUNE_AMPLITUDE, WHEEL_AUTOTUNE_HYSTERESIS, AUTOTUNE_TIMEOUT);
+        let servo_relay = RelayAutotune::new(SERVO_AUTOTUNE_AMPLITUDE, SERVO_AUTOTUNE_HYSTERESIS, AUTOTUNE_TIMEOUT);
+        let chassis = chassis.get_controlled_mut();
+        match target {
+            AutotuneTarget::LeftWheel => {
+                chassis.get_left_mut().get_wheel_mut().enable();
+                tuning.start(target, rule, wheel_relay, WHEEL_AUTOTUNE_SETPOINT, WHEEL_AUTOTUNE_BIAS);
+            },
+            AutotuneTarget::RightWheel => {
+                chassis.get_right_mut().get_wheel_mut().enable();
+                tuning.start(target, rule, wheel_relay, WHEEL_AUTOTUNE_SETPOINT, WHEEL_AUTOTUNE_BIAS);
+            },
+            AutotuneTarget::LeftServo => {
+                let setpoint = chassis.get_left_mut().get_measurement();
+                tuning.start(target, rule, servo_relay, setpoint, 0.0);
+            },
+            AutotuneTarget::RightServo => {
+                let setpoint = chassis.get_right_mut().get_measurement();
+                tuning.start(target, rule, servo_relay, setpoint, 0.0);
+            },
+        }
+    }
+
+    /// Drives the tuned loop instead of the chassis, the odometry catches up once the tuning ends,
+    /// which leaves the chassis halted
+    fn update_tuning(chassis: &mut ChassisT, tuning: &mut Tuning, time_delta: Time) {
+        let controlled = chassis.get_controlled_mut();
+        match tuning.get_target() {
+            AutotuneTarget::LeftWheel => tuning.update(controlled.get_left_mut().get_wheel_mut(), time_delta),
+            AutotuneTarget::RightWheel => tuning.update(controlled.get_right_mut().get_wheel_mut(), time_delta),
+            AutotuneTarget::LeftServo => tuning.update(controlled.get_left_mut(), time_delta),
+            AutotuneTarget::RightServo => tuning.update(controlled.get_right_mut(), time_delta),
+        }
+
+        if !tuning.is_running() {
+            chassis.halt();
+        }
     }
 
-    #[task(shared = [chassis])]
-    fn updater(mut cx: updater::Context) {
-        const TIME_DELTA_SECONDS: f32 = 0.025;
+    /// Leaves the chassis halted, as when the tuning ends
+    fn cancel_tuning(chassis: &mut ChassisT, tuning: &mut Tuning) {
+        if !tuning.is_running() {
+            return;
+        }
+
+        let controlled = chassis.get_controlled_mut();
+        match tuning.get_target() {
+            AutotuneTarget::LeftWheel => tuning.cancel(controlled.get_left_mut().get_wheel_mut()),
+            AutotuneTarget::RightWheel => tuning.cancel(controlled.get_right_mut().get_wheel_mut()),
+            AutotuneTarget::LeftServo => tuning.cancel(controlled.get_left_mut()),
+            AutotuneTarget::RightServo => tuning.cancel(controlled.get_right_mut()),
+        }
+        chassis.halt();
+    }
+
+    #[task(capacity = 4, shared = [serial, chassis, sequencer, tuning], local = [speed])]
+    fn handle_command(cx: handle_command::Context, sequence: u8, command: Command) {
+        let serial = cx.shared.serial;
+        let chassis = cx.shared.chassis;
+        let sequencer = cx.shared.sequencer;
+        let tuning = cx.shared.tuning;
+        let speed = cx.local.speed;
+
+        (serial, chassis, sequencer, tuning).lock(|serial, chassis, sequencer, tuning| {
+            let moves = matches!(command,
+                Command::MoveRelative { .. } | Command::MoveTo { .. } | Command::SetVelocity { .. } |
+                Command::Resume | Command::Autotune { .. });
+            if moves && tuning.is_running() {
+                send_reply(serial, sequence, Reply::Error(ErrorCode::Busy));
+                return;
+            }
 
-        cx.shared.chassis.lock(|chassis| {
-            chassis.update(TIME_DELTA_SECONDS);
+            let reply = match command {
+                Command::MoveRelative { x, y, angle } => {
+                    match chassis.move_relative(chassis_position(x, y, angle)) {
+                        Ok(()) => Reply::Ack,
+                        Err(_) => Reply::Error(ErrorCode::Busy)
+                    }
+                },
+                Command::MoveTo { x, y, angle } => {
+                    match chassis.move_to(chassis_position(x, y, angle)) {
+                        Ok(()) => Reply::Ack,
+                        Err(_) => Reply::Error(ErrorCode::Busy)
+                    }
+                },
+                Command::ClearQueue => {
+                    chassis.clear_queue();
+                    Reply::Ack
+                },
+                Command::GetProgress => {
+                    match chassis.get_progress() {
+                        Some(progress) => Reply::Progress {
+                            stage: match progress.stage {
+                                MovementStage::InitialRotation => Stage::InitialRotation,
+                                MovementStage::Translation => Stage::Translation,
+                                MovementStage::FinalRotation => Stage::FinalRotation,
+                            },
+                            queued: progress.queued as u8,
+                            distance: progress.distance.to_cm()
+                        },
+                        None => Reply::Progress { stage: Stage::Idle, queued: 0, distance: 0.0 }
+                    }
+                },
+                Command::Halt => {
+                    cancel_tuning(chassis, tuning);
+                    chassis.halt();
+                    sequencer.halt();
+                    Reply::Ack
+                },
+                Command::Pause => {
+                    chassis.pause();
+                    Reply::Ack
+                },
+                Command::Resume => {
+                    chassis.resume();
+                    Reply::Ack
+                },
+                Command::GetState => {
+                    Reply::State(match chassis.get_state() {
+                        ControllerState::Idle => MotionState::Idle,
+                        ControllerState::Moving => MotionState::Moving,
+                        ControllerState::Driving => MotionState::Driving,
+                        ControllerState::Stopping => MotionState::Stopping,
+                        ControllerState::Stopped => MotionState::Stopped,
+                        ControllerState::Halted => MotionState::Halted,
+                        ControllerState::Paused => MotionState::Paused,
+                        ControllerState::Failed(TargetState::Stalled) => MotionState::Stalled,
+                        ControllerState::Failed(_) => MotionState::TimedOut,
+                    })
+                },
+                Command::SetSpeed { linear, angular } => {
+                    *speed = chassis_speed(linear, angular);
+                    chassis.set_speed(*speed);
+                    Reply::Ack
+                },
+                Command::Stop => {
+                    cancel_tuning(chassis, tuning);
+                    chassis.stop();
+                    Reply::Ack
+                },
+                // the carriage and the claw have no pins assigned yet, so a sort could never run
+                Command::DrawerSelect(_) | Command::Sort(_) => Reply::Error(ErrorCode::Unsupported),
+                Command::DrawerOpen(id) => drawers_reply(sequencer.drawers.raise(id)),
+                Command::DrawerClose(id) => drawers_reply(sequencer.drawers.lower(id)),
+                Command::GetDrawerState => drawers_state_reply(sequencer.drawers.get_state()),
+                Command::GetSortState => sort_state_reply(sequencer.get_state()),
+                Command::Heartbeat => Reply::Ack,
+                Command::GetParam(param) => {
+                    let value = match param {
+                        Param::LinearSpeed => speed.linear.to_cm_per_s(),
+                        Param::AngularSpeed => speed.angular.to_degrees_per_s(),
+                    };
+                    Reply::Param(param, value)
+                },
+                Command::SetParam(param, value) => {
+                    match param {
+                        Param::LinearSpeed => speed.linear = Velocity::from_cm_per_s(value),
+                        Param::AngularSpeed => speed.angular = AngularVelocity::from_degrees_per_s(value),
+                    };
+                    chassis.set_speed(*speed);
+                    Reply::Ack
+                },
+                Command::SetVelocity { linear, angular } => {
+                    chassis.set_velocity(chassis_speed(linear, angular));
+                    Reply::Ack
+                },
+                Command::Autotune { target, rule } => {
+                    start_tuning(chassis, tuning, target, rule);
+                    Reply::Ack
+                },
+                Command::GetAutotuneResult => tuning.get_result(),
+            };
+
+            send_reply(serial, sequence, reply);
         });
+    }
+
+    #[task(shared = [chassis, sequencer, link, timing, tuning], local = [watchdog, last_update: Option<Instant> = None, next_update: Option<Instant> = None])]
+    fn updater(cx: updater::Context) {
+        let now = monotonics::now();
+        cx.local.watchdog.feed();
+
+        // the first update has nothing to be measured against
+        let measured = cx.local.last_update.replace(now)
+            .and_then(|last_update| now.checked_duration_since(last_update));
+        let time_delta = match measured {
+            Some(time_delta) => Time::from_seconds(time_delta.ticks() as f32 / 1_000_000.0),
+            None => UPDATE_PERIOD
+        };
+
+        (cx.shared.chassis, cx.shared.sequencer, cx.shared.link, cx.shared.timing, cx.shared.tuning).lock(|chassis, sequencer, link, timing, tuning| {
+            if measured.is_some() {
+                timing.record(time_delta);
+            }
+
+            if link.update(time_delta) {
+                rprintln!("link lost, stopping");
+                cancel_tuning(chassis, tuning);
+                chassis.stop();
+                sequencer.stop();
+            }
+
+            if tuning.is_running() {
+                update_tuning(chassis, tuning, time_delta);
+            } else {
+                chassis.update(time_delta);
+            }
+            sequencer.update(time_delta);
+        });
+
+        // scheduled on a fixed grid, so that the execution time does not add up,
+        // deadlines already missed are skipped rather than made up for
+        let period = UPDATE_PERIOD_MICROS.micros();
+        let next_update = match *cx.local.next_update {
+            Some(next_update) if next_update + period > now => next_update + period,
+            _ => now + period
+        };
+        *cx.local.next_update = Some(next_update);
 
-        updater::spawn_after(25.millis()).ok();
+        updater::spawn_at(next_update).ok();
     }
 }
@@ -0,0 +1,101 @@
+//! Safe state on panic and the cause of the last reset.
+//!
+//! A panic cuts every motor output and halts, the independent watchdog is no longer fed
+//! then, so it resets the controller. A marker left in uninitialized RAM tells that reset apart.
+
+use core::{panic::PanicInfo, ptr::{addr_of, addr_of_mut}, sync::atomic::{compiler_fence, Ordering}};
+
+use rtt_target::rprintln;
+use stm32f4xx_hal::pac::{self, RCC};
+
+const PANIC_MARKER: u32 = 0xDEAD_C0DE;
+
+#[link_section = ".uninit.PANIC_MARKER"]
+static mut PANIC_MARKER_WORD: u32 = 0;
+
+// direction pins of the wheels and the drawers, and the drawer enables
+const GPIOA_MOTOR_PINS: u32 = 1 << 12;
+const GPIOB_MOTOR_PINS: u32 = 1 << 3 | 1 << 6 | 1 << 10 | 1 << 13;
+const GPIOC_MOTOR_PINS: u32 = 1 << 5 | 1 << 6 | 1 << 7 | 1 << 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetCause {
+    PowerOn,
+    /// The reset button or the debugger
+    Pin,
+    BrownOut,
+    Software,
+    /// The control loop stopped feeding the independent watchdog
+    Watchdog,
+    /// The watchdog reset after a panic
+    Panic,
+    WindowWatchdog,
+    LowPower,
+    Unknown
+}
+
+/// Reads the reset flags and clears them, together with the panic marker, for the next reset
+pub fn take_reset_cause(rcc: &RCC) -> ResetCause {
+    let csr = rcc.csr.read();
+    let panicked = unsafe { core::ptr::read_volatile(addr_of!(PANIC_MARKER_WORD)) } == PANIC_MARKER;
+
+    // a power-on reset sets the brown-out flag as well,
+    // and the reset pin flag is set by every reset, so it goes last
+    let cause = if csr.borrstf().bit_is_set() && !csr.porrstf().bit_is_set() {
+        ResetCause::BrownOut
+    } else if csr.porrstf().bit_is_set() {
+        ResetCause::PowerOn
+    } else if csr.wdgrstf().bit_is_set() && panicked {
+        ResetCause::Panic
+    } else if csr.wdgrstf().bit_is_set() {
+        ResetCause::Watchdog
+    } else if csr.wwdgrstf().bit_is_set() {
+        ResetCause::WindowWatchdog
+    } else if csr.lpwrrstf().bit_is_set() {
+        ResetCause::LowPower
+    } else if csr.sftrstf().bit_is_set() {
+        ResetCause::Software
+    } else if csr.padrstf().bit_is_set() {
+        ResetCause::Pin
+    } else {
+        ResetCause::Unknown
+    };
+
+    rcc.csr.modify(|_, w| w.rmvf().set_bit());
+    unsafe { core::ptr::write_volatile(addr_of_mut!(PANIC_MARKER_WORD), 0) };
+
+    cause
+}
+
+/// Drives every motor direction pin low and zeroes the PWM duties,
+/// regardless of who owns the peripherals
+pub fn cut_motor_outputs() {
+    // SAFETY: only single writes to the set/reset and compare registers, which cannot
+    // break the state of the drivers, everything is about to stop anyway
+    unsafe {
+        let tim1 = &*pac::TIM1::ptr();
+        tim1.ccr1.write(|w| w.ccr().bits(0));
+        tim1.ccr2.write(|w| w.ccr().bits(0));
+        tim1.bdtr.modify(|_, w| w.moe().clear_bit());
+
+        (*pac::GPIOA::ptr()).bsrr.write(|w| w.bits(GPIOA_MOTOR_PINS << 16));
+        (*pac::GPIOB::ptr()).bsrr.write(|w| w.bits(GPIOB_MOTOR_PINS << 16));
+        (*pac::GPIOC::ptr()).bsrr.write(|w| w.bits(GPIOC_MOTOR_PINS << 16));
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+    cut_motor_outputs();
+
+    unsafe { core::ptr::write_volatile(addr_of_mut!(PANIC_MARKER_WORD), PANIC_MARKER) };
+    compiler_fence(Ordering::SeqCst);
+
+    rprintln!("{}", info);
+
+    // the watchdog resets the controller
+    loop {
+        compiler_fence(Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,94 @@
+//! Relay auto-tuning of one loop of the chassis at a time, and its result for the telemetry
+
+use units::Time;
+use autotune::{RelayAutotune, RelayPlant, AutotuneState, UltimateGain, TuningRule};
+use protocol::{Reply, ActionStatus, AutotuneTarget};
+
+pub struct Tuning {
+    /// Nominal period of the loops, their gains are per period
+    pub period: Time,
+
+    relay: RelayAutotune,
+    target: AutotuneTarget,
+    rule: TuningRule,
+    cancelled: bool,
+    reported: bool
+}
+
+impl Tuning {
+    pub fn new(period: Time) -> Self {
+        Self {
+            period,
+
+            relay: RelayAutotune::new(0.0, 0.0, Time::ZERO),
+            target: AutotuneTarget::LeftWheel,
+            rule: TuningRule::ZieglerNichols,
+            cancelled: false,
+            reported: true
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.relay.is_running()
+    }
+
+    pub fn get_target(&self) -> AutotuneTarget {
+        self.target
+    }
+
+    pub fn start(&mut self, target: AutotuneTarget, rule: protocol::TuningRule, mut relay: RelayAutotune, setpoint: f32, bias: f32) {
+        relay.start(setpoint, bias);
+
+        self.relay = relay;
+        self.target = target;
+        self.rule = match rule {
+            protocol::TuningRule::ZieglerNichols => TuningRule::ZieglerNichols,
+            protocol::TuningRule::ZieglerNicholsPi => TuningRule::ZieglerNicholsPi,
+            protocol::TuningRule::SomeOvershoot => TuningRule::SomeOvershoot,
+            protocol::TuningRule::NoOvershoot => TuningRule::NoOvershoot,
+            protocol::TuningRule::TyreusLuyben => TuningRule::TyreusLuyben,
+        };
+        self.cancelled = false;
+        self.reported = false;
+    }
+
+    /// The plant has to be the loop of the current target
+    pub fn update<P: RelayPlant>(&mut self, plant: &mut P, time_delta: Time) {
+        self.relay.update(plant, time_delta);
+    }
+
+    pub fn cancel<P: RelayPlant>(&mut self, plant: &mut P) {
+        if self.is_running() {
+            self.cancelled = true;
+            self.relay.cancel(plant);
+        }
+    }
+
+    /// The result, once after the tuning has ended
+    pub fn take_finished(&mut self) -> Option<Reply> {
+        if self.reported || self.is_running() {
+            return None;
+        }
+        self.reported = true;
+
+        Some(self.get_result())
+    }
+
+    pub fn get_result(&self) -> Reply {
+        let (status, ultimate_gain) = match self.relay.get_state() {
+            AutotuneState::Idle if self.cancelled => (ActionStatus::Stopped, None),
+            AutotuneState::Idle => (ActionStatus::Idle, None),
+            AutotuneState::Running => (ActionStatus::Moving, None),
+            AutotuneState::Done(ultimate_gain) => (ActionStatus::Done, Some(ultimate_gain)),
+            AutotuneState::TimedOut => (ActionStatus::TimedOut, None),
+            AutotuneState::NoOscillation => (ActionStatus::Failed, None),
+        };
+        let UltimateGain { ku, tu } = ultimate_gain.unwrap_or(UltimateGain { ku: 0.0, tu: 0.0 });
+        let (kp, ki, kd) = match ultimate_gain {
+            Some(ultimate_gain) => ultimate_gain.gains(self.rule).to_incremental(self.period.to_seconds()),
+            None => (0.0, 0.0, 0.0)
+        };
+
+        Reply::AutotuneResult { target: self.target, status, ku, tu, kp, ki, kd }
+    }
+}
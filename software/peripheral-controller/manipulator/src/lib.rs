@@ -0,0 +1,390 @@
+#![no_std]
+#![feature(trait_alias)]
+
+//! The front claw: a gripper on a flip joint, lifted by a lead screw.
+//! Items are grabbed from the floor, lifted to be carried, then flipped over the drawers to be dumped.
+
+use units::{Length, Angle, Time};
+use encoder::{Update, GetPosition};
+use servo::{SetPosition, Stop, CheckTargetReached, TargetState};
+
+/// An axis that moves a joint, e.g. a `Servo`
+pub trait ManipulatorAxis = SetPosition<Position = Length> + GetPosition<Position = Length> + Stop + CheckTargetReached + Update;
+
+/// Position of a joint, e.g. a `Length` for a lift or an `Angle` for a rotary joint
+pub trait JointPosition: Copy + PartialOrd {
+    /// From metres or radians
+    fn from_base(value: f32) -> Self;
+
+    /// In metres or radians
+    fn to_base(self) -> f32;
+}
+
+impl JointPosition for Length {
+    fn from_base(value: f32) -> Self {
+        Length::from_m(value)
+    }
+
+    fn to_base(self) -> f32 {
+        self.to_m()
+    }
+}
+
+impl JointPosition for Angle {
+    fn from_base(value: f32) -> Self {
+        Angle::from_radians(value)
+    }
+
+    fn to_base(self) -> f32 {
+        self.to_radians()
+    }
+}
+
+/// An axis in the quantity of the joint, with the targets limited to its travel
+pub struct Joint<A: ManipulatorAxis, P: JointPosition> {
+    axis: A,
+
+    /// Metres or radians of the joint per metre of the axis
+    pub ratio: f32,
+    pub min: P,
+    pub max: P
+}
+
+impl<A: ManipulatorAxis, P: JointPosition> Joint<A, P> {
+    /// Both positions are zero at the same point, e.g. where the axis was homed
+    pub fn new(axis: A, ratio: f32, min: P, max: P) -> Self {
+        assert!(ratio != 0.0 && min <= max);
+
+        Self { axis, ratio, min, max }
+    }
+
+    pub fn get_axis(&self) -> &A {
+        &self.axis
+    }
+
+    pub fn get_axis_mut(&mut self) -> &mut A {
+        &mut self.axis
+    }
+}
+
+impl<A: ManipulatorAxis> Joint<A, Length> {
+    /// A lift driven by a lead screw, which advances by its lead while the axis travels
+    /// its distance per revolution of the screw
+    pub fn lead_screw(axis: A, lead_per_rev: Length, axis_travel_per_rev: Length, min: Length, max: Length) -> Self {
+        Self::new(axis, lead_per_rev / axis_travel_per_rev, min, max)
+    }
+}
+
+impl<A: ManipulatorAxis> Joint<A, Angle> {
+    /// A joint which turns a revolution while the axis travels the distance
+    pub fn rotary(axis: A, axis_travel_per_rev: Length, min: Angle, max: Angle) -> Self {
+        Self::new(axis, Angle::from_revolutions(1.0).to_radians() / axis_travel_per_rev.to_m(), min, max)
+    }
+}
+
+impl<A: ManipulatorAxis, P: JointPosition> GetPosition for Joint<A, P> {
+    type Position = P;
+
+    fn get_position(&self) -> Self::Position {
+        P::from_base(self.axis.get_position().to_m() * self.ratio)
+    }
+}
+
+impl<A: ManipulatorAxis, P: JointPosition> SetPosition for Joint<A, P> {
+    type Position = P;
+
+    /// Targets outside of the travel are clamped to it
+    fn set_position(&mut self, position: Self::Position) {
+        let position = match position {
+            position if position < self.min => self.min,
+            position if position > self.max => self.max,
+            position => position
+        };
+        self.axis.set_position(Length::from_m(position.to_base() / self.ratio));
+    }
+}
+
+impl<A: ManipulatorAxis, P: JointPosition> CheckTargetReached for Joint<A, P> {
+    fn get_target_state(&self) -> TargetState {
+        self.axis.get_target_state()
+    }
+}
+
+impl<A: ManipulatorAxis, P: JointPosition> Update for Joint<A, P> {
+    fn update(&mut self, time_delta: Time) {
+        self.axis.update(time_delta);
+    }
+}
+
+impl<A: ManipulatorAxis, P: JointPosition> Stop for Joint<A, P> {
+    fn stop(&mut self) {
+        self.axis.stop();
+    }
+
+    fn halt(&mut self) {
+        self.axis.halt();
+    }
+}
+
+/// Joint positions the operations move to
+#[derive(Debug, Clone, Copy)]
+pub struct ManipulatorPoses {
+    pub floor_height: Length,
+    pub carry_height: Length,
+    /// High enough for the claw to be flipped over the drawers
+    pub dump_height: Length,
+
+    pub carry_angle: Angle,
+    pub dump_angle: Angle,
+
+    /// Travel of the gripper jaws
+    pub gripper_open: Length,
+    pub gripper_closed: Length
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Closes the gripper, the claw is expected to be lowered around the item
+    Grab,
+    /// Lifts the claw off the floor
+    LiftToCarry,
+    /// Lifts the claw over the drawers, flips it, releases the item and flips it back
+    FlipToDump,
+    /// Lowers the open claw to the floor, ready for the next item
+    Lower
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JointId {
+    Lift,
+    Flip,
+    Gripper
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Lift(fn(&ManipulatorPoses) -> Length),
+    Flip(fn(&ManipulatorPoses) -> Angle),
+    Gripper(fn(&ManipulatorPoses) -> Length)
+}
+
+impl Step {
+    fn joint(self) -> JointId {
+        match self {
+            Step::Lift(_) => JointId::Lift,
+            Step::Flip(_) => JointId::Flip,
+            Step::Gripper(_) => JointId::Gripper
+        }
+    }
+}
+
+impl Operation {
+    /// Joints are moved one at a time, so that the claw is never flipped low
+    fn steps(self) -> &'static [Step] {
+        match self {
+            Operation::Grab => &[
+                Step::Gripper(|poses| poses.gripper_closed)
+            ],
+            Operation::LiftToCarry => &[
+                Step::Flip(|poses| poses.carry_angle),
+                Step::Lift(|poses| poses.carry_height)
+            ],
+            Operation::FlipToDump => &[
+                Step::Flip(|poses| poses.carry_angle),
+                Step::Lift(|poses| poses.dump_height),
+                Step::Flip(|poses| poses.dump_angle),
+                Step::Gripper(|poses| poses.gripper_open),
+                Step::Flip(|poses| poses.carry_angle)
+            ],
+            Operation::Lower => &[
+                Step::Flip(|poses| poses.carry_angle),
+                Step::Gripper(|poses| poses.gripper_open),
+                Step::Lift(|poses| poses.floor_height)
+            ]
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManipulatorState {
+    Idle,
+    Busy(Operation),
+    Done(Operation),
+    /// Interrupted by `stop` or `halt`
+    Stopped(Operation),
+    /// The joint did not reach its target, the other joints are stopped
+    Failed { operation: Operation, joint: JointId, state: TargetState }
+}
+
+/// Another operation is in progress, it has to be stopped first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Busy;
+
+pub struct Manipulator<L, F, G>
+where
+    L: ManipulatorAxis,
+    F: ManipulatorAxis,
+    G: ManipulatorAxis
+{
+    pub lift: Joint<L, Length>,
+    pub flip: Joint<F, Angle>,
+    pub gripper: Joint<G, Length>,
+
+    pub poses: ManipulatorPoses,
+
+    state: ManipulatorState,
+    step: usize
+}
+
+impl<L, F, G> Manipulator<L, F, G>
+where
+    L: ManipulatorAxis,
+    F: ManipulatorAxis,
+    G: ManipulatorAxis
+{
+    pub fn new(lift: Joint<L, Length>, flip: Joint<F, Angle>, gripper: Joint<G, Length>, poses: ManipulatorPoses) -> Self {
+        Self {
+            lift,
+            flip,
+            gripper,
+
+            poses,
+
+            state: ManipulatorState::Idle,
+            step: 0
+        }
+    }
+
+    pub fn get_state(&self) -> ManipulatorState {
+        self.state
+    }
+
+    pub fn is_busy(&self) -> bool {
+        matches!(self.state, ManipulatorState::Busy(_))
+    }
+
+    pub fn grab(&mut self) -> Result<(), Busy> {
+        self.start(Operation::Grab)
+    }
+
+    pub fn lift_to_carry(&mut self) -> Result<(), Busy> {
+        self.start(Operation::LiftToCarry)
+    }
+
+    pub fn flip_to_dump(&mut self) -> Result<(), Busy> {
+        self.start(Operation::FlipToDump)
+    }
+
+    pub fn lower(&mut self) -> Result<(), Busy> {
+        self.start(Operation::Lower)
+    }
+
+    pub fn start(&mut self, operation: Operation) -> Result<(), Busy> {
+        if self.is_busy() {
+            return Err(Busy);
+        }
+
+        self.state = ManipulatorState::Busy(operation);
+        self.step = 0;
+        self.start_step(operation.steps()[0]);
+
+        Ok(())
+    }
+
+    fn start_step(&mut self, step: Step) {
+        match step {
+            Step::Lift(position) => self.lift.set_position(position(&self.poses)),
+            Step::Flip(position) => self.flip.set_position(position(&self.poses)),
+            Step::Gripper(position) => self.gripper.set_position(position(&self.poses))
+        }
+    }
+
+    fn get_joint_state(&self, joint: JointId) -> TargetState {
+        match joint {
+            JointId::Lift => self.lift.get_target_state(),
+            JointId::Flip => self.flip.get_target_state(),
+            JointId::Gripper => self.gripper.get_target_state()
+        }
+    }
+
+    fn interrupt(&mut self) {
+        if let ManipulatorState::Busy(operation) = self.state {
+            self.state = ManipulatorState::Stopped(operation);
+        }
+    }
+}
+
+impl<L, F, G> Update for Manipulator<L, F, G>
+where
+    L: ManipulatorAxis,
+    F: ManipulatorAxis,
+    G: ManipulatorAxis
+{
+    fn update(&mut self, time_delta: Time) {
+        self.lift.update(time_delta);
+        self.flip.update(time_delta);
+        self.gripper.update(time_delta);
+
+        let operation = match self.state {
+            ManipulatorState::Busy(operation) => operation,
+            _ => return
+        };
+        let steps = operation.steps();
+        let joint = steps[self.step].joint();
+
+        match self.get_joint_state(joint) {
+            TargetState::Moving => {},
+            TargetState::Reached if self.step + 1 < steps.len() => {
+                self.step += 1;
+                self.start_step(steps[self.step]);
+            },
+            TargetState::Reached => {
+                self.state = ManipulatorState::Done(operation);
+            },
+            failure => {
+                self.lift.stop();
+                self.flip.stop();
+                self.gripper.stop();
+                self.state = ManipulatorState::Failed { operation, joint, state: failure };
+            }
+        }
+    }
+}
+
+impl<L, F, G> CheckTargetReached for Manipulator<L, F, G>
+where
+    L: ManipulatorAxis,
+    F: ManipulatorAxis,
+    G: ManipulatorAxis
+{
+    fn get_target_state(&self) -> TargetState {
+        match self.state {
+            ManipulatorState::Busy(_) => TargetState::Moving,
+            ManipulatorState::Failed { state, .. } => state,
+            _ => TargetState::Reached
+        }
+    }
+}
+
+impl<L, F, G> Stop for Manipulator<L, F, G>
+where
+    L: ManipulatorAxis,
+    F: ManipulatorAxis,
+    G: ManipulatorAxis
+{
+    /// Decelerates all the joints wherever they are
+    fn stop(&mut self) {
+        self.interrupt();
+        self.lift.stop();
+        self.flip.stop();
+        self.gripper.stop();
+    }
+
+    /// Cuts all the joints at once, the lead screw keeps the claw from falling
+    fn halt(&mut self) {
+        self.interrupt();
+        self.lift.halt();
+        self.flip.halt();
+        self.gripper.halt();
+    }
+}
@@ -0,0 +1,138 @@
+use units::{Length, Angle};
+use encoder::{Update, GetPosition};
+use servo::{SetPosition, Stop, CheckTargetReached, TargetState};
+use manipulator::{Manipulator, ManipulatorPoses, ManipulatorState, Joint, JointId, Operation, Busy};
+use test_support::{MockAxis, Log, TIME_DELTA};
+
+/// The lift screw advances 2 mm per 4 mm of its axis, the flip joint turns a revolution per 10 cm of its axis
+const LIFT_LEAD: Length = Length::from_mm(2.0);
+const LIFT_AXIS_TRAVEL: Length = Length::from_mm(4.0);
+const FLIP_AXIS_TRAVEL: Length = Length::from_cm(10.0);
+const GRIPPER_LEAD: Length = Length::from_mm(1.0);
+const GRIPPER_AXIS_TRAVEL: Length = Length::from_mm(10.0);
+
+const POSES: ManipulatorPoses = ManipulatorPoses {
+    floor_height: Length::ZERO,
+    carry_height: Length::from_mm(40.0),
+    dump_height: Length::from_mm(250.0),
+
+    carry_angle: Angle::ZERO,
+    dump_angle: Angle::from_degrees(150.0),
+
+    gripper_open: Length::ZERO,
+    gripper_closed: Length::from_mm(10.0)
+};
+
+fn manipulator(log: &Log) -> Manipulator<MockAxis, MockAxis, MockAxis> {
+    Manipulator::new(
+        Joint::lead_screw(MockAxis::logged("lift", log), LIFT_LEAD, LIFT_AXIS_TRAVEL, Length::ZERO, Length::from_mm(200.0)),
+        Joint::rotary(MockAxis::logged("flip", log), FLIP_AXIS_TRAVEL, Angle::ZERO, Angle::from_degrees(180.0)),
+        Joint::lead_screw(MockAxis::logged("gripper", log), GRIPPER_LEAD, GRIPPER_AXIS_TRAVEL, Length::ZERO, Length::from_mm(10.0)),
+        POSES
+    )
+}
+
+fn assert_near(position: Length, expected: Length) {
+    assert!((position - expected).abs() < Length::from_mm(0.01), "{:?} {:?}", position, expected);
+}
+
+fn run_until_done<T: Update + CheckTargetReached>(manipulator: &mut T) {
+    for _ in 0..1000 {
+        manipulator.update(TIME_DELTA);
+        if manipulator.get_target_state() != TargetState::Moving {
+            return;
+        }
+    }
+    panic!("the operation did not finish");
+}
+
+#[test]
+fn flip_to_dump_lifts_before_flipping() {
+    let log = Log::default();
+    let mut manipulator = manipulator(&log);
+
+    manipulator.flip_to_dump().unwrap();
+    assert_eq!(manipulator.get_state(), ManipulatorState::Busy(Operation::FlipToDump));
+    run_until_done(&mut manipulator);
+    assert_eq!(manipulator.get_state(), ManipulatorState::Done(Operation::FlipToDump));
+
+    // the dump height is above the lift travel, so it is limited to 200 mm, i.e. 40 cm of the axis
+    let expected = [
+        ("flip", Length::ZERO),
+        ("lift", Length::from_cm(40.0)),
+        ("flip", FLIP_AXIS_TRAVEL * (150.0 / 360.0)),
+        ("gripper", Length::ZERO),
+        ("flip", Length::ZERO)
+    ];
+    assert_eq!(log.borrow().len(), expected.len(), "{:?}", log.borrow());
+    for (&(axis, target), (expected_axis, expected_target)) in log.borrow().iter().zip(expected) {
+        assert_eq!(axis, expected_axis);
+        assert_near(target, expected_target);
+    }
+    assert_near(manipulator.lift.get_position(), Length::from_mm(200.0));
+}
+
+#[test]
+fn lead_screw_targets_the_axis_by_the_lead() {
+    let log = Log::default();
+    // an 8 mm lead screw, turned a revolution per 2 cm of the axis
+    let mut lift = Joint::lead_screw(MockAxis::logged("lift", &log), Length::from_mm(8.0), Length::from_cm(2.0),
+                                     Length::ZERO, Length::from_mm(300.0));
+
+    // 12.5 revolutions of the screw
+    lift.set_position(Length::from_mm(100.0));
+    assert_near(log.borrow()[0].1, Length::from_cm(25.0));
+
+    run_until_done(&mut lift);
+    assert_near(lift.get_position(), Length::from_mm(100.0));
+}
+
+#[test]
+fn carry_cycle() {
+    let log = Log::default();
+    let mut manipulator = manipulator(&log);
+
+    manipulator.grab().unwrap();
+    run_until_done(&mut manipulator);
+    assert_near(manipulator.gripper.get_position(), Length::from_mm(10.0));
+
+    manipulator.lift_to_carry().unwrap();
+    run_until_done(&mut manipulator);
+    assert_near(manipulator.lift.get_position(), Length::from_mm(40.0));
+
+    manipulator.lower().unwrap();
+    run_until_done(&mut manipulator);
+    assert_eq!(manipulator.get_state(), ManipulatorState::Done(Operation::Lower));
+    assert_eq!(manipulator.lift.get_position(), Length::ZERO);
+    assert_eq!(manipulator.gripper.get_position(), Length::ZERO);
+}
+
+#[test]
+fn operations_do_not_overlap() {
+    let log = Log::default();
+    let mut manipulator = manipulator(&log);
+
+    manipulator.lift_to_carry().unwrap();
+    manipulator.update(TIME_DELTA);
+    assert_eq!(manipulator.flip_to_dump(), Err(Busy));
+
+    manipulator.stop();
+    assert_eq!(manipulator.get_state(), ManipulatorState::Stopped(Operation::LiftToCarry));
+    manipulator.flip_to_dump().unwrap();
+}
+
+#[test]
+fn joint_failure_is_reported() {
+    let log = Log::default();
+    let mut manipulator = manipulator(&log);
+    manipulator.lift.get_axis_mut().stuck = true;
+
+    manipulator.lift_to_carry().unwrap();
+    run_until_done(&mut manipulator);
+    assert_eq!(manipulator.get_state(), ManipulatorState::Failed {
+        operation: Operation::LiftToCarry,
+        joint: JointId::Lift,
+        state: TargetState::Stalled
+    });
+    assert!(!manipulator.is_busy());
+}
@@ -0,0 +1,68 @@
+//! Consistent Overhead Byte Stuffing, removes zeroes from the data so that
+//! a zero byte can be used as the frame delimiter
+
+use crate::Error;
+
+/// Largest possible encoded size of `len` bytes of data
+pub const fn max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+pub fn encode(src: &[u8], dst: &mut [u8]) -> Result<usize, Error> {
+    if dst.len() < max_encoded_len(src.len()) {
+        return Err(Error::BufferTooSmall);
+    }
+
+    let mut code_index = 0;
+    let mut write = 1;
+    let mut code = 1_u8;
+
+    for &byte in src {
+        if byte != 0 {
+            dst[write] = byte;
+            write += 1;
+            code += 1;
+        }
+
+        if byte == 0 || code == 0xFF {
+            dst[code_index] = code;
+            code_index = write;
+            write += 1;
+            code = 1;
+        }
+    }
+    dst[code_index] = code;
+
+    Ok(write)
+}
+
+pub fn decode(src: &[u8], dst: &mut [u8]) -> Result<usize, Error> {
+    let mut read = 0;
+    let mut write = 0;
+
+    while read < src.len() {
+        let code = src[read];
+        if code == 0 {
+            return Err(Error::Cobs);
+        }
+        read += 1;
+
+        for _ in 1..code {
+            let byte = *src.get(read).ok_or(Error::Cobs)?;
+            if byte == 0 {
+                return Err(Error::Cobs);
+            }
+
+            *dst.get_mut(write).ok_or(Error::BufferTooSmall)? = byte;
+            read += 1;
+            write += 1;
+        }
+
+        if code != 0xFF && read < src.len() {
+            *dst.get_mut(write).ok_or(Error::BufferTooSmall)? = 0;
+            write += 1;
+        }
+    }
+
+    Ok(write)
+}
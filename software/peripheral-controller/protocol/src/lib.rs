@@ -0,0 +1,126 @@
+#![no_std]
+
+//! Framed binary protocol between the Raspberry Pi and the peripheral controller.
+//!
+//! A frame is `[sequence number][message][CRC-16 of the previous bytes, LE]`,
+//! COBS-encoded and terminated with a zero byte.
+
+pub mod cobs;
+pub mod crc;
+pub mod link;
+pub mod message;
+
+pub use crate::message::{Message, Command, Reply, Param, ErrorCode, Stage, MotionState, ActionStatus, SortStep, AutotuneTarget, TuningRule};
+
+use crate::message::{Reader, Writer};
+
+/// Largest unencoded frame: sequence number, message and CRC
+pub const MAX_PAYLOAD_LEN: usize = 32;
+/// Largest encoded frame, including the delimiter
+pub const MAX_FRAME_LEN: usize = cobs::max_encoded_len(MAX_PAYLOAD_LEN) + 1;
+
+const CRC_LEN: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    BufferTooSmall,
+    FrameTooLong,
+    Cobs,
+    Crc,
+    Truncated,
+    UnknownTag,
+    InvalidValue,
+}
+
+/// Writes a complete frame, including the delimiter, and returns its length
+pub fn encode_frame<M: Message>(sequence: u8, message: &M, out: &mut [u8]) -> Result<usize, Error> {
+    let mut payload = [0_u8; MAX_PAYLOAD_LEN];
+
+    let mut writer = Writer::new(&mut payload);
+    writer.u8(sequence)?;
+    message.write(&mut writer)?;
+    let len = writer.len();
+
+    let crc = crc::crc16(&payload[..len]);
+    Writer::new(&mut payload[len..]).bytes(&crc.to_le_bytes())?;
+    let len = len + CRC_LEN;
+
+    let encoded_len = cobs::encode(&payload[..len], out)?;
+    *out.get_mut(encoded_len).ok_or(Error::BufferTooSmall)? = 0;
+
+    Ok(encoded_len + 1)
+}
+
+/// Decodes a frame without the delimiter into its sequence number and message
+pub fn decode_frame<M: Message>(frame: &[u8]) -> Result<(u8, M), Error> {
+    let mut payload = [0_u8; MAX_PAYLOAD_LEN];
+    let len = cobs::decode(frame, &mut payload)?;
+    if len < 1 + CRC_LEN {
+        return Err(Error::Truncated);
+    }
+
+    let (data, crc) = payload[..len].split_at(len - CRC_LEN);
+    if crc::crc16(data) != u16::from_le_bytes([crc[0], crc[1]]) {
+        return Err(Error::Crc);
+    }
+
+    let mut reader = Reader::new(data);
+    let sequence = reader.u8()?;
+    let message = M::read(&mut reader)?;
+    if !reader.is_empty() {
+        return Err(Error::InvalidValue);
+    }
+
+    Ok((sequence, message))
+}
+
+/// Accumulates received bytes until a frame delimiter shows up
+pub struct FrameDecoder<const N: usize = MAX_FRAME_LEN> {
+    buf: [u8; N],
+    len: usize,
+    overflow: bool
+}
+
+impl<const N: usize> FrameDecoder<N> {
+    pub fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            overflow: false
+        }
+    }
+
+    /// Returns the decoded frame once its delimiter is pushed
+    pub fn push<M: Message>(&mut self, byte: u8) -> Option<Result<(u8, M), Error>> {
+        if byte != 0 {
+            match self.buf.get_mut(self.len) {
+                Some(slot) => {
+                    *slot = byte;
+                    self.len += 1;
+                },
+                None => self.overflow = true
+            }
+            return None;
+        }
+
+        let result = if self.overflow {
+            Err(Error::FrameTooLong)
+        } else if self.len == 0 {
+            // consecutive delimiters, nothing to decode
+            return None;
+        } else {
+            decode_frame(&self.buf[..self.len])
+        };
+
+        self.len = 0;
+        self.overflow = false;
+
+        Some(result)
+    }
+}
+
+impl<const N: usize> Default for FrameDecoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,60 @@
+//! Supervision of the link to the host, which is expected to send a frame,
+//! e.g. a `Heartbeat`, at least once per timeout.
+
+use units::Time;
+
+/// The link was lost since the last frame, everything was stopped meanwhile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkLost;
+
+pub struct LinkSupervisor {
+    /// Longest silence of the host before the link is considered lost
+    pub timeout: Time,
+
+    silence: Time,
+    lost: bool
+}
+
+impl LinkSupervisor {
+    /// The link is considered up from the start, the host has `timeout` to show up
+    pub fn new(timeout: Time) -> Self {
+        Self {
+            timeout,
+
+            silence: Time::ZERO,
+            lost: false
+        }
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.lost
+    }
+
+    /// Time since the last frame
+    pub fn get_silence(&self) -> Time {
+        self.silence
+    }
+
+    /// To be called for every valid frame from the host. The first frame after a loss
+    /// is reported, so that the host learns the robot was stopped before it is commanded again
+    pub fn feed(&mut self) -> Result<(), LinkLost> {
+        self.silence = Time::ZERO;
+
+        if self.lost {
+            self.lost = false;
+            return Err(LinkLost);
+        }
+        Ok(())
+    }
+
+    /// Returns true exactly once when the link is lost, at which point everything is to be stopped
+    pub fn update(&mut self, time_delta: Time) -> bool {
+        self.silence += time_delta;
+
+        if !self.lost && self.silence >= self.timeout {
+            self.lost = true;
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,590 @@
+use crate::Error;
+
+pub trait Message: Sized {
+    fn write(&self, writer: &mut Writer) -> Result<(), Error>;
+    fn read(reader: &mut Reader) -> Result<Self, Error>;
+}
+
+/// Tunable values that can be read and written by the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Param {
+    LinearSpeed = 0,
+    AngularSpeed = 1,
+}
+
+impl TryFrom<u8> for Param {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Param::LinearSpeed),
+            1 => Ok(Param::AngularSpeed),
+            _ => Err(Error::InvalidValue)
+        }
+    }
+}
+
+/// Sent by the host, every command is answered with a `Reply` carrying the same sequence number
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    /// Offset in the odometry frame, cm, and the rotation after it, degrees.
+    /// Movements are queued, `Busy` is replied when the queue is full
+    MoveRelative { x: f32, y: f32, angle: f32 },
+    SetSpeed { linear: f32, angular: f32 },
+    /// Decelerates to a standstill and drops the queued movements
+    Stop,
+    /// Moves the drawer assembly so that the given drawer is under the drop zone
+    DrawerSelect(u8),
+    /// Raises the drawer, so that it is emptied
+    DrawerOpen(u8),
+    GetParam(Param),
+    SetParam(Param, f32),
+    /// Drives at the given linear (cm/s) and angular (degrees/s) velocity,
+    /// the robot stops unless the command is repeated within the timeout
+    SetVelocity { linear: f32, angular: f32 },
+    /// Pose in the odometry frame, queued like `MoveRelative`
+    MoveTo { x: f32, y: f32, angle: f32 },
+    /// Drops the queued movements, the one in progress is finished
+    ClearQueue,
+    GetProgress,
+    /// Cuts the motors at once and drops the queued movements
+    Halt,
+    /// Decelerates to a standstill, the queued movements are kept until `Resume`
+    Pause,
+    Resume,
+    GetState,
+    /// Lowers the drawer back
+    DrawerClose(u8),
+    GetDrawerState,
+    /// Picks the item in front of the claw and dumps it into the given bin, `InvalidArgument`
+    /// is replied for an unknown bin and `Busy` while another item is being sorted.
+    /// `Unsupported` is replied by firmware without a claw and a carriage
+    Sort(u8),
+    /// Replied with `SortState`, which the progress of `Sort` is polled with
+    GetSortState,
+    /// Keeps the link alive while no other commands are sent, everything is stopped
+    /// when the host is silent for too long
+    Heartbeat,
+    /// Oscillates the loop with a relay and computes its gains with the rule,
+    /// the motion commands are refused with `Busy` until it ends
+    Autotune { target: AutotuneTarget, rule: TuningRule },
+    GetAutotuneResult,
+}
+
+/// Loop tuned by `Autotune`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AutotuneTarget {
+    /// Speed loop of the wheel
+    LeftWheel = 0,
+    RightWheel = 1,
+    /// Position loop of the servo
+    LeftServo = 2,
+    RightServo = 3,
+}
+
+impl TryFrom<u8> for AutotuneTarget {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(AutotuneTarget::LeftWheel),
+            1 => Ok(AutotuneTarget::RightWheel),
+            2 => Ok(AutotuneTarget::LeftServo),
+            3 => Ok(AutotuneTarget::RightServo),
+            _ => Err(Error::InvalidValue)
+        }
+    }
+}
+
+/// Rule turning the ultimate gain and period into PID gains
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TuningRule {
+    ZieglerNichols = 0,
+    ZieglerNicholsPi = 1,
+    SomeOvershoot = 2,
+    NoOvershoot = 3,
+    TyreusLuyben = 4,
+}
+
+impl TryFrom<u8> for TuningRule {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(TuningRule::ZieglerNichols),
+            1 => Ok(TuningRule::ZieglerNicholsPi),
+            2 => Ok(TuningRule::SomeOvershoot),
+            3 => Ok(TuningRule::NoOvershoot),
+            4 => Ok(TuningRule::TyreusLuyben),
+            _ => Err(Error::InvalidValue)
+        }
+    }
+}
+
+/// Progress of an action of a mechanism, like a drawer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ActionStatus {
+    Idle = 0,
+    Moving = 1,
+    Done = 2,
+    Stopped = 3,
+    TimedOut = 4,
+    /// A part reported a failure, or an interlock was broken
+    Failed = 5,
+}
+
+impl TryFrom<u8> for ActionStatus {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ActionStatus::Idle),
+            1 => Ok(ActionStatus::Moving),
+            2 => Ok(ActionStatus::Done),
+            3 => Ok(ActionStatus::Stopped),
+            4 => Ok(ActionStatus::TimedOut),
+            5 => Ok(ActionStatus::Failed),
+            _ => Err(Error::InvalidValue)
+        }
+    }
+}
+
+/// What the robot is doing, in reply to `GetState`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MotionState {
+    Idle = 0,
+    Moving = 1,
+    /// Following `SetVelocity`
+    Driving = 2,
+    Stopping = 3,
+    Stopped = 4,
+    Halted = 5,
+    Paused = 6,
+    /// The movement did not finish in time and was dropped
+    TimedOut = 7,
+    /// The wheels were blocked and the movement was dropped
+    Stalled = 8,
+}
+
+impl TryFrom<u8> for MotionState {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(MotionState::Idle),
+            1 => Ok(MotionState::Moving),
+            2 => Ok(MotionState::Driving),
+            3 => Ok(MotionState::Stopping),
+            4 => Ok(MotionState::Stopped),
+            5 => Ok(MotionState::Halted),
+            6 => Ok(MotionState::Paused),
+            7 => Ok(MotionState::TimedOut),
+            8 => Ok(MotionState::Stalled),
+            _ => Err(Error::InvalidValue)
+        }
+    }
+}
+
+/// Stage of the movement in progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Stage {
+    Idle = 0,
+    InitialRotation = 1,
+    Translation = 2,
+    FinalRotation = 3,
+}
+
+impl TryFrom<u8> for Stage {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Stage::Idle),
+            1 => Ok(Stage::InitialRotation),
+            2 => Ok(Stage::Translation),
+            3 => Ok(Stage::FinalRotation),
+            _ => Err(Error::InvalidValue)
+        }
+    }
+}
+
+/// Step of the pick-and-sort routine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SortStep {
+    Idle = 0,
+    Grab = 1,
+    Lift = 2,
+    SelectBin = 3,
+    Dump = 4,
+    Return = 5,
+}
+
+impl TryFrom<u8> for SortStep {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(SortStep::Idle),
+            1 => Ok(SortStep::Grab),
+            2 => Ok(SortStep::Lift),
+            3 => Ok(SortStep::SelectBin),
+            4 => Ok(SortStep::Dump),
+            5 => Ok(SortStep::Return),
+            _ => Err(Error::InvalidValue)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ErrorCode {
+    /// The frame could not be decoded, its sequence number is unknown
+    InvalidFrame = 0,
+    InvalidArgument = 1,
+    Unsupported = 2,
+    Busy = 3,
+    /// The link to the host was lost and everything was stopped, the command was dropped
+    LinkLost = 4,
+}
+
+impl TryFrom<u8> for ErrorCode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ErrorCode::InvalidFrame),
+            1 => Ok(ErrorCode::InvalidArgument),
+            2 => Ok(ErrorCode::Unsupported),
+            3 => Ok(ErrorCode::Busy),
+            4 => Ok(ErrorCode::LinkLost),
+            _ => Err(Error::InvalidValue)
+        }
+    }
+}
+
+/// Sent by the robot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reply {
+    Ack,
+    Error(ErrorCode),
+    Param(Param, f32),
+    /// Periodic telemetry, the pose in the odometry frame
+    Position { x: f32, y: f32, angle: f32 },
+    /// Movements waiting after the current one and the distance left to its goal, cm
+    Progress { stage: Stage, queued: u8, distance: f32 },
+    State(MotionState),
+    /// The last drawer action, `open` tells whether it was raising the drawer
+    DrawerState { id: u8, open: bool, status: ActionStatus },
+    /// The last pick-and-sort routine and the step it is at, or has ended at
+    SortState { bin: u8, step: SortStep, status: ActionStatus },
+    /// Periodic telemetry, the measured control loop periods, seconds,
+    /// and the number of missed deadlines since the last report
+    LoopTiming { min: f32, max: f32, mean: f32, overruns: u8 },
+    /// The last auto-tuning, sent once when it ends. The ultimate gain and period, seconds,
+    /// and the gains of the `Pid` of the loop, which adds its output every period
+    AutotuneResult { target: AutotuneTarget, status: ActionStatus, ku: f32, tu: f32, kp: f32, ki: f32, kd: f32 },
+}
+
+mod tag {
+    pub const MOVE_RELATIVE: u8 = 0x01;
+    pub const SET_SPEED: u8 = 0x02;
+    pub const STOP: u8 = 0x03;
+    pub const DRAWER_SELECT: u8 = 0x04;
+    pub const DRAWER_OPEN: u8 = 0x05;
+    pub const GET_PARAM: u8 = 0x06;
+    pub const SET_PARAM: u8 = 0x07;
+    pub const SET_VELOCITY: u8 = 0x08;
+    pub const MOVE_TO: u8 = 0x09;
+    pub const CLEAR_QUEUE: u8 = 0x0A;
+    pub const GET_PROGRESS: u8 = 0x0B;
+    pub const HALT: u8 = 0x0C;
+    pub const PAUSE: u8 = 0x0D;
+    pub const RESUME: u8 = 0x0E;
+    pub const GET_STATE: u8 = 0x0F;
+    pub const DRAWER_CLOSE: u8 = 0x10;
+    pub const GET_DRAWER_STATE: u8 = 0x11;
+    pub const SORT: u8 = 0x12;
+    pub const GET_SORT_STATE: u8 = 0x13;
+    pub const HEARTBEAT: u8 = 0x14;
+    pub const AUTOTUNE: u8 = 0x15;
+    pub const GET_AUTOTUNE_RESULT: u8 = 0x16;
+
+    pub const ACK: u8 = 0x80;
+    pub const ERROR: u8 = 0x81;
+    pub const PARAM: u8 = 0x82;
+    pub const POSITION: u8 = 0x83;
+    pub const PROGRESS: u8 = 0x84;
+    pub const STATE: u8 = 0x85;
+    pub const DRAWER_STATE: u8 = 0x86;
+    pub const SORT_STATE: u8 = 0x87;
+    pub const LOOP_TIMING: u8 = 0x88;
+    pub const AUTOTUNE_RESULT: u8 = 0x89;
+}
+
+impl Message for Command {
+    fn write(&self, writer: &mut Writer) -> Result<(), Error> {
+        match *self {
+            Command::MoveRelative { x, y, angle } => {
+                writer.u8(tag::MOVE_RELATIVE)?;
+                writer.f32(x)?;
+                writer.f32(y)?;
+                writer.f32(angle)
+            },
+            Command::SetSpeed { linear, angular } => {
+                writer.u8(tag::SET_SPEED)?;
+                writer.f32(linear)?;
+                writer.f32(angular)
+            },
+            Command::Stop => writer.u8(tag::STOP),
+            Command::DrawerSelect(id) => {
+                writer.u8(tag::DRAWER_SELECT)?;
+                writer.u8(id)
+            },
+            Command::DrawerOpen(id) => {
+                writer.u8(tag::DRAWER_OPEN)?;
+                writer.u8(id)
+            },
+            Command::GetParam(param) => {
+                writer.u8(tag::GET_PARAM)?;
+                writer.u8(param as u8)
+            },
+            Command::SetParam(param, value) => {
+                writer.u8(tag::SET_PARAM)?;
+                writer.u8(param as u8)?;
+                writer.f32(value)
+            },
+            Command::SetVelocity { linear, angular } => {
+                writer.u8(tag::SET_VELOCITY)?;
+                writer.f32(linear)?;
+                writer.f32(angular)
+            },
+            Command::MoveTo { x, y, angle } => {
+                writer.u8(tag::MOVE_TO)?;
+                writer.f32(x)?;
+                writer.f32(y)?;
+                writer.f32(angle)
+            },
+            Command::ClearQueue => writer.u8(tag::CLEAR_QUEUE),
+            Command::GetProgress => writer.u8(tag::GET_PROGRESS),
+            Command::Halt => writer.u8(tag::HALT),
+            Command::Pause => writer.u8(tag::PAUSE),
+            Command::Resume => writer.u8(tag::RESUME),
+            Command::GetState => writer.u8(tag::GET_STATE),
+            Command::DrawerClose(id) => {
+                writer.u8(tag::DRAWER_CLOSE)?;
+                writer.u8(id)
+            },
+            Command::GetDrawerState => writer.u8(tag::GET_DRAWER_STATE),
+            Command::Sort(bin) => {
+                writer.u8(tag::SORT)?;
+                writer.u8(bin)
+            },
+            Command::GetSortState => writer.u8(tag::GET_SORT_STATE),
+            Command::Heartbeat => writer.u8(tag::HEARTBEAT),
+            Command::Autotune { target, rule } => {
+                writer.u8(tag::AUTOTUNE)?;
+                writer.u8(target as u8)?;
+                writer.u8(rule as u8)
+            },
+            Command::GetAutotuneResult => writer.u8(tag::GET_AUTOTUNE_RESULT),
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, Error> {
+        match reader.u8()? {
+            tag::MOVE_RELATIVE => Ok(Command::MoveRelative { x: reader.f32()?, y: reader.f32()?, angle: reader.f32()? }),
+            tag::SET_SPEED => Ok(Command::SetSpeed { linear: reader.f32()?, angular: reader.f32()? }),
+            tag::STOP => Ok(Command::Stop),
+            tag::DRAWER_SELECT => Ok(Command::DrawerSelect(reader.u8()?)),
+            tag::DRAWER_OPEN => Ok(Command::DrawerOpen(reader.u8()?)),
+            tag::GET_PARAM => Ok(Command::GetParam(reader.u8()?.try_into()?)),
+            tag::SET_PARAM => Ok(Command::SetParam(reader.u8()?.try_into()?, reader.f32()?)),
+            tag::SET_VELOCITY => Ok(Command::SetVelocity { linear: reader.f32()?, angular: reader.f32()? }),
+            tag::MOVE_TO => Ok(Command::MoveTo { x: reader.f32()?, y: reader.f32()?, angle: reader.f32()? }),
+            tag::CLEAR_QUEUE => Ok(Command::ClearQueue),
+            tag::GET_PROGRESS => Ok(Command::GetProgress),
+            tag::HALT => Ok(Command::Halt),
+            tag::PAUSE => Ok(Command::Pause),
+            tag::RESUME => Ok(Command::Resume),
+            tag::GET_STATE => Ok(Command::GetState),
+            tag::DRAWER_CLOSE => Ok(Command::DrawerClose(reader.u8()?)),
+            tag::GET_DRAWER_STATE => Ok(Command::GetDrawerState),
+            tag::SORT => Ok(Command::Sort(reader.u8()?)),
+            tag::GET_SORT_STATE => Ok(Command::GetSortState),
+            tag::HEARTBEAT => Ok(Command::Heartbeat),
+            tag::AUTOTUNE => Ok(Command::Autotune { target: reader.u8()?.try_into()?, rule: reader.u8()?.try_into()? }),
+            tag::GET_AUTOTUNE_RESULT => Ok(Command::GetAutotuneResult),
+            _ => Err(Error::UnknownTag)
+        }
+    }
+}
+
+impl Message for Reply {
+    fn write(&self, writer: &mut Writer) -> Result<(), Error> {
+        match *self {
+            Reply::Ack => writer.u8(tag::ACK),
+            Reply::Error(code) => {
+                writer.u8(tag::ERROR)?;
+                writer.u8(code as u8)
+            },
+            Reply::Param(param, value) => {
+                writer.u8(tag::PARAM)?;
+                writer.u8(param as u8)?;
+                writer.f32(value)
+            },
+            Reply::Position { x, y, angle } => {
+                writer.u8(tag::POSITION)?;
+                writer.f32(x)?;
+                writer.f32(y)?;
+                writer.f32(angle)
+            },
+            Reply::Progress { stage, queued, distance } => {
+                writer.u8(tag::PROGRESS)?;
+                writer.u8(stage as u8)?;
+                writer.u8(queued)?;
+                writer.f32(distance)
+            },
+            Reply::State(state) => {
+                writer.u8(tag::STATE)?;
+                writer.u8(state as u8)
+            },
+            Reply::DrawerState { id, open, status } => {
+                writer.u8(tag::DRAWER_STATE)?;
+                writer.u8(id)?;
+                writer.u8(open as u8)?;
+                writer.u8(status as u8)
+            },
+            Reply::SortState { bin, step, status } => {
+                writer.u8(tag::SORT_STATE)?;
+                writer.u8(bin)?;
+                writer.u8(step as u8)?;
+                writer.u8(status as u8)
+            },
+            Reply::LoopTiming { min, max, mean, overruns } => {
+                writer.u8(tag::LOOP_TIMING)?;
+                writer.f32(min)?;
+                writer.f32(max)?;
+                writer.f32(mean)?;
+                writer.u8(overruns)
+            },
+            Reply::AutotuneResult { target, status, ku, tu, kp, ki, kd } => {
+                writer.u8(tag::AUTOTUNE_RESULT)?;
+                writer.u8(target as u8)?;
+                writer.u8(status as u8)?;
+                writer.f32(ku)?;
+                writer.f32(tu)?;
+                writer.f32(kp)?;
+                writer.f32(ki)?;
+                writer.f32(kd)
+            },
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, Error> {
+        match reader.u8()? {
+            tag::ACK => Ok(Reply::Ack),
+            tag::ERROR => Ok(Reply::Error(reader.u8()?.try_into()?)),
+            tag::PARAM => Ok(Reply::Param(reader.u8()?.try_into()?, reader.f32()?)),
+            tag::POSITION => Ok(Reply::Position { x: reader.f32()?, y: reader.f32()?, angle: reader.f32()? }),
+            tag::STATE => Ok(Reply::State(reader.u8()?.try_into()?)),
+            tag::DRAWER_STATE => Ok(Reply::DrawerState { id: reader.u8()?, open: reader.bool()?, status: reader.u8()?.try_into()? }),
+            tag::SORT_STATE => Ok(Reply::SortState { bin: reader.u8()?, step: reader.u8()?.try_into()?, status: reader.u8()?.try_into()? }),
+            tag::LOOP_TIMING => Ok(Reply::LoopTiming { min: reader.f32()?, max: reader.f32()?, mean: reader.f32()?, overruns: reader.u8()? }),
+            tag::AUTOTUNE_RESULT => Ok(Reply::AutotuneResult {
+                target: reader.u8()?.try_into()?,
+                status: reader.u8()?.try_into()?,
+                ku: reader.f32()?,
+                tu: reader.f32()?,
+                kp: reader.f32()?,
+                ki: reader.f32()?,
+                kd: reader.f32()?
+            }),
+            tag::PROGRESS => Ok(Reply::Progress { stage: reader.u8()?.try_into()?, queued: reader.u8()?, distance: reader.f32()? }),
+            _ => Err(Error::UnknownTag)
+        }
+    }
+}
+
+/// Little-endian serializer over a byte slice
+pub struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.len + bytes.len();
+        self.buf.get_mut(self.len..end).ok_or(Error::BufferTooSmall)?.copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    pub fn u8(&mut self, value: u8) -> Result<(), Error> {
+        self.bytes(&[value])
+    }
+
+    pub fn f32(&mut self, value: f32) -> Result<(), Error> {
+        self.bytes(&value.to_le_bytes())
+    }
+}
+
+/// Little-endian deserializer over a byte slice
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    pub fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let end = self.pos + N;
+        let bytes = self.buf.get(self.pos..end).ok_or(Error::Truncated)?;
+        self.pos = end;
+
+        let mut array = [0; N];
+        array.copy_from_slice(bytes);
+        Ok(array)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::InvalidValue)
+        }
+    }
+
+    pub fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_le_bytes(self.bytes()?))
+    }
+}
@@ -0,0 +1,132 @@
+use protocol::{cobs, crc::crc16, encode_frame, Command, Reply, Param, ErrorCode, Stage, MotionState, ActionStatus, SortStep, AutotuneTarget, TuningRule, Error, FrameDecoder, MAX_FRAME_LEN};
+
+fn cobs_roundtrip(data: &[u8]) {
+    let mut encoded = [0_u8; 600];
+    let mut decoded = [0_u8; 600];
+
+    let len = cobs::encode(data, &mut encoded).unwrap();
+    assert!(len <= cobs::max_encoded_len(data.len()));
+    assert!(!encoded[..len].contains(&0));
+
+    let decoded_len = cobs::decode(&encoded[..len], &mut decoded).unwrap();
+    assert_eq!(&decoded[..decoded_len], data);
+}
+
+#[test]
+fn cobs_known_vectors() {
+    let mut encoded = [0_u8; 16];
+
+    let len = cobs::encode(&[0x11, 0x22, 0x00, 0x33], &mut encoded).unwrap();
+    assert_eq!(&encoded[..len], &[0x03, 0x11, 0x22, 0x02, 0x33]);
+
+    let len = cobs::encode(&[0x00, 0x00], &mut encoded).unwrap();
+    assert_eq!(&encoded[..len], &[0x01, 0x01, 0x01]);
+}
+
+#[test]
+fn cobs_roundtrips() {
+    cobs_roundtrip(&[]);
+    cobs_roundtrip(&[0]);
+    cobs_roundtrip(&[1, 0, 2, 0, 0, 3]);
+
+    let long: Vec<u8> = (0..520).map(|i| (i % 255 + 1) as u8).collect();
+    cobs_roundtrip(&long[..254]);
+    cobs_roundtrip(&long[..255]);
+    cobs_roundtrip(&long);
+}
+
+#[test]
+fn crc_check_value() {
+    assert_eq!(crc16(b"123456789"), 0x29B1);
+}
+
+fn feed<M: protocol::Message>(decoder: &mut FrameDecoder, bytes: &[u8]) -> Vec<Result<(u8, M), Error>> {
+    bytes.iter().filter_map(|&byte| decoder.push(byte)).collect()
+}
+
+#[test]
+fn commands_roundtrip() {
+    let commands = [
+        Command::MoveRelative { x: 10.0, y: -20.5, angle: 90.0 },
+        Command::SetSpeed { linear: 45.0, angular: 0.0 },
+        Command::Stop,
+        Command::DrawerSelect(2),
+        Command::DrawerOpen(0),
+        Command::GetParam(Param::AngularSpeed),
+        Command::SetParam(Param::LinearSpeed, 12.5),
+        Command::SetVelocity { linear: -10.0, angular: 30.0 },
+        Command::MoveTo { x: 100.0, y: 0.5, angle: -45.0 },
+        Command::ClearQueue,
+        Command::GetProgress,
+        Command::Halt,
+        Command::Pause,
+        Command::Resume,
+        Command::GetState,
+        Command::DrawerClose(1),
+        Command::GetDrawerState,
+        Command::Sort(1),
+        Command::GetSortState,
+        Command::Heartbeat,
+        Command::Autotune { target: AutotuneTarget::RightServo, rule: TuningRule::TyreusLuyben },
+        Command::GetAutotuneResult,
+    ];
+
+    let mut decoder = FrameDecoder::new();
+    for (sequence, command) in commands.iter().enumerate() {
+        let mut frame = [0_u8; MAX_FRAME_LEN];
+        let len = encode_frame(sequence as u8, command, &mut frame).unwrap();
+        assert_eq!(frame[len - 1], 0);
+
+        let decoded = feed::<Command>(&mut decoder, &frame[..len]);
+        assert_eq!(decoded, vec![Ok((sequence as u8, *command))]);
+    }
+}
+
+#[test]
+fn replies_roundtrip() {
+    let replies = [
+        Reply::Ack,
+        Reply::Error(ErrorCode::Busy),
+        Reply::Param(Param::LinearSpeed, 45.0),
+        Reply::Position { x: 1.0, y: 2.0, angle: -3.0 },
+        Reply::Progress { stage: Stage::Translation, queued: 3, distance: 12.5 },
+        Reply::State(MotionState::Stalled),
+        Reply::DrawerState { id: 2, open: true, status: ActionStatus::TimedOut },
+        Reply::SortState { bin: 1, step: SortStep::Dump, status: ActionStatus::Failed },
+        Reply::LoopTiming { min: 0.024, max: 0.031, mean: 0.025, overruns: 2 },
+        Reply::AutotuneResult { target: AutotuneTarget::LeftWheel, status: ActionStatus::Done, ku: 1.37, tu: 0.1, kp: 0.4, ki: 0.0, kd: 0.82 },
+    ];
+
+    let mut decoder = FrameDecoder::new();
+    for reply in replies.iter() {
+        let mut frame = [0_u8; MAX_FRAME_LEN];
+        let len = encode_frame(7, reply, &mut frame).unwrap();
+
+        assert_eq!(feed::<Reply>(&mut decoder, &frame[..len]), vec![Ok((7, *reply))]);
+    }
+}
+
+#[test]
+fn corrupted_frame_is_rejected() {
+    let mut payload = [1, 0x03, 0, 0];
+    let crc = crc16(&payload[..2]) ^ 0x0100;
+    payload[2..].copy_from_slice(&crc.to_le_bytes());
+
+    let mut frame = [0_u8; MAX_FRAME_LEN];
+    let len = cobs::encode(&payload, &mut frame).unwrap();
+
+    assert_eq!(protocol::decode_frame::<Command>(&frame[..len]), Err(Error::Crc));
+}
+
+#[test]
+fn decoder_resynchronizes_after_garbage() {
+    let mut frame = [0_u8; MAX_FRAME_LEN];
+    let len = encode_frame(3, &Command::Stop, &mut frame).unwrap();
+
+    let mut bytes = vec![0x42; MAX_FRAME_LEN * 2];
+    bytes.push(0);
+    bytes.extend_from_slice(&frame[..len]);
+
+    let mut decoder = FrameDecoder::new();
+    assert_eq!(feed::<Command>(&mut decoder, &bytes), vec![Err(Error::FrameTooLong), Ok((3, Command::Stop))]);
+}
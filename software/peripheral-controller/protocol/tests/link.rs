@@ -0,0 +1,39 @@
+use units::Time;
+use protocol::link::{LinkSupervisor, LinkLost};
+
+const TIME_DELTA: Time = Time::from_seconds(0.1);
+const TIMEOUT: Time = Time::from_seconds(0.5);
+
+fn run(link: &mut LinkSupervisor, seconds: f32) -> usize {
+    (0..(seconds / TIME_DELTA.to_seconds()).round() as usize)
+        .filter(|_| link.update(TIME_DELTA))
+        .count()
+}
+
+#[test]
+fn frames_keep_the_link_up() {
+    let mut link = LinkSupervisor::new(TIMEOUT);
+
+    for _ in 0..10 {
+        assert_eq!(run(&mut link, 0.4), 0);
+        assert_eq!(link.feed(), Ok(()));
+    }
+    assert!(!link.is_lost());
+}
+
+#[test]
+fn silence_is_reported_once() {
+    let mut link = LinkSupervisor::new(TIMEOUT);
+
+    assert_eq!(run(&mut link, 3.0), 1);
+    assert!(link.is_lost());
+    assert!(link.get_silence() > Time::from_seconds(2.9));
+
+    // the first frame after the loss is rejected, the link is up again
+    assert_eq!(link.feed(), Err(LinkLost));
+    assert!(!link.is_lost());
+    assert_eq!(link.feed(), Ok(()));
+
+    assert_eq!(run(&mut link, 0.4), 0);
+    assert_eq!(run(&mut link, 0.2), 1);
+}
@@ -0,0 +1,182 @@
+//! Index pulse (Z channel) of an encoder, once per revolution.
+//!
+//! The interrupt of the index edge latches the raw counter value, the encoder turns it into
+//! a multi-turn count on its next update. Successive index counts must be whole revolutions
+//! apart, any disagreement is a count lost or gained in between.
+
+use core::{convert::Infallible, sync::atomic::{AtomicBool, AtomicU32, Ordering}};
+
+use embedded_hal::digital::v2::InputPin;
+
+use crate::{RotaryEncoder, WrappingCount, velocity::VelocityEstimator};
+
+/// Raw counter values latched at the index edges
+pub trait IndexLatch<C> {
+    /// The latest latched value, if there was an index edge since the last call
+    fn take_index(&mut self) -> Option<C>;
+}
+
+/// Placeholder for encoders without an index pulse
+pub enum NoIndex {}
+
+impl<C> IndexLatch<C> for NoIndex {
+    fn take_index(&mut self) -> Option<C> {
+        match *self {}
+    }
+}
+
+/// Counter value latched by the index interrupt, usually a `static`
+pub struct LatchedIndex {
+    count: AtomicU32,
+    pending: AtomicBool
+}
+
+impl LatchedIndex {
+    pub const fn new() -> Self {
+        Self {
+            count: AtomicU32::new(0),
+            pending: AtomicBool::new(false)
+        }
+    }
+
+    /// To be called on the index edge with the raw counter value, e.g. the `CNT` of the timer
+    pub fn latch(&self, count: u32) {
+        self.count.store(count, Ordering::Relaxed);
+        self.pending.store(true, Ordering::Release);
+    }
+}
+
+impl Default for LatchedIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IndexLatch<u32> for &LatchedIndex {
+    fn take_index(&mut self) -> Option<u32> {
+        match self.pending.swap(false, Ordering::Acquire) {
+            true => Some(self.count.load(Ordering::Relaxed)),
+            false => None
+        }
+    }
+}
+
+impl IndexLatch<u16> for &LatchedIndex {
+    fn take_index(&mut self) -> Option<u16> {
+        IndexLatch::<u32>::take_index(self).map(|count| count as u16)
+    }
+}
+
+/// Placeholder for homing at the first index, without a reference switch
+pub enum NoReference {}
+
+impl InputPin for NoReference {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        match *self {}
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        match *self {}
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomingState {
+    Idle,
+    /// The axis has to move towards the reference switch
+    SearchingReference,
+    /// Past the reference switch, the next index edge is the zero
+    SearchingIndex,
+    Homed
+}
+
+/// Zeroes the position of an encoder at the first index after a reference switch, which
+/// picks a single revolution out of the travel. The axis has to be moved by the caller
+/// while homing, slow enough that the switch is not passed within an update
+pub struct IndexHoming<SW = NoReference>
+where
+    SW: InputPin
+{
+    reference: Option<SW>,
+
+    state: HomingState,
+    // index edges seen by the encoder when the reference switch was reached
+    indexes_at_reference: u32
+}
+
+impl IndexHoming {
+    pub fn new() -> Self {
+        Self {
+            reference: None,
+
+            state: HomingState::Idle,
+            indexes_at_reference: 0
+        }
+    }
+}
+
+impl Default for IndexHoming {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<SW> IndexHoming<SW>
+where
+    SW: InputPin
+{
+    /// The switch reads high at the reference
+    pub fn with_reference_switch<NSW: InputPin>(self, reference: NSW) -> IndexHoming<NSW> {
+        IndexHoming {
+            reference: Some(reference),
+
+            state: self.state,
+            indexes_at_reference: self.indexes_at_reference
+        }
+    }
+
+    pub fn get_state(&self) -> HomingState {
+        self.state
+    }
+
+    pub fn start(&mut self) {
+        self.state = HomingState::SearchingReference;
+    }
+
+    /// To be called after every update of the encoder
+    pub fn update<QEI, V, I>(&mut self, encoder: &mut RotaryEncoder<QEI, V, I>) -> Result<HomingState, SW::Error>
+    where
+        QEI: embedded_hal::Qei,
+        QEI::Count: WrappingCount,
+        V: VelocityEstimator,
+        I: IndexLatch<QEI::Count>
+    {
+        match self.state {
+            HomingState::SearchingReference => {
+                let at_reference = match self.reference {
+                    Some(ref reference) => reference.is_high()?,
+                    None => true
+                };
+
+                if at_reference {
+                    self.indexes_at_reference = encoder.get_index_edges();
+                    self.state = HomingState::SearchingIndex;
+                }
+            },
+            HomingState::SearchingIndex => {
+                let index_count = match encoder.get_index_count() {
+                    Some(index_count) if encoder.get_index_edges() != self.indexes_at_reference => index_count,
+                    _ => return Ok(self.state)
+                };
+
+                encoder.set_count(encoder.get_count() - index_count);
+                self.state = HomingState::Homed;
+            },
+            _ => {}
+        }
+
+        Ok(self.state)
+    }
+}
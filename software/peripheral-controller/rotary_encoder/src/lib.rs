@@ -1,109 +1,262 @@
 #![no_std]
 
-use core::fmt::Display;
+//! Quadrature encoder on a hardware counter. The counter wraps, the counts are accumulated
+//! into a 64-bit multi-turn count, which is exact as long as the counter moves by less than
+//! half of its range between two updates.
 
-use num_traits::{AsPrimitive, NumCast};
+pub mod velocity;
+pub mod quadrature;
+pub mod index;
 
 use embedded_hal::Qei;
 
+use units::{Time, Angle, AngularVelocity};
 use encoder::{Update, GetPosition, GetVelocity};
 
-pub struct RotaryEncoder<QEI>
+use crate::{velocity::{VelocityEstimator, Difference}, index::{IndexLatch, NoIndex}};
+
+/// Hardware counter value, which wraps around its range
+pub trait WrappingCount: Copy {
+    /// Signed distance from the previous value, the shorter way around
+    fn delta_since(self, previous: Self) -> i64;
+}
+
+impl WrappingCount for u16 {
+    fn delta_since(self, previous: Self) -> i64 {
+        self.wrapping_sub(previous) as i16 as i64
+    }
+}
+
+impl WrappingCount for u32 {
+    fn delta_since(self, previous: Self) -> i64 {
+        self.wrapping_sub(previous) as i32 as i64
+    }
+}
+
+pub struct RotaryEncoder<QEI, V = Difference, I = NoIndex>
 where
     QEI: Qei,
-    QEI::Count: Into<i64>
+    QEI::Count: WrappingCount,
+    V: VelocityEstimator,
+    I: IndexLatch<QEI::Count>
 {
     qei: QEI,
+    velocity_estimator: V,
+    index: Option<I>,
 
     pub ppr: f32,
+    /// The position grows as the counter counts down
     pub reverse: bool,
+    /// Counts that successive index edges may disagree by, the edge is a count
+    /// further when it is passed in the other direction
+    pub index_tolerance: i64,
 
-    // angular
-    position: f32,
-    velocity: f32,
+    count: i64,
+    last_raw_count: QEI::Count,
 
-    last_count: i64,
+    index_count: Option<i64>,
+    index_edges: u32,
+    index_errors: u32,
+    index_drift: i64,
 }
 
-impl<QEI> RotaryEncoder<QEI> 
+impl<QEI> RotaryEncoder<QEI>
 where
     QEI: Qei,
-    QEI::Count: Into<i64>
+    QEI::Count: WrappingCount
 {
+    /// The position starts at zero, wherever the counter is
     pub fn new(qei: QEI, pulse_per_rev: f32, reverse: bool) -> Self {
+        let last_raw_count = qei.count();
+
         Self {
             qei,
+            velocity_estimator: Difference::default(),
+            index: None,
 
             ppr: pulse_per_rev,
             reverse,
+            index_tolerance: 1,
 
-            position: 0_f32,
-            velocity: 0_f32,
+            count: 0,
+            last_raw_count,
 
-            last_count: 0,
-        }
-    }
-
-    fn maybe_reverse(&self, val: f32) -> f32 {
-        if self.reverse {
-            val * -1.0
-        } else {
-            val
+            index_count: None,
+            index_edges: 0,
+            index_errors: 0,
+            index_drift: 0,
         }
     }
 }
 
-impl<QEI> Update for RotaryEncoder<QEI> 
+impl<QEI, V, I> RotaryEncoder<QEI, V, I>
 where
     QEI: Qei,
-    QEI::Count: Into<i64> + SignedPairMatcher,
-    <QEI::Count as SignedPairMatcher>::Type: 'static + Copy + NumCast + Display,
-    i64: AsPrimitive<<QEI::Count as SignedPairMatcher>::Type>
+    QEI::Count: WrappingCount,
+    V: VelocityEstimator,
+    I: IndexLatch<QEI::Count>
 {
-    fn update(&mut self, time_delta_seconds: f32) {
-        let count: i64 = self.qei.count().into();
-        let count_delta: <QEI::Count as SignedPairMatcher>::Type = (self.last_count - count).as_();
-        let count_delta: f32 = NumCast::from(count_delta).unwrap();
+    /// Replaces the difference of the positions of the last two updates
+    pub fn with_velocity_estimator<W: VelocityEstimator>(self, velocity_estimator: W) -> RotaryEncoder<QEI, W, I> {
+        RotaryEncoder {
+            qei: self.qei,
+            velocity_estimator,
+            index: self.index,
+
+            ppr: self.ppr,
+            reverse: self.reverse,
+            index_tolerance: self.index_tolerance,
+
+            count: self.count,
+            last_raw_count: self.last_raw_count,
+
+            index_count: self.index_count,
+            index_edges: self.index_edges,
+            index_errors: self.index_errors,
+            index_drift: self.index_drift,
+        }
+    }
+
+    /// Latches the count at the index edges of the same counter, once per revolution
+    pub fn with_index<J: IndexLatch<QEI::Count>>(self, index: J) -> RotaryEncoder<QEI, V, J> {
+        RotaryEncoder {
+            qei: self.qei,
+            velocity_estimator: self.velocity_estimator,
+            index: Some(index),
+
+            ppr: self.ppr,
+            reverse: self.reverse,
+            index_tolerance: self.index_tolerance,
+
+            count: self.count,
+            last_raw_count: self.last_raw_count,
+
+            index_count: None,
+            index_edges: 0,
+            index_errors: 0,
+            index_drift: 0,
+        }
+    }
+
+    /// Multi-turn count, in the direction of the position
+    pub fn get_count(&self) -> i64 {
+        self.count
+    }
+
+    /// Redefines the current position, e.g. after homing, the velocity is not affected
+    pub fn set_count(&mut self, count: i64) {
+        let offset = count - self.count;
+        self.velocity_estimator.offset(offset as f32 / self.ppr);
+        if let Some(ref mut index_count) = self.index_count {
+            *index_count += offset;
+        }
+
+        self.count = count;
+    }
+
+    /// Redefines the current position, rounded to a whole count
+    pub fn set_position(&mut self, position: Angle) {
+        self.set_count(libm::roundf(position.to_revolutions() * self.ppr) as i64);
+    }
+
+    fn get_revolutions(&self) -> f32 {
+        self.count as f32 / self.ppr
+    }
 
-        let last_position = self.position;
-        self.position += count_delta / self.ppr;
+    /// Count at the latest index edge
+    pub fn get_index_count(&self) -> Option<i64> {
+        self.index_count
+    }
 
-        let position_delta = self.position - last_position;
-        self.velocity = position_delta / time_delta_seconds;
+    /// Index edges seen since the start, wrapping
+    pub fn get_index_edges(&self) -> u32 {
+        self.index_edges
+    }
 
-        self.last_count = count;
+    /// Index edges that were not a whole number of revolutions away from the previous one
+    pub fn get_index_errors(&self) -> u32 {
+        self.index_errors
+    }
+
+    /// Counts lost or gained between the last two index edges that disagreed
+    pub fn get_index_drift(&self) -> i64 {
+        self.index_drift
+    }
+
+    fn update_index(&mut self, raw_count_before: QEI::Count, count_before: i64) {
+        let latched = match self.index {
+            Some(ref mut index) => index.take_index(),
+            None => None
+        };
+        let latched = match latched {
+            Some(latched) => latched,
+            None => return
+        };
+
+        // the edge was within the last update, so it is close to the previous count
+        let delta = latched.delta_since(raw_count_before);
+        let index_count = count_before + if self.reverse { -delta } else { delta };
+
+        if let Some(last_index_count) = self.index_count {
+            let distance = index_count - last_index_count;
+            let turns = libm::round(distance as f64 / self.ppr as f64);
+            let drift = distance - (turns * self.ppr as f64) as i64;
+
+            if drift.abs() > self.index_tolerance {
+                self.index_errors = self.index_errors.wrapping_add(1);
+                self.index_drift = drift;
+            }
+        }
+
+        self.index_count = Some(index_count);
+        self.index_edges = self.index_edges.wrapping_add(1);
     }
 }
 
-impl<QEI> GetPosition for RotaryEncoder<QEI> 
+impl<QEI, V, I> Update for RotaryEncoder<QEI, V, I>
 where
     QEI: Qei,
-    QEI::Count: Into<i64>
+    QEI::Count: WrappingCount,
+    V: VelocityEstimator,
+    I: IndexLatch<QEI::Count>
 {
-    fn get_position(&self) -> Self::Position {
-        self.maybe_reverse(self.position)
+    fn update(&mut self, time_delta: Time) {
+        let (raw_count_before, count_before) = (self.last_raw_count, self.count);
+
+        let raw_count = self.qei.count();
+        let delta = raw_count.delta_since(self.last_raw_count);
+        self.last_raw_count = raw_count;
+
+        self.count += if self.reverse { -delta } else { delta };
+        self.update_index(raw_count_before, count_before);
+        self.velocity_estimator.update(self.get_revolutions(), time_delta.to_seconds());
     }
 }
 
-impl<QEI> GetVelocity for RotaryEncoder<QEI> 
+impl<QEI, V, I> GetPosition for RotaryEncoder<QEI, V, I>
 where
     QEI: Qei,
-    QEI::Count: Into<i64>
+    QEI::Count: WrappingCount,
+    V: VelocityEstimator,
+    I: IndexLatch<QEI::Count>
 {
-    fn get_velocity(&self) -> f32 {
-        self.maybe_reverse(self.velocity)
-    }
-}
+    type Position = Angle;
 
-trait SignedPairMatcher {
-    type Type;
+    fn get_position(&self) -> Self::Position {
+        Angle::from_revolutions(self.get_revolutions())
+    }
 }
 
-impl SignedPairMatcher for u16 {
-    type Type = i16;
-}
+impl<QEI, V, I> GetVelocity for RotaryEncoder<QEI, V, I>
+where
+    QEI: Qei,
+    QEI::Count: WrappingCount,
+    V: VelocityEstimator,
+    I: IndexLatch<QEI::Count>
+{
+    type Velocity = AngularVelocity;
 
-impl SignedPairMatcher for u32 {
-    type Type = i32;
+    fn get_velocity(&self) -> Self::Velocity {
+        AngularVelocity::from_rps(self.velocity_estimator.get_velocity())
+    }
 }
-
@@ -0,0 +1,130 @@
+//! Quadrature decoding in software, for encoders on pins without an encoder capable timer.
+//!
+//! The decoder is owned by the interrupt of both pin edges (EXTI), which calls `on_edge`.
+//! It counts into a `QuadratureCounter`, usually a `static`, and a reference to the counter
+//! is the `Qei` that a `RotaryEncoder` reads in the control loop.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use embedded_hal::{Qei, Direction, digital::v2::InputPin};
+
+/// Count change for a transition from the previous to the new state of the pins,
+/// indexed by `previous << 2 | new`, where a state is `a << 1 | b`.
+/// Both pins changing at once is illegal, an edge was missed and the direction is unknown
+const TRANSITIONS: [Option<i8>; 16] = [
+    Some(0), Some(1), Some(-1), None,
+    Some(-1), Some(0), None, Some(1),
+    Some(1), None, Some(0), Some(-1),
+    None, Some(-1), Some(1), Some(0)
+];
+
+/// Counts of a decoder, shared between its interrupt and the readers
+pub struct QuadratureCounter {
+    count: AtomicU32,
+    errors: AtomicU32,
+    downcounting: AtomicBool
+}
+
+impl QuadratureCounter {
+    pub const fn new() -> Self {
+        Self {
+            count: AtomicU32::new(0),
+            errors: AtomicU32::new(0),
+            downcounting: AtomicBool::new(false)
+        }
+    }
+
+    /// Illegal transitions since the start, each of them lost an unknown direction count
+    pub fn get_errors(&self) -> u32 {
+        self.errors.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for QuadratureCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Qei for &QuadratureCounter {
+    type Count = u32;
+
+    /// Wrapping, four counts per pulse
+    fn count(&self) -> u32 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    fn direction(&self) -> Direction {
+        match self.downcounting.load(Ordering::Relaxed) {
+            true => Direction::Downcounting,
+            false => Direction::Upcounting
+        }
+    }
+}
+
+pub struct QuadratureDecoder<'a, A, B>
+where
+    A: InputPin,
+    B: InputPin<Error = A::Error>
+{
+    a: A,
+    b: B,
+    counter: &'a QuadratureCounter,
+
+    state: u8
+}
+
+impl<'a, A, B> QuadratureDecoder<'a, A, B>
+where
+    A: InputPin,
+    B: InputPin<Error = A::Error>
+{
+    /// Reads the initial state of the pins
+    pub fn new(a: A, b: B, counter: &'a QuadratureCounter) -> Result<Self, A::Error> {
+        let mut decoder = Self {
+            a,
+            b,
+            counter,
+
+            state: 0
+        };
+        decoder.state = decoder.read_state()?;
+
+        Ok(decoder)
+    }
+
+    pub fn get_counter(&self) -> &'a QuadratureCounter {
+        self.counter
+    }
+
+    /// To be called on every edge of either pin. The interrupt latency has to stay below
+    /// the time between two edges, otherwise transitions are lost or illegal
+    pub fn on_edge(&mut self) -> Result<(), A::Error> {
+        let state = self.read_state()?;
+        let transition = (self.state << 2 | state) as usize;
+        self.state = state;
+
+        match TRANSITIONS[transition] {
+            Some(0) => {},
+            Some(delta) => {
+                // the only writer, so a load and a store are enough even without atomic read-modify-write
+                let count = self.counter.count.load(Ordering::Relaxed);
+                self.counter.count.store(count.wrapping_add(delta as u32), Ordering::Relaxed);
+                self.counter.downcounting.store(delta < 0, Ordering::Relaxed);
+            },
+            None => {
+                let errors = self.counter.errors.load(Ordering::Relaxed);
+                self.counter.errors.store(errors.wrapping_add(1), Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn release(self) -> (A, B) {
+        (self.a, self.b)
+    }
+
+    fn read_state(&self) -> Result<u8, A::Error> {
+        Ok((self.a.is_high()? as u8) << 1 | self.b.is_high()? as u8)
+    }
+}
@@ -0,0 +1,219 @@
+//! Velocity estimators, fed with the quantized position on every update.
+//!
+//! A plain difference of two positions jumps by a whole count per update at low speed,
+//! the other estimators trade some delay for less of that noise.
+
+/// Estimates the velocity from the positions, in position units per second
+pub trait VelocityEstimator {
+    fn update(&mut self, position: f32, time_delta_seconds: f32);
+    fn get_velocity(&self) -> f32;
+
+    /// The positions are redefined by adding the offset, it is not a movement
+    fn offset(&mut self, offset: f32);
+}
+
+/// Position difference over the last update, starting from zero
+#[derive(Default)]
+pub struct Difference {
+    last_position: f32,
+    velocity: f32
+}
+
+impl VelocityEstimator for Difference {
+    fn update(&mut self, position: f32, time_delta_seconds: f32) {
+        self.velocity = (position - self.last_position) / time_delta_seconds;
+        self.last_position = position;
+    }
+
+    fn get_velocity(&self) -> f32 {
+        self.velocity
+    }
+
+    fn offset(&mut self, offset: f32) {
+        self.last_position += offset;
+    }
+}
+
+/// Position difference over the last `N` updates, which cuts the quantization noise
+/// by `N` at the cost of `N / 2` updates of delay
+pub struct MovingAverage<const N: usize> {
+    // position and time deltas of the last updates
+    window: [(f32, f32); N],
+    next: usize,
+    filled: usize,
+    last_position: Option<f32>,
+    velocity: f32
+}
+
+impl<const N: usize> MovingAverage<N> {
+    pub fn new() -> Self {
+        assert!(N > 0);
+
+        Self {
+            window: [(0.0, 0.0); N],
+            next: 0,
+            filled: 0,
+            last_position: None,
+            velocity: 0.0
+        }
+    }
+}
+
+impl<const N: usize> Default for MovingAverage<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> VelocityEstimator for MovingAverage<N> {
+    fn update(&mut self, position: f32, time_delta_seconds: f32) {
+        let last_position = match self.last_position.replace(position) {
+            Some(last_position) => last_position,
+            None => return
+        };
+
+        self.window[self.next] = (position - last_position, time_delta_seconds);
+        self.next = (self.next + 1) % N;
+        self.filled = (self.filled + 1).min(N);
+
+        // summed every time, so that no rounding error builds up
+        let (distance, time) = self.window[..self.filled].iter()
+            .fold((0.0, 0.0), |(distance, time), (position_delta, time_delta)| (distance + position_delta, time + time_delta));
+        self.velocity = distance / time;
+    }
+
+    fn get_velocity(&self) -> f32 {
+        self.velocity
+    }
+
+    fn offset(&mut self, offset: f32) {
+        if let Some(ref mut last_position) = self.last_position {
+            *last_position += offset;
+        }
+    }
+}
+
+/// Second order tracking loop, like a PLL locked onto the position: a position estimate
+/// follows the measurement, driven by the velocity estimate, which integrates the error
+pub struct TrackingObserver {
+    pub kp: f32,
+    pub ki: f32,
+
+    position: Option<f32>,
+    velocity: f32
+}
+
+impl TrackingObserver {
+    pub fn new(kp: f32, ki: f32) -> Self {
+        Self {
+            kp,
+            ki,
+
+            position: None,
+            velocity: 0.0
+        }
+    }
+
+    /// Critically damped loop with the given bandwidth, Hz. It should stay well below
+    /// the update rate, the lower it is, the smoother and the more delayed the estimate
+    pub fn with_bandwidth(bandwidth_hz: f32) -> Self {
+        let omega = 2.0 * core::f32::consts::PI * bandwidth_hz;
+
+        Self::new(2.0 * omega, omega * omega)
+    }
+}
+
+impl VelocityEstimator for TrackingObserver {
+    fn update(&mut self, position: f32, time_delta_seconds: f32) {
+        let estimate = match self.position {
+            Some(estimate) => estimate,
+            None => {
+                self.position = Some(position);
+                return;
+            }
+        };
+
+        let error = position - estimate;
+        self.velocity += self.ki * error * time_delta_seconds;
+        self.position = Some(estimate + (self.velocity + self.kp * error) * time_delta_seconds);
+    }
+
+    fn get_velocity(&self) -> f32 {
+        self.velocity
+    }
+
+    fn offset(&mut self, offset: f32) {
+        if let Some(ref mut position) = self.position {
+            *position += offset;
+        }
+    }
+}
+
+/// Time of the latest encoder edge, e.g. from an input capture channel
+pub trait EdgeTimestamp {
+    /// Microseconds, wrapping
+    fn get_last_edge_micros(&self) -> u32;
+    /// Microseconds on the same clock, wrapping
+    fn get_now_micros(&self) -> u32;
+}
+
+/// Distance between the latest edges of the last two updates over the time between them,
+/// which is exact at any speed, as long as an edge comes in every update (the M/T method)
+pub struct EdgeTiming<T: EdgeTimestamp> {
+    timestamps: T,
+    /// Position change per count
+    pub resolution: f32,
+
+    last_edge: Option<(u32, f32)>,
+    velocity: f32
+}
+
+impl<T: EdgeTimestamp> EdgeTiming<T> {
+    pub fn new(timestamps: T, resolution: f32) -> Self {
+        Self {
+            timestamps,
+            resolution,
+
+            last_edge: None,
+            velocity: 0.0
+        }
+    }
+}
+
+impl<T: EdgeTimestamp> VelocityEstimator for EdgeTiming<T> {
+    fn update(&mut self, position: f32, _time_delta_seconds: f32) {
+        let edge = self.timestamps.get_last_edge_micros();
+
+        let (last_edge, last_position) = match self.last_edge {
+            Some(last_edge) => last_edge,
+            None => {
+                self.last_edge = Some((edge, position));
+                return;
+            }
+        };
+
+        if position != last_position {
+            let period = edge.wrapping_sub(last_edge) as f32 / 1_000_000.0;
+            if period > 0.0 {
+                self.velocity = (position - last_position) / period;
+            }
+            self.last_edge = Some((edge, position));
+        } else {
+            // without a new edge the speed can only be as high as a single count since the last one
+            let since_edge = self.timestamps.get_now_micros().wrapping_sub(last_edge) as f32 / 1_000_000.0;
+            let max_speed = self.resolution / since_edge;
+
+            self.velocity = self.velocity.min(max_speed).max(-max_speed);
+        }
+    }
+
+    fn get_velocity(&self) -> f32 {
+        self.velocity
+    }
+
+    fn offset(&mut self, offset: f32) {
+        if let Some((_, ref mut position)) = self.last_edge {
+            *position += offset;
+        }
+    }
+}
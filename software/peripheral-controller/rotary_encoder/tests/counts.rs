@@ -0,0 +1,127 @@
+use std::{cell::Cell, rc::Rc};
+
+use embedded_hal::{Qei, Direction};
+
+use units::{Time, Angle};
+use encoder::{Update, GetPosition, GetVelocity};
+use rotary_encoder::RotaryEncoder;
+
+const PPR: f32 = 1440.0;
+const TIME_DELTA: Time = Time::from_seconds(0.025);
+
+struct MockQei<C: Copy>(Rc<Cell<C>>);
+
+impl<C: Copy> Qei for MockQei<C> {
+    type Count = C;
+
+    fn count(&self) -> C {
+        self.0.get()
+    }
+
+    fn direction(&self) -> Direction {
+        Direction::Upcounting
+    }
+}
+
+/// Moves the counter by `step` for `updates` updates from `start`, the accumulated count
+/// must follow exactly, across the wraps
+fn run_u16(start: u16, step: i64, updates: i64, reverse: bool) {
+    let raw = Rc::new(Cell::new(start));
+    let mut encoder = RotaryEncoder::new(MockQei(raw.clone()), PPR, reverse);
+
+    for update in 1..=updates {
+        raw.set(raw.get().wrapping_add(step as u16));
+        encoder.update(TIME_DELTA);
+
+        let expected = if reverse { -step * update } else { step * update };
+        assert_eq!(encoder.get_count(), expected, "start {} step {} update {}", start, step, update);
+    }
+}
+
+#[test]
+fn u16_counts_survive_wraps_from_any_start() {
+    // the largest step is the half range minus one, the fastest that is still unambiguous
+    let steps = [1, 7, 255, 1000, i16::MAX as i64];
+
+    for start in 0..=u16::MAX {
+        for &step in steps.iter() {
+            for reverse in [false, true] {
+                run_u16(start, step, 3, reverse);
+                run_u16(start, -step, 3, reverse);
+            }
+        }
+    }
+}
+
+#[test]
+fn u16_counts_keep_going_for_many_turns() {
+    // 100 wraps of the counter in both directions
+    let updates = 100 * 65_536 / 1000;
+
+    run_u16(u16::MAX - 10, 1000, updates, false);
+    run_u16(10, -1000, updates, false);
+    run_u16(u16::MAX - 10, 1000, updates, true);
+}
+
+#[test]
+fn u32_counts_survive_wraps() {
+    let steps = [1, 1000, 1 << 20, i32::MAX as i64];
+    let starts = [0, 1, u32::MAX / 2, u32::MAX / 2 + 1, u32::MAX - 1, u32::MAX];
+
+    for &start in starts.iter() {
+        for &step in steps.iter() {
+            for reverse in [false, true] {
+                for direction in [1, -1] {
+                    let raw = Rc::new(Cell::new(start));
+                    let mut encoder = RotaryEncoder::new(MockQei(raw.clone()), PPR, reverse);
+
+                    for update in 1..=5 {
+                        raw.set(raw.get().wrapping_add((direction * step) as u32));
+                        encoder.update(TIME_DELTA);
+
+                        let expected = direction * step * update;
+                        let expected = if reverse { -expected } else { expected };
+                        assert_eq!(encoder.get_count(), expected, "start {} step {}", start, direction * step);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn position_starts_at_zero_and_can_be_set() {
+    let raw = Rc::new(Cell::new(12_345_u16));
+    let mut encoder = RotaryEncoder::new(MockQei(raw.clone()), PPR, false);
+    assert_eq!(encoder.get_position().to_revolutions(), 0.0);
+
+    raw.set(12_345 + 720);
+    encoder.update(TIME_DELTA);
+    assert_eq!(encoder.get_position().to_revolutions(), 0.5);
+
+    encoder.set_position(Angle::from_revolutions(2.0));
+    assert_eq!(encoder.get_count(), 2880);
+
+    raw.set(12_345 + 1440);
+    encoder.update(TIME_DELTA);
+    assert_eq!(encoder.get_count(), 3600);
+
+    encoder.set_count(0);
+    assert_eq!(encoder.get_position(), Angle::ZERO);
+}
+
+#[test]
+fn setting_the_position_is_not_a_movement() {
+    let raw = Rc::new(Cell::new(0_u16));
+    let mut encoder = RotaryEncoder::new(MockQei(raw.clone()), PPR, false);
+
+    for update in 1..=10 {
+        raw.set(update * 36);
+        encoder.update(TIME_DELTA);
+    }
+    encoder.set_position(Angle::from_revolutions(-100.0));
+
+    raw.set(11 * 36);
+    encoder.update(TIME_DELTA);
+    assert!((encoder.get_velocity().to_rps() - 1.0).abs() < 1e-3, "{:?}", encoder.get_velocity());
+}
@@ -0,0 +1,163 @@
+use std::{cell::Cell, convert::Infallible, rc::Rc};
+
+use embedded_hal::{Qei, Direction, digital::v2::InputPin};
+
+use units::Time;
+use encoder::{Update, GetPosition};
+use rotary_encoder::{RotaryEncoder, index::{LatchedIndex, IndexHoming, HomingState}};
+
+const PPR: f32 = 1000.0;
+const TIME_DELTA: Time = Time::from_seconds(0.025);
+/// Raw count of the index edge, modulo `PPR`
+const INDEX_PHASE: i64 = 300;
+
+struct MockQei(Rc<Cell<u16>>);
+
+impl Qei for MockQei {
+    type Count = u16;
+
+    fn count(&self) -> u16 {
+        self.0.get()
+    }
+
+    fn direction(&self) -> Direction {
+        Direction::Upcounting
+    }
+}
+
+#[derive(Clone, Default)]
+struct MockPin(Rc<Cell<bool>>);
+
+impl InputPin for MockPin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.0.get())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.0.get())
+    }
+}
+
+/// Shaft turning by whole counts, the index interrupt fires once per revolution
+struct Shaft<'a> {
+    angle: i64,
+    raw: Rc<Cell<u16>>,
+    index: &'a LatchedIndex,
+    /// Counts the counter misses from now on
+    lost: i64
+}
+
+impl<'a> Shaft<'a> {
+    fn turn(&mut self, counts: i64) {
+        let step = counts.signum();
+
+        for _ in 0..counts.abs() {
+            self.angle += step;
+            if self.lost > 0 {
+                self.lost -= 1;
+            } else {
+                self.raw.set(self.raw.get().wrapping_add(step as u16));
+            }
+
+            if self.angle.rem_euclid(PPR as i64) == INDEX_PHASE {
+                self.index.latch(self.raw.get() as u32);
+            }
+        }
+    }
+}
+
+#[test]
+fn index_edges_are_whole_turns_apart() {
+    let index = LatchedIndex::new();
+    let raw = Rc::new(Cell::new(u16::MAX - 100));
+    let mut shaft = Shaft { angle: 0, raw: raw.clone(), index: &index, lost: 0 };
+    let mut encoder = RotaryEncoder::new(MockQei(raw), PPR, false).with_index(&index);
+
+    for _ in 0..100 {
+        shaft.turn(170);
+        encoder.update(TIME_DELTA);
+    }
+    assert_eq!(encoder.get_index_edges(), 17);
+    assert_eq!(encoder.get_index_count(), Some(16 * 1000 + INDEX_PHASE));
+
+    // and back, where the edge is crossed at the same count
+    for _ in 0..100 {
+        shaft.turn(-170);
+        encoder.update(TIME_DELTA);
+    }
+    assert_eq!(encoder.get_index_count(), Some(INDEX_PHASE));
+    assert_eq!(encoder.get_index_errors(), 0);
+}
+
+#[test]
+fn lost_counts_are_detected() {
+    let index = LatchedIndex::new();
+    let raw = Rc::new(Cell::new(0));
+    let mut shaft = Shaft { angle: 0, raw: raw.clone(), index: &index, lost: 0 };
+    let mut encoder = RotaryEncoder::new(MockQei(raw), PPR, true).with_index(&index);
+
+    for _ in 0..30 {
+        shaft.turn(-170);
+        encoder.update(TIME_DELTA);
+    }
+    assert_eq!(encoder.get_index_errors(), 0);
+
+    shaft.lost = 5;
+    for _ in 0..30 {
+        shaft.turn(-170);
+        encoder.update(TIME_DELTA);
+    }
+    assert_eq!(encoder.get_index_errors(), 1);
+    assert_eq!(encoder.get_index_drift(), -5);
+}
+
+#[test]
+fn homes_at_the_first_index_after_the_reference() {
+    let index = LatchedIndex::new();
+    let raw = Rc::new(Cell::new(0));
+    let reference = MockPin::default();
+    let mut shaft = Shaft { angle: 0, raw: raw.clone(), index: &index, lost: 0 };
+    let mut encoder = RotaryEncoder::new(MockQei(raw), PPR, false).with_index(&index);
+    let mut homing = IndexHoming::new().with_reference_switch(reference.clone());
+
+    homing.start();
+    let mut homed_at = None;
+    for _ in 0..200 {
+        shaft.turn(50);
+        // the switch is on between 2.5 and 2.8 turns
+        reference.0.set((2500..2800).contains(&shaft.angle));
+        encoder.update(TIME_DELTA);
+
+        if homing.update(&mut encoder).unwrap() == HomingState::Homed {
+            homed_at.get_or_insert(shaft.angle);
+        }
+    }
+
+    // the first index after the switch is at 3.3 turns, two before it were skipped
+    assert!(homed_at.is_some());
+    let expected = (shaft.angle - 3300) as f32 / PPR;
+    assert!((encoder.get_position().to_revolutions() - expected).abs() < 1e-6, "{:?}", encoder.get_position());
+    assert_eq!(encoder.get_index_count(), Some(6 * 1000));
+}
+
+#[test]
+fn homes_at_the_first_index_without_a_reference() {
+    let index = LatchedIndex::new();
+    let raw = Rc::new(Cell::new(0));
+    let mut shaft = Shaft { angle: 0, raw: raw.clone(), index: &index, lost: 0 };
+    let mut encoder = RotaryEncoder::new(MockQei(raw), PPR, false).with_index(&index);
+    let mut homing = IndexHoming::new();
+
+    homing.start();
+    for _ in 0..10 {
+        shaft.turn(-100);
+        encoder.update(TIME_DELTA);
+        homing.update(&mut encoder).unwrap();
+    }
+
+    // the index at -0.7 turns
+    assert_eq!(homing.get_state(), HomingState::Homed);
+    assert_eq!(encoder.get_count(), -1000 + 700);
+}
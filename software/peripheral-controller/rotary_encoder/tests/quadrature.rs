@@ -0,0 +1,120 @@
+use std::{cell::Cell, convert::Infallible, rc::Rc};
+
+use embedded_hal::{Qei, Direction, digital::v2::InputPin};
+
+use units::Time;
+use encoder::{Update, GetPosition};
+use rotary_encoder::{RotaryEncoder, quadrature::{QuadratureCounter, QuadratureDecoder}};
+
+const PPR: f32 = 400.0;
+
+#[derive(Clone, Default)]
+struct MockPin(Rc<Cell<bool>>);
+
+impl InputPin for MockPin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.0.get())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.0.get())
+    }
+}
+
+/// Pin states in the order the decoder counts up, b leads a
+const FORWARD: [(bool, bool); 4] = [(false, false), (false, true), (true, true), (true, false)];
+
+struct Pins {
+    a: MockPin,
+    b: MockPin,
+    phase: usize
+}
+
+impl Pins {
+    fn new() -> Self {
+        Self { a: MockPin::default(), b: MockPin::default(), phase: 0 }
+    }
+
+    /// Moves a quarter of a pulse and triggers the interrupt
+    fn step(&mut self, decoder: &mut QuadratureDecoder<MockPin, MockPin>, forward: bool) {
+        self.phase = if forward { (self.phase + 1) % 4 } else { (self.phase + 3) % 4 };
+        self.set(FORWARD[self.phase]);
+        decoder.on_edge().unwrap();
+    }
+
+    fn set(&self, (a, b): (bool, bool)) {
+        self.a.0.set(a);
+        self.b.0.set(b);
+    }
+}
+
+#[test]
+fn counts_both_directions_across_the_wrap() {
+    let counter = QuadratureCounter::new();
+    let mut pins = Pins::new();
+    let mut decoder = QuadratureDecoder::new(pins.a.clone(), pins.b.clone(), &counter).unwrap();
+
+    for _ in 0..10 {
+        pins.step(&mut decoder, true);
+    }
+    assert_eq!((&counter).count(), 10);
+    assert_eq!((&counter).direction(), Direction::Upcounting);
+
+    for _ in 0..25 {
+        pins.step(&mut decoder, false);
+    }
+    assert_eq!((&counter).count(), 15_u32.wrapping_neg());
+    assert_eq!((&counter).direction(), Direction::Downcounting);
+    assert_eq!(counter.get_errors(), 0);
+}
+
+#[test]
+fn spurious_interrupts_do_not_count() {
+    let counter = QuadratureCounter::new();
+    let mut pins = Pins::new();
+    let mut decoder = QuadratureDecoder::new(pins.a.clone(), pins.b.clone(), &counter).unwrap();
+
+    pins.step(&mut decoder, true);
+    decoder.on_edge().unwrap();
+    decoder.on_edge().unwrap();
+
+    assert_eq!((&counter).count(), 1);
+    assert_eq!(counter.get_errors(), 0);
+}
+
+#[test]
+fn missed_edges_are_errors() {
+    let counter = QuadratureCounter::new();
+    let pins = Pins::new();
+    let mut decoder = QuadratureDecoder::new(pins.a.clone(), pins.b.clone(), &counter).unwrap();
+
+    // both pins change between two interrupts, every time
+    for state in [(true, true), (false, false), (true, true), (false, false)] {
+        pins.set(state);
+        decoder.on_edge().unwrap();
+    }
+
+    assert_eq!((&counter).count(), 0);
+    assert_eq!(counter.get_errors(), 4);
+}
+
+#[test]
+fn backs_a_rotary_encoder() {
+    let counter = QuadratureCounter::new();
+    let mut pins = Pins::new();
+    let mut decoder = QuadratureDecoder::new(pins.a.clone(), pins.b.clone(), &counter).unwrap();
+    let mut encoder = RotaryEncoder::new(&counter, PPR, false);
+
+    // two and a half turns back, in updates of 100 counts
+    for _ in 0..10 {
+        for _ in 0..100 {
+            pins.step(&mut decoder, false);
+        }
+        encoder.update(Time::from_seconds(0.025));
+    }
+
+    assert_eq!(encoder.get_count(), -1000);
+    assert!((encoder.get_position().to_revolutions() + 2.5).abs() < 1e-6, "{:?}", encoder.get_position());
+}
@@ -0,0 +1,132 @@
+use std::{cell::Cell, rc::Rc};
+
+use embedded_hal::{Qei, Direction};
+
+use units::Time;
+use encoder::{Update, GetVelocity};
+use rotary_encoder::{RotaryEncoder, velocity::{VelocityEstimator, Difference, MovingAverage, TrackingObserver, EdgeTiming, EdgeTimestamp}};
+
+const PPR: f32 = 1440.0;
+const TIME_DELTA_SECONDS: f32 = 0.025;
+/// 1.8 counts per update, so the count delta alternates between 1 and 2
+const SLOW_SPEED: f32 = 0.05;
+
+/// Largest error of the estimate after the first `settle_time` of a constant speed,
+/// the position is quantized to whole counts
+fn max_error<V: VelocityEstimator>(estimator: &mut V, speed: f32, settle_time: f32) -> f32 {
+    let mut max_error: f32 = 0.0;
+
+    for step in 1..400 {
+        let time = step as f32 * TIME_DELTA_SECONDS;
+        let position = (speed * time * PPR).floor() / PPR;
+
+        estimator.update(position, TIME_DELTA_SECONDS);
+        if time > settle_time {
+            max_error = max_error.max((estimator.get_velocity() - speed).abs());
+        }
+    }
+    max_error
+}
+
+#[test]
+fn difference_is_quantized() {
+    let error = max_error(&mut Difference::default(), SLOW_SPEED, 0.1);
+
+    // a whole count per update
+    assert!(error > 0.3 * SLOW_SPEED, "{}", error);
+}
+
+#[test]
+fn moving_average_is_smoother() {
+    let error = max_error(&mut MovingAverage::<10>::new(), SLOW_SPEED, 0.5);
+
+    // a whole count per window
+    assert!(error < 0.06 * SLOW_SPEED, "{}", error);
+}
+
+#[test]
+fn tracking_observer_converges_and_is_smoother() {
+    let error = max_error(&mut TrackingObserver::with_bandwidth(1.0), SLOW_SPEED, 3.0);
+    assert!(error < 0.1 * SLOW_SPEED, "{}", error);
+
+    // and follows a faster speed too
+    let error = max_error(&mut TrackingObserver::with_bandwidth(1.0), 2.0, 3.0);
+    assert!(error < 0.01 * 2.0, "{}", error);
+}
+
+#[derive(Clone, Default)]
+struct Timestamps {
+    last_edge: Rc<Cell<u32>>,
+    now: Rc<Cell<u32>>
+}
+
+impl EdgeTimestamp for Timestamps {
+    fn get_last_edge_micros(&self) -> u32 {
+        self.last_edge.get()
+    }
+
+    fn get_now_micros(&self) -> u32 {
+        self.now.get()
+    }
+}
+
+#[test]
+fn edge_timing_is_exact_and_decays_when_stopped() {
+    let timestamps = Timestamps::default();
+    let mut estimator = EdgeTiming::new(timestamps.clone(), 1.0 / PPR);
+
+    // the clock wraps in the middle
+    let start = u32::MAX - 500_000;
+    let mut position = 0.0;
+    for step in 1..100_u32 {
+        let now = start.wrapping_add(step * 25_000);
+        let counts = (SLOW_SPEED * (step * 25_000) as f32 / 1e6 * PPR).floor();
+        let last_edge_micros = (counts / PPR / SLOW_SPEED * 1e6).ceil() as u32;
+
+        position = counts / PPR;
+        timestamps.now.set(now);
+        timestamps.last_edge.set(start.wrapping_add(last_edge_micros));
+        estimator.update(position, TIME_DELTA_SECONDS);
+
+        if step > 2 {
+            assert!((estimator.get_velocity() - SLOW_SPEED).abs() < 0.01 * SLOW_SPEED, "{}", estimator.get_velocity());
+        }
+    }
+
+    // no more edges, the speed is bounded by a count since the last one
+    let stopped_at = timestamps.now.get();
+    for step in 1..=40_u32 {
+        timestamps.now.set(stopped_at.wrapping_add(step * 25_000));
+        estimator.update(position, TIME_DELTA_SECONDS);
+    }
+    assert!(estimator.get_velocity() < 1.0 / PPR);
+}
+
+struct MockQei(Rc<Cell<u16>>);
+
+impl Qei for MockQei {
+    type Count = u16;
+
+    fn count(&self) -> u16 {
+        self.0.get()
+    }
+
+    fn direction(&self) -> Direction {
+        Direction::Upcounting
+    }
+}
+
+#[test]
+fn encoder_uses_the_estimator() {
+    let count = Rc::new(Cell::new(0));
+    let mut encoder = RotaryEncoder::new(MockQei(count.clone()), PPR, false)
+        .with_velocity_estimator(MovingAverage::<10>::new());
+
+    for step in 1..100 {
+        let time = step as f32 * TIME_DELTA_SECONDS;
+        count.set((SLOW_SPEED * time * PPR).floor() as u16);
+        encoder.update(Time::from_seconds(TIME_DELTA_SECONDS));
+    }
+
+    assert!((encoder.get_velocity().to_rps() - SLOW_SPEED).abs() < 0.06 * SLOW_SPEED, "{:?}", encoder.get_velocity());
+}
@@ -0,0 +1,397 @@
+#![no_std]
+
+//! Pick-and-sort routine: the claw grabs the item and lifts it, the carriage brings
+//! the bin under the drop zone, then the claw dumps the item into it and goes back down.
+
+use embedded_hal::digital::v2::InputPin;
+
+use units::Time;
+use motor::SetDirection;
+use encoder::Update;
+use servo::{Stop, CheckTargetReached, TargetState};
+use manipulator::{Manipulator, ManipulatorAxis, Operation};
+use carriage::{Carriage, CarriageAxis, CarriageError};
+use drawers_controller::{Drawers, DrawersState, DrawerAction, DrawerEnableControl, DrawerLimits};
+
+/// The claw, see `Manipulator`
+pub trait Claw: Update + CheckTargetReached + Stop {
+    fn start(&mut self, operation: Operation) -> Result<(), manipulator::Busy>;
+}
+
+/// The drawer carriage, see `Carriage`
+pub trait BinCarriage: Update + CheckTargetReached + Stop {
+    fn bin_count(&self) -> u8;
+    fn select_bin(&mut self, bin: u8) -> Result<(), CarriageError>;
+}
+
+/// The drawers, see `Drawers`
+pub trait DrawerStack: Update {
+    /// No drawer is moving or raised, so nothing sticks out into the way of the claw
+    fn is_clear(&self) -> bool;
+    fn stop(&mut self);
+}
+
+impl<L, F, G> Claw for Manipulator<L, F, G>
+where
+    L: ManipulatorAxis,
+    F: ManipulatorAxis,
+    G: ManipulatorAxis
+{
+    fn start(&mut self, operation: Operation) -> Result<(), manipulator::Busy> {
+        Manipulator::start(self, operation)
+    }
+}
+
+impl<A, SW, const N: usize> BinCarriage for Carriage<A, SW, N>
+where
+    A: CarriageAxis,
+    SW: InputPin
+{
+    fn bin_count(&self) -> u8 {
+        N as u8
+    }
+
+    fn select_bin(&mut self, bin: u8) -> Result<(), CarriageError> {
+        Carriage::select_bin(self, bin)
+    }
+}
+
+impl<D, ENS, L> DrawerStack for Drawers<D, ENS, L>
+where
+    D: SetDirection,
+    ENS: DrawerEnableControl,
+    L: DrawerLimits
+{
+    /// Drawers are lowered at power-up, any other outcome of the last action leaves one up
+    fn is_clear(&self) -> bool {
+        matches!(self.get_state(), DrawersState::Idle | DrawersState::Done { action: DrawerAction::Lower, .. })
+    }
+
+    fn stop(&mut self) {
+        Drawers::stop(self)
+    }
+}
+
+/// Placeholder for robots without a claw, every operation is rejected, so a sort fails at its first step
+pub struct NoClaw;
+
+impl Claw for NoClaw {
+    fn start(&mut self, _operation: Operation) -> Result<(), manipulator::Busy> {
+        Err(manipulator::Busy)
+    }
+}
+
+impl Update for NoClaw {
+    fn update(&mut self, _time_delta: Time) {}
+}
+
+impl CheckTargetReached for NoClaw {
+    fn get_target_state(&self) -> TargetState {
+        TargetState::Reached
+    }
+}
+
+impl Stop for NoClaw {
+    fn stop(&mut self) {}
+
+    fn halt(&mut self) {}
+}
+
+/// Placeholder for robots without a drawer carriage, with the bins it is to have, none of them can be selected
+pub struct NoCarriage<const N: usize>;
+
+impl<const N: usize> BinCarriage for NoCarriage<N> {
+    fn bin_count(&self) -> u8 {
+        N as u8
+    }
+
+    fn select_bin(&mut self, _bin: u8) -> Result<(), CarriageError> {
+        Err(CarriageError::NotHomed)
+    }
+}
+
+impl<const N: usize> Update for NoCarriage<N> {
+    fn update(&mut self, _time_delta: Time) {}
+}
+
+impl<const N: usize> CheckTargetReached for NoCarriage<N> {
+    fn get_target_state(&self) -> TargetState {
+        TargetState::Reached
+    }
+}
+
+impl<const N: usize> Stop for NoCarriage<N> {
+    fn stop(&mut self) {}
+
+    fn halt(&mut self) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortStep {
+    /// Closes the claw on the item
+    Grab,
+    /// Lifts the claw off the floor
+    Lift,
+    /// Moves the carriage, so that the bin is under the drop zone
+    SelectBin,
+    /// Flips the claw over the drawers and releases the item
+    Dump,
+    /// Lowers the open claw back to the floor
+    Return
+}
+
+impl SortStep {
+    fn next(self) -> Option<SortStep> {
+        match self {
+            SortStep::Grab => Some(SortStep::Lift),
+            SortStep::Lift => Some(SortStep::SelectBin),
+            SortStep::SelectBin => Some(SortStep::Dump),
+            SortStep::Dump => Some(SortStep::Return),
+            SortStep::Return => None
+        }
+    }
+}
+
+/// Time every step must finish within, including the wait for its interlocks
+#[derive(Debug, Clone, Copy)]
+pub struct StepTimeouts {
+    pub grab: Time,
+    pub lift: Time,
+    pub select_bin: Time,
+    pub dump: Time,
+    pub return_to_floor: Time
+}
+
+impl StepTimeouts {
+    fn get(&self, step: SortStep) -> Time {
+        match step {
+            SortStep::Grab => self.grab,
+            SortStep::Lift => self.lift,
+            SortStep::SelectBin => self.select_bin,
+            SortStep::Dump => self.dump,
+            SortStep::Return => self.return_to_floor
+        }
+    }
+}
+
+impl Default for StepTimeouts {
+    fn default() -> Self {
+        Self {
+            grab: Time::from_seconds(3.0),
+            lift: Time::from_seconds(5.0),
+            select_bin: Time::from_seconds(10.0),
+            dump: Time::from_seconds(15.0),
+            return_to_floor: Time::from_seconds(8.0)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortFailure {
+    /// The step did not finish in time, or its interlocks were not released in time
+    TimedOut,
+    /// The part did not reach its target
+    Part(TargetState),
+    /// The part refused the command, e.g. the carriage is not homed
+    Rejected,
+    /// An interlock was broken while the step was running
+    Interlock
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequencerState {
+    Idle,
+    Running { bin: u8, step: SortStep },
+    Done { bin: u8 },
+    /// Interrupted by `stop` or `halt`
+    Stopped { bin: u8, step: SortStep },
+    /// All the parts are stopped where they are
+    Failed { bin: u8, step: SortStep, failure: SortFailure }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequencerError {
+    InvalidBin,
+    /// Another item is being sorted
+    Busy
+}
+
+/// Runs the pick-and-sort routine one step at a time, a step only starts once
+/// the previous one has finished and its interlocks are released
+pub struct Sequencer<C, B, D>
+where
+    C: Claw,
+    B: BinCarriage,
+    D: DrawerStack
+{
+    pub claw: C,
+    pub carriage: B,
+    pub drawers: D,
+
+    pub timeouts: StepTimeouts,
+
+    state: SequencerState,
+    started: bool,
+    elapsed: Time
+}
+
+impl<C, B, D> Sequencer<C, B, D>
+where
+    C: Claw,
+    B: BinCarriage,
+    D: DrawerStack
+{
+    pub fn new(claw: C, carriage: B, drawers: D, timeouts: StepTimeouts) -> Self {
+        Self {
+            claw,
+            carriage,
+            drawers,
+
+            timeouts,
+
+            state: SequencerState::Idle,
+            started: false,
+            elapsed: Time::ZERO
+        }
+    }
+
+    pub fn get_state(&self) -> SequencerState {
+        self.state
+    }
+
+    pub fn is_busy(&self) -> bool {
+        matches!(self.state, SequencerState::Running { .. })
+    }
+
+    /// Starts sorting the item in front of the claw into the bin
+    pub fn sort(&mut self, bin: u8) -> Result<(), SequencerError> {
+        if bin >= self.carriage.bin_count() {
+            return Err(SequencerError::InvalidBin);
+        }
+        if self.is_busy() {
+            return Err(SequencerError::Busy);
+        }
+
+        self.enter(bin, SortStep::Grab);
+        Ok(())
+    }
+
+    fn enter(&mut self, bin: u8, step: SortStep) {
+        self.state = SequencerState::Running { bin, step };
+        self.started = false;
+        self.elapsed = Time::ZERO;
+    }
+
+    /// Whether the step must not run, the claw is never flipped while the carriage
+    /// moves, and nothing moves sideways past a raised drawer
+    fn is_interlocked(&self, step: SortStep) -> bool {
+        match step {
+            SortStep::SelectBin => !self.drawers.is_clear(),
+            SortStep::Dump => self.carriage.get_target_state() != TargetState::Reached || !self.drawers.is_clear(),
+            SortStep::Grab | SortStep::Lift | SortStep::Return => false
+        }
+    }
+
+    fn start_step(&mut self, bin: u8, step: SortStep) -> Result<(), SortFailure> {
+        let started = match step {
+            SortStep::Grab => self.claw.start(Operation::Grab).is_ok(),
+            SortStep::Lift => self.claw.start(Operation::LiftToCarry).is_ok(),
+            SortStep::SelectBin => self.carriage.select_bin(bin).is_ok(),
+            SortStep::Dump => self.claw.start(Operation::FlipToDump).is_ok(),
+            SortStep::Return => self.claw.start(Operation::Lower).is_ok()
+        };
+
+        if started { Ok(()) } else { Err(SortFailure::Rejected) }
+    }
+
+    fn get_step_state(&self, step: SortStep) -> TargetState {
+        match step {
+            SortStep::SelectBin => self.carriage.get_target_state(),
+            _ => self.claw.get_target_state()
+        }
+    }
+
+    fn stop_parts(&mut self) {
+        self.claw.stop();
+        self.carriage.stop();
+        self.drawers.stop();
+    }
+
+    fn fail(&mut self, bin: u8, step: SortStep, failure: SortFailure) {
+        self.stop_parts();
+        self.state = SequencerState::Failed { bin, step, failure };
+    }
+
+    fn interrupt(&mut self) {
+        if let SequencerState::Running { bin, step } = self.state {
+            self.state = SequencerState::Stopped { bin, step };
+        }
+    }
+}
+
+impl<C, B, D> Update for Sequencer<C, B, D>
+where
+    C: Claw,
+    B: BinCarriage,
+    D: DrawerStack
+{
+    fn update(&mut self, time_delta: Time) {
+        self.claw.update(time_delta);
+        self.carriage.update(time_delta);
+        self.drawers.update(time_delta);
+
+        let (bin, step) = match self.state {
+            SequencerState::Running { bin, step } => (bin, step),
+            _ => return
+        };
+        self.elapsed += time_delta;
+        let timed_out = self.elapsed >= self.timeouts.get(step);
+
+        if !self.started {
+            if !self.is_interlocked(step) {
+                match self.start_step(bin, step) {
+                    Ok(()) => self.started = true,
+                    Err(failure) => self.fail(bin, step, failure)
+                }
+            } else if timed_out {
+                self.fail(bin, step, SortFailure::TimedOut);
+            }
+            return;
+        }
+
+        if self.is_interlocked(step) {
+            self.fail(bin, step, SortFailure::Interlock);
+            return;
+        }
+
+        match self.get_step_state(step) {
+            TargetState::Moving if timed_out => self.fail(bin, step, SortFailure::TimedOut),
+            TargetState::Moving => {},
+            TargetState::Reached => match step.next() {
+                Some(next) => self.enter(bin, next),
+                None => self.state = SequencerState::Done { bin }
+            },
+            failure => self.fail(bin, step, SortFailure::Part(failure))
+        }
+    }
+}
+
+impl<C, B, D> Stop for Sequencer<C, B, D>
+where
+    C: Claw,
+    B: BinCarriage,
+    D: DrawerStack
+{
+    /// Decelerates all the parts wherever they are
+    fn stop(&mut self) {
+        self.interrupt();
+        self.stop_parts();
+    }
+
+    fn halt(&mut self) {
+        self.interrupt();
+        self.claw.halt();
+        self.carriage.halt();
+        self.drawers.stop();
+    }
+}
@@ -0,0 +1,249 @@
+use std::{cell::RefCell, rc::Rc};
+
+use units::Time;
+use encoder::Update;
+use servo::{Stop, CheckTargetReached, TargetState};
+use manipulator::{Operation, Busy};
+use carriage::CarriageError;
+use sequencer::{Sequencer, SequencerState, SequencerError, SortStep, SortFailure, StepTimeouts, Claw, BinCarriage, DrawerStack, NoClaw, NoCarriage};
+
+const TIME_DELTA: Time = Time::from_seconds(0.1);
+/// Every action of the mock parts takes this long
+const ACTION_SECONDS: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Event {
+    Claw(Operation),
+    Carriage(u8),
+    Stopped
+}
+
+#[derive(Default)]
+struct Parts {
+    log: Vec<Event>,
+    drawers_raised: bool,
+    carriage_homed: bool,
+    /// The claw stalls on this operation
+    claw_stall: Option<Operation>
+}
+
+type SharedParts = Rc<RefCell<Parts>>;
+
+/// Finishes every action after a fixed time
+#[derive(Default)]
+struct Action {
+    remaining: f32
+}
+
+impl Action {
+    fn start(&mut self) {
+        self.remaining = ACTION_SECONDS;
+    }
+
+    fn update(&mut self, time_delta_seconds: f32) {
+        self.remaining = (self.remaining - time_delta_seconds).max(0.0);
+    }
+
+    fn get_state(&self) -> TargetState {
+        if self.remaining > 0.0 { TargetState::Moving } else { TargetState::Reached }
+    }
+}
+
+struct MockClaw(SharedParts, Action, Option<Operation>);
+struct MockCarriage(SharedParts, Action);
+struct MockDrawers(SharedParts);
+
+impl Claw for MockClaw {
+    fn start(&mut self, operation: Operation) -> Result<(), Busy> {
+        self.0.borrow_mut().log.push(Event::Claw(operation));
+        self.1.start();
+        self.2 = Some(operation);
+        Ok(())
+    }
+}
+
+impl CheckTargetReached for MockClaw {
+    fn get_target_state(&self) -> TargetState {
+        match self.2 {
+            Some(operation) if self.0.borrow().claw_stall == Some(operation) => TargetState::Stalled,
+            _ => self.1.get_state()
+        }
+    }
+}
+
+impl BinCarriage for MockCarriage {
+    fn bin_count(&self) -> u8 {
+        3
+    }
+
+    fn select_bin(&mut self, bin: u8) -> Result<(), CarriageError> {
+        if !self.0.borrow().carriage_homed {
+            return Err(CarriageError::NotHomed);
+        }
+
+        self.0.borrow_mut().log.push(Event::Carriage(bin));
+        self.1.start();
+        Ok(())
+    }
+}
+
+impl CheckTargetReached for MockCarriage {
+    fn get_target_state(&self) -> TargetState {
+        self.1.get_state()
+    }
+}
+
+impl DrawerStack for MockDrawers {
+    fn is_clear(&self) -> bool {
+        !self.0.borrow().drawers_raised
+    }
+
+    fn stop(&mut self) {}
+}
+
+impl Update for MockClaw {
+    fn update(&mut self, time_delta: Time) {
+        self.1.update(time_delta.to_seconds());
+    }
+}
+
+impl Update for MockCarriage {
+    fn update(&mut self, time_delta: Time) {
+        self.1.update(time_delta.to_seconds());
+    }
+}
+
+impl Update for MockDrawers {
+    fn update(&mut self, _time_delta: Time) {}
+}
+
+impl Stop for MockClaw {
+    fn stop(&mut self) {
+        self.0.borrow_mut().log.push(Event::Stopped);
+        self.1.remaining = 0.0;
+    }
+
+    fn halt(&mut self) {
+        self.stop();
+    }
+}
+
+impl Stop for MockCarriage {
+    fn stop(&mut self) {
+        self.1.remaining = 0.0;
+    }
+
+    fn halt(&mut self) {
+        self.stop();
+    }
+}
+
+type TestSequencer = Sequencer<MockClaw, MockCarriage, MockDrawers>;
+
+fn sequencer() -> (TestSequencer, SharedParts) {
+    let parts = SharedParts::new(RefCell::new(Parts { carriage_homed: true, ..Default::default() }));
+    let sequencer = Sequencer::new(
+        MockClaw(parts.clone(), Action::default(), None),
+        MockCarriage(parts.clone(), Action::default()),
+        MockDrawers(parts.clone()),
+        StepTimeouts::default()
+    );
+
+    (sequencer, parts)
+}
+
+fn run(sequencer: &mut TestSequencer, seconds: f32) {
+    for _ in 0..(seconds / TIME_DELTA.to_seconds()).round() as usize {
+        sequencer.update(TIME_DELTA);
+    }
+}
+
+#[test]
+fn sorts_step_by_step() {
+    let (mut sequencer, parts) = sequencer();
+
+    assert_eq!(sequencer.sort(3), Err(SequencerError::InvalidBin));
+    sequencer.sort(2).unwrap();
+    assert_eq!(sequencer.sort(1), Err(SequencerError::Busy));
+
+    run(&mut sequencer, 0.5);
+    assert_eq!(sequencer.get_state(), SequencerState::Running { bin: 2, step: SortStep::Grab });
+
+    run(&mut sequencer, 15.0);
+    assert_eq!(sequencer.get_state(), SequencerState::Done { bin: 2 });
+    assert_eq!(parts.borrow().log, [
+        Event::Claw(Operation::Grab),
+        Event::Claw(Operation::LiftToCarry),
+        Event::Carriage(2),
+        Event::Claw(Operation::FlipToDump),
+        Event::Claw(Operation::Lower)
+    ]);
+}
+
+#[test]
+fn raised_drawer_holds_the_carriage() {
+    let (mut sequencer, parts) = sequencer();
+    parts.borrow_mut().drawers_raised = true;
+
+    sequencer.sort(0).unwrap();
+    run(&mut sequencer, 5.0);
+    assert_eq!(sequencer.get_state(), SequencerState::Running { bin: 0, step: SortStep::SelectBin });
+
+    // the step goes on once the drawer is down
+    parts.borrow_mut().drawers_raised = false;
+    run(&mut sequencer, 0.2);
+    assert_eq!(parts.borrow().log.last(), Some(&Event::Carriage(0)));
+
+    parts.borrow_mut().drawers_raised = true;
+    run(&mut sequencer, 0.1);
+    assert_eq!(sequencer.get_state(), SequencerState::Failed { bin: 0, step: SortStep::SelectBin, failure: SortFailure::Interlock });
+    assert_eq!(parts.borrow().log.last(), Some(&Event::Stopped));
+}
+
+#[test]
+fn interlock_wait_times_out() {
+    let (mut sequencer, parts) = sequencer();
+    parts.borrow_mut().drawers_raised = true;
+    sequencer.timeouts.select_bin = Time::from_seconds(2.0);
+
+    sequencer.sort(1).unwrap();
+    run(&mut sequencer, 5.0);
+    assert_eq!(sequencer.get_state(), SequencerState::Failed { bin: 1, step: SortStep::SelectBin, failure: SortFailure::TimedOut });
+    assert!(!parts.borrow().log.contains(&Event::Carriage(1)));
+}
+
+#[test]
+fn failures_stop_the_routine() {
+    let (mut sequencer, parts) = sequencer();
+    parts.borrow_mut().carriage_homed = false;
+
+    sequencer.sort(1).unwrap();
+    run(&mut sequencer, 5.0);
+    assert_eq!(sequencer.get_state(), SequencerState::Failed { bin: 1, step: SortStep::SelectBin, failure: SortFailure::Rejected });
+
+    parts.borrow_mut().carriage_homed = true;
+    parts.borrow_mut().claw_stall = Some(Operation::FlipToDump);
+    sequencer.sort(1).unwrap();
+    run(&mut sequencer, 10.0);
+    assert_eq!(sequencer.get_state(), SequencerState::Failed {
+        bin: 1,
+        step: SortStep::Dump,
+        failure: SortFailure::Part(TargetState::Stalled)
+    });
+
+    parts.borrow_mut().claw_stall = None;
+    sequencer.sort(0).unwrap();
+    run(&mut sequencer, 0.5);
+    sequencer.stop();
+    assert_eq!(sequencer.get_state(), SequencerState::Stopped { bin: 0, step: SortStep::Grab });
+}
+
+#[test]
+fn placeholder_parts_reject_the_sort() {
+    let mut sequencer = Sequencer::new(NoClaw, NoCarriage::<3>, MockDrawers(SharedParts::default()), StepTimeouts::default());
+
+    assert_eq!(sequencer.sort(3), Err(SequencerError::InvalidBin));
+    sequencer.sort(1).unwrap();
+    sequencer.update(TIME_DELTA);
+    assert_eq!(sequencer.get_state(), SequencerState::Failed { bin: 1, step: SortStep::Grab, failure: SortFailure::Rejected });
+}
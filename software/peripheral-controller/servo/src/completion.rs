@@ -0,0 +1,117 @@
+use units::{Length, Velocity, Time};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetState {
+    Moving,
+    Reached,
+    /// The target was not reached within the expected time
+    TimedOut,
+    /// The motor was driven, but did not move
+    Stalled
+}
+
+impl TargetState {
+    /// State of two axes moving together: a failure of either wins, then movement
+    pub fn combine(self, other: TargetState) -> TargetState {
+        use TargetState::*;
+
+        match (self, other) {
+            (Stalled, _) | (_, Stalled) => Stalled,
+            (TimedOut, _) | (_, TimedOut) => TimedOut,
+            (Reached, Reached) => Reached,
+            _ => Moving
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(self, TargetState::TimedOut | TargetState::Stalled)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompletionCriteria {
+    pub position_tolerance: Length,
+    pub velocity_tolerance: Velocity,
+    /// How long both tolerances must hold
+    pub settle_time: Time,
+
+    /// Time allowed on top of the expected movement duration
+    pub timeout: Time,
+
+    /// Commanded speed above which the motor is considered to be driven
+    pub stall_speed: Velocity,
+    /// How long the motor may be driven without moving
+    pub stall_time: Time,
+}
+
+pub struct CompletionDetector {
+    pub criteria: CompletionCriteria,
+
+    state: TargetState,
+
+    elapsed: Time,
+    deadline: Time,
+    settled_for: Time,
+    stalled_for: Time,
+}
+
+impl CompletionDetector {
+    pub fn new(criteria: CompletionCriteria) -> Self {
+        Self {
+            criteria,
+
+            state: TargetState::Reached,
+
+            elapsed: Time::ZERO,
+            deadline: Time::ZERO,
+            settled_for: Time::ZERO,
+            stalled_for: Time::ZERO,
+        }
+    }
+
+    pub fn get_state(&self) -> TargetState {
+        self.state
+    }
+
+    /// Starts watching a new movement, which is expected to last `expected_duration`
+    pub fn start(&mut self, expected_duration: Time) {
+        self.state = TargetState::Moving;
+
+        self.elapsed = Time::ZERO;
+        self.deadline = expected_duration + self.criteria.timeout;
+        self.settled_for = Time::ZERO;
+        self.stalled_for = Time::ZERO;
+    }
+
+    /// `error` is the distance to the target, `drive` is the commanded speed
+    pub fn update(&mut self, error: Length, velocity: Velocity, drive: Velocity, time_delta: Time) -> TargetState {
+        if self.state != TargetState::Moving {
+            return self.state;
+        }
+        let criteria = &self.criteria;
+
+        self.elapsed += time_delta;
+
+        let in_position = error.abs() <= criteria.position_tolerance;
+        let still = velocity.abs() <= criteria.velocity_tolerance;
+
+        self.settled_for = if in_position && still { self.settled_for + time_delta } else { Time::ZERO };
+        self.stalled_for = if !in_position && still && drive.abs() >= criteria.stall_speed {
+            self.stalled_for + time_delta
+        } else {
+            Time::ZERO
+        };
+
+        self.state = if self.settled_for >= criteria.settle_time {
+            TargetState::Reached
+        } else if self.stalled_for >= criteria.stall_time {
+            TargetState::Stalled
+        } else if self.elapsed >= self.deadline {
+            TargetState::TimedOut
+        } else {
+            TargetState::Moving
+        };
+
+        self.state
+    }
+}
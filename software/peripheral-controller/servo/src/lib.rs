@@ -1,92 +1,172 @@
 #![no_std]
 
+pub mod profile;
+pub mod completion;
+
 use core::{ops::Add, fmt::Display};
 
 use num_traits::{NumCast, ToPrimitive, bounds::Bounded};
 use pid::Pid;
 
+use units::{Length, Velocity, Acceleration, Time};
 use motor::{SetSpeed, GetSpeed};
 use encoder::{Encoder, Update, GetPosition};
 use wheel::Wheel;
 
+use crate::profile::TrapezoidalProfile;
+pub use crate::completion::{TargetState, CompletionCriteria, CompletionDetector};
+
 pub trait SetPosition {
     type Position;
 
     fn set_position(&mut self, position: Self::Position);
 }
 
+/// Keeps moving at the given velocity instead of going to a position,
+/// until a new position is set
+pub trait SetVelocity {
+    type Velocity;
+
+    fn set_velocity(&mut self, velocity: Self::Velocity);
+}
+
+/// Interrupts whatever the motor is doing
+pub trait Stop {
+    /// Decelerates to a standstill within the acceleration limit
+    fn stop(&mut self);
+
+    /// Cuts the motor output at once, the motor stays free until the next command
+    fn halt(&mut self);
+}
+
 pub trait CheckTargetReached {
-    fn is_target_reached(&self) -> bool;
+    fn get_target_state(&self) -> TargetState;
+
+    fn is_target_reached(&self) -> bool {
+        self.get_target_state() == TargetState::Reached
+    }
 }
 
 pub struct Servo <S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>,
+    E: Encoder
 {
     wheel: Wheel<S, E>,
 
     pid: Pid<f32>,
+    profile: TrapezoidalProfile,
+    completion: CompletionDetector,
+    // set while in the velocity mode
+    velocity: Option<Velocity>,
+    halted: bool,
 
-    max_position: f32,
-    max_target_distance: f32,
+    max_position: Length,
 
-    pub max_speed: f32,
+    pub max_speed: Velocity,
+    pub max_acceleration: Acceleration,
 }
 
 impl<S, E> Servo<S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
-    pub fn new(wheel: Wheel<S, E>, pid: Pid<f32>, max_position: f32, target_position_epsilon: f32) -> Self {
-        assert_ne!(max_position, 0.0);
+    pub fn new(wheel: Wheel<S, E>, pid: Pid<f32>, max_position: Length, completion_criteria: CompletionCriteria, max_acceleration: Acceleration) -> Self {
+        assert_ne!(max_position, Length::ZERO);
+
+        let profile = TrapezoidalProfile::hold(wheel.get_position());
+        let max_speed = wheel.max_speed;
 
         Self {
             wheel,
 
             pid,
+            profile,
+            completion: CompletionDetector::new(completion_criteria),
+            velocity: None,
+            halted: false,
 
             max_position,
-            max_target_distance: target_position_epsilon,
 
-            max_speed: 1.0,
+            max_speed,
+            max_acceleration,
         }
     }
 
-    pub fn get_target_position(&self) -> f32 {
-        self.denormalize_position(self.pid.setpoint)
+    pub fn get_target_position(&self) -> Length {
+        self.profile.get_target()
     }
 
-    fn normalize_position(&self, pos: f32) -> f32 {
-        pos / self.max_position
+    /// Time left until the motion profile reaches the target position
+    pub fn get_remaining_time(&self) -> Time {
+        self.profile.get_remaining_time()
     }
 
-    fn denormalize_position(&self, pos: f32) -> f32 {
-        pos * self.max_position
+    fn normalize_position(&self, position: Length) -> f32 {
+        position / self.max_position
     }
 
-    fn normalize_speed(&self, speed: f32) -> f32 {
+    fn normalize_speed(&self, speed: Velocity) -> f32 {
         speed / self.wheel.max_speed
     }
 
-    fn denormalize_speed(&self, speed: f32) -> f32 {
-        speed * self.wheel.max_speed
+    fn denormalize_speed(&self, speed: f32) -> Velocity {
+        self.wheel.max_speed * speed
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn get_wheel(&self) -> &Wheel<S, E> {
+        &self.wheel
+    }
+
+    pub fn get_wheel_mut(&mut self) -> &mut Wheel<S, E> {
+        &mut self.wheel
+    }
+
+    /// Position the PID works in fractions of
+    pub fn get_max_position(&self) -> Length {
+        self.max_position
+    }
+
+    /// Replaces the gains of the position PID, the state of the loop is kept
+    pub fn set_pid_gains(&mut self, kp: f32, ki: f32, kd: f32) {
+        self.pid.kp = kp;
+        self.pid.ki = ki;
+        self.pid.kd = kd;
+    }
+
+    /// Takes the control back after `halt`
+    fn resume(&mut self) {
+        if self.halted {
+            self.halted = false;
+            self.pid.reset_integral_term();
+            self.wheel.enable();
+        }
+    }
+
+    /// Keeps the position loop in sync while it does not drive the wheel,
+    /// so that it picks up smoothly afterwards
+    fn follow_position(&mut self) {
+        let position = self.get_position();
+        self.profile = TrapezoidalProfile::hold(position);
+        self.pid.setpoint = self.normalize_position(position);
+        self.pid.next_control_output(self.normalize_position(position));
     }
 }
 
 impl<S, E> SetSpeed for Servo<S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
-    type Speed = f32;
+    type Speed = Velocity;
 
     fn set_speed(&mut self, speed: Self::Speed) {
-        assert_ne!(speed, 0.0);
+        assert_ne!(speed, Velocity::ZERO);
 
         self.max_speed = speed;
     }
@@ -95,10 +175,9 @@ where
 impl<S, E> GetSpeed for Servo<S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
-    type Speed = f32;
+    type Speed = Velocity;
 
     fn get_speed(&mut self) -> Self::Speed {
         self.wheel.get_speed()
@@ -108,9 +187,10 @@ where
 impl<S, E> GetPosition for Servo<S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
+    type Position = Length;
+
     fn get_position(&self) -> Self::Position {
         self.wheel.get_position()
     }
@@ -119,25 +199,59 @@ where
 impl<S, E> SetPosition for Servo<S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
-    type Position = f32;
+    type Position = Length;
 
     fn set_position(&mut self, position: Self::Position) {
-        self.pid.setpoint = self.normalize_position(position);
+        self.resume();
+
+        let velocity = match self.velocity.take() {
+            Some(velocity) => velocity,
+            None if self.profile.is_finished() => Velocity::ZERO,
+            None => self.profile.get_velocity()
+        };
+        self.profile = TrapezoidalProfile::new(self.get_position(), velocity, position,
+                                               self.max_speed, self.max_acceleration);
+        self.completion.start(self.profile.get_remaining_time());
+    }
+}
+
+impl<S, E> SetVelocity for Servo<S, E>
+where
+    S: SetSpeed + GetSpeed,
+    E: Encoder
+{
+    type Velocity = Velocity;
+
+    fn set_velocity(&mut self, velocity: Self::Velocity) {
+        self.resume();
+
+        if self.velocity.is_none() {
+            self.pid.reset_integral_term();
+        }
+
+        self.velocity = Some(velocity.min(self.max_speed).max(-self.max_speed));
     }
 }
 
 impl<S, E> CheckTargetReached for Servo<S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
-    fn is_target_reached(&self) -> bool {
-        let distance = self.get_target_position() - self.get_position();
-        distance < self.max_target_distance
+    /// In the velocity mode the target is reached once the velocity is zero,
+    /// a halted servo has no target at all
+    fn get_target_state(&self) -> TargetState {
+        if self.halted {
+            return TargetState::Reached;
+        }
+
+        match self.velocity {
+            Some(velocity) if velocity != Velocity::ZERO => TargetState::Moving,
+            Some(_) => TargetState::Reached,
+            None => self.completion.get_state()
+        }
     }
 }
 
@@ -147,11 +261,23 @@ where
     <S as SetSpeed>::Speed: NumCast + Bounded,
     <S as GetSpeed>::Speed: NumCast + Add + Copy,
     <<S as GetSpeed>::Speed as Add>::Output: ToPrimitive + Display,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
-    fn update(&mut self, time_delta_seconds: f32) {
-        self.wheel.update(time_delta_seconds);
+    fn update(&mut self, time_delta: Time) {
+        self.wheel.update(time_delta);
+
+        if self.halted {
+            self.follow_position();
+            return;
+        }
+        if let Some(velocity) = self.velocity {
+            self.follow_position();
+            self.wheel.set_speed(velocity);
+            return;
+        }
+
+        self.profile.advance(time_delta);
+        self.pid.setpoint = self.normalize_position(self.profile.get_position());
 
         let position = self.get_position();
         let position = self.normalize_position(position);
@@ -164,6 +290,42 @@ where
         let new_speed = self.denormalize_speed(new_speed).min(self.max_speed).max(-self.max_speed);
 
         self.wheel.set_speed(new_speed);
+
+        let was_moving = self.completion.get_state() == TargetState::Moving;
+        let error = self.get_target_position() - self.get_position();
+        let velocity = self.wheel.get_speed();
+        let state = self.completion.update(error, velocity, new_speed, time_delta);
+        if was_moving && state.is_failure() {
+            // stop pushing towards a target that cannot be reached
+            self.profile = TrapezoidalProfile::hold(self.get_position());
+        }
     }
 }
 
+impl<S, E> Stop for Servo<S, E>
+where
+    S: SetSpeed + GetSpeed,
+    <S as SetSpeed>::Speed: NumCast,
+    E: Encoder
+{
+    fn stop(&mut self) {
+        if self.halted {
+            return;
+        }
+
+        let velocity = match self.velocity {
+            Some(velocity) => velocity,
+            None => self.profile.get_velocity()
+        };
+        let stopping_distance = velocity * (velocity.abs() / (2.0 * self.max_acceleration));
+
+        self.set_position(self.get_position() + stopping_distance);
+    }
+
+    fn halt(&mut self) {
+        self.halted = true;
+        self.velocity = None;
+        self.profile = TrapezoidalProfile::hold(self.get_position());
+        self.wheel.disable();
+    }
+}
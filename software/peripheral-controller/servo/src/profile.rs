@@ -0,0 +1,152 @@
+use units::{Length, Velocity, Acceleration, Time};
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Segment {
+    duration: f32,
+    acceleration: f32
+}
+
+/// Trapezoidal velocity profile: accelerate, cruise, decelerate.
+///
+/// The profile may start with a non-zero velocity. If it points away from the target,
+/// or is too high to stop in time, the profile first brakes to a standstill.
+/// Internally everything is in metres and seconds.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TrapezoidalProfile {
+    start_position: f32,
+    start_velocity: f32,
+    target: f32,
+
+    segments: [Segment; 4],
+    duration: f32,
+
+    elapsed: f32
+}
+
+impl TrapezoidalProfile {
+    pub fn new(start_position: Length, start_velocity: Velocity, target: Length, max_velocity: Velocity, max_acceleration: Acceleration) -> Self {
+        assert!(max_velocity > Velocity::ZERO && max_acceleration > Acceleration::ZERO);
+
+        let (start_position, start_velocity, target) = (start_position.to_m(), start_velocity.to_m_per_s(), target.to_m());
+        let (max_velocity, max_acceleration) = (max_velocity.to_m_per_s(), max_acceleration.to_m_per_s2());
+
+        let mut profile = Self {
+            start_position,
+            start_velocity,
+            target,
+            ..Self::default()
+        };
+        let mut segments = 0;
+
+        let mut position = start_position;
+        let mut velocity = start_velocity;
+
+        let stopping_distance = velocity * velocity.abs() / (2.0 * max_acceleration);
+        let distance = target - position;
+        let overshoots = stopping_distance.abs() > distance.abs();
+        if velocity != 0.0 && (velocity * distance <= 0.0 || overshoots) {
+            profile.segments[segments] = Segment {
+                duration: velocity.abs() / max_acceleration,
+                acceleration: -velocity.signum() * max_acceleration
+            };
+            segments += 1;
+
+            position += stopping_distance;
+            velocity = 0.0;
+        }
+
+        let distance = target - position;
+        let direction = if distance != 0.0 { distance.signum() } else { 1.0 };
+        let (distance, velocity) = (distance.abs(), velocity.abs());
+
+        let accelerating_to = |peak: f32| (peak * peak - velocity * velocity).abs() / (2.0 * max_acceleration);
+        let braking_from = |peak: f32| peak * peak / (2.0 * max_acceleration);
+
+        let mut peak = max_velocity;
+        let mut cruise = distance - accelerating_to(peak) - braking_from(peak);
+        if cruise < 0.0 {
+            peak = libm::sqrtf((2.0 * max_acceleration * distance + velocity * velocity) / 2.0).min(peak);
+            cruise = 0.0;
+        }
+
+        let ramp = Segment {
+            duration: (peak - velocity).abs() / max_acceleration,
+            acceleration: (peak - velocity).signum() * max_acceleration * direction
+        };
+        let cruise = Segment {
+            duration: if peak > 0.0 { cruise / peak } else { 0.0 },
+            acceleration: 0.0
+        };
+        let brake = Segment {
+            duration: peak / max_acceleration,
+            acceleration: -max_acceleration * direction
+        };
+
+        for segment in [ramp, cruise, brake] {
+            profile.segments[segments] = segment;
+            segments += 1;
+        }
+        profile.duration = profile.segments.iter().map(|segment| segment.duration).sum();
+
+        profile
+    }
+
+    /// A profile which holds the given position
+    pub fn hold(position: Length) -> Self {
+        let position = position.to_m();
+
+        Self {
+            start_position: position,
+            target: position,
+            ..Self::default()
+        }
+    }
+
+    pub fn advance(&mut self, time_delta: Time) {
+        self.elapsed = (self.elapsed + time_delta.to_seconds()).min(self.duration);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    pub fn get_remaining_time(&self) -> Time {
+        Time::from_seconds(self.duration - self.elapsed)
+    }
+
+    pub fn get_position(&self) -> Length {
+        Length::from_m(self.sample().0)
+    }
+
+    pub fn get_velocity(&self) -> Velocity {
+        Velocity::from_m_per_s(self.sample().1)
+    }
+
+    pub fn get_target(&self) -> Length {
+        Length::from_m(self.target)
+    }
+
+    fn sample(&self) -> (f32, f32) {
+        if self.is_finished() {
+            return (self.target, 0.0);
+        }
+
+        let mut position = self.start_position;
+        let mut velocity = self.start_velocity;
+        let mut time = self.elapsed;
+
+        for segment in self.segments.iter() {
+            let dt = time.min(segment.duration);
+
+            position += velocity * dt + segment.acceleration * dt * dt / 2.0;
+            velocity += segment.acceleration * dt;
+
+            time -= dt;
+            if time <= 0.0 {
+                break;
+            }
+        }
+
+        (position, velocity)
+    }
+}
@@ -0,0 +1,99 @@
+use servo::profile::TrapezoidalProfile;
+use units::{Length, Velocity, Acceleration, Time};
+
+const TIME_DELTA: Time = Time::from_seconds(0.01);
+const EPSILON: f32 = 1e-3;
+
+fn cm(centimeters: f32) -> Length {
+    Length::from_cm(centimeters)
+}
+
+fn cm_per_s(centimeters_per_second: f32) -> Velocity {
+    Velocity::from_cm_per_s(centimeters_per_second)
+}
+
+fn cm_per_s2(centimeters_per_second_squared: f32) -> Acceleration {
+    Acceleration::from_cm_per_s2(centimeters_per_second_squared)
+}
+
+fn seconds(seconds: f32) -> Time {
+    Time::from_seconds(seconds)
+}
+
+/// Runs the profile to the end, checking the velocity and acceleration limits on the way
+fn run(mut profile: TrapezoidalProfile, max_velocity: f32, max_acceleration: f32) -> f32 {
+    let mut elapsed = 0.0;
+    let mut velocity = profile.get_velocity().to_cm_per_s();
+
+    while !profile.is_finished() {
+        profile.advance(TIME_DELTA);
+        elapsed += TIME_DELTA.to_seconds();
+
+        let new_velocity = profile.get_velocity().to_cm_per_s();
+        assert!(new_velocity.abs() <= max_velocity + EPSILON);
+        assert!((new_velocity - velocity).abs() <= max_acceleration * TIME_DELTA.to_seconds() + EPSILON);
+        velocity = new_velocity;
+    }
+
+    elapsed
+}
+
+#[test]
+fn trapezoid() {
+    let profile = TrapezoidalProfile::new(cm(10.0), cm_per_s(0.0), cm(110.0), cm_per_s(20.0), cm_per_s2(10.0));
+
+    // 2 s accelerating over 20 cm, 3 s cruising over 60 cm, 2 s braking over 20 cm
+    assert!((profile.get_remaining_time().to_seconds() - 7.0).abs() < EPSILON);
+
+    let elapsed = run(profile, 20.0, 10.0);
+    assert!((elapsed - 7.0).abs() < 2.0 * TIME_DELTA.to_seconds());
+}
+
+#[test]
+fn triangle() {
+    let mut profile = TrapezoidalProfile::new(cm(0.0), cm_per_s(0.0), cm(-10.0), cm_per_s(100.0), cm_per_s2(10.0));
+    assert!((profile.get_remaining_time().to_seconds() - 2.0).abs() < EPSILON);
+
+    profile.advance(seconds(1.0));
+    assert!((profile.get_position().to_cm() + 5.0).abs() < EPSILON);
+    assert!((profile.get_velocity().to_cm_per_s() + 10.0).abs() < EPSILON);
+
+    profile.advance(seconds(1.0));
+    assert!(profile.is_finished());
+    assert_eq!(profile.get_position(), cm(-10.0));
+    assert_eq!(profile.get_velocity(), Velocity::ZERO);
+}
+
+#[test]
+fn brakes_before_reversing() {
+    let mut profile = TrapezoidalProfile::new(cm(0.0), cm_per_s(10.0), cm(-5.0), cm_per_s(10.0), cm_per_s2(10.0));
+
+    // 1 s to stop 5 cm past the start, then a 10 cm triangle back
+    assert!((profile.get_remaining_time().to_seconds() - 3.0).abs() < EPSILON);
+
+    profile.advance(seconds(1.0));
+    assert!((profile.get_position().to_cm() - 5.0).abs() < EPSILON);
+    assert!(profile.get_velocity().to_cm_per_s().abs() < EPSILON);
+
+    run(profile, 10.0, 10.0);
+}
+
+#[test]
+fn continues_from_moving_start() {
+    let mut profile = TrapezoidalProfile::new(cm(0.0), cm_per_s(10.0), cm(100.0), cm_per_s(10.0), cm_per_s2(10.0));
+
+    // already at the cruise velocity: 9.5 s cruising, 1 s braking
+    assert!((profile.get_remaining_time().to_seconds() - 10.5).abs() < EPSILON);
+
+    profile.advance(seconds(0.5));
+    assert!((profile.get_velocity().to_cm_per_s() - 10.0).abs() < EPSILON);
+}
+
+#[test]
+fn hold_is_finished() {
+    let profile = TrapezoidalProfile::hold(cm(3.0));
+
+    assert!(profile.is_finished());
+    assert_eq!(profile.get_position(), cm(3.0));
+    assert_eq!(profile.get_remaining_time(), Time::ZERO);
+}
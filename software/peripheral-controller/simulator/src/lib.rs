@@ -0,0 +1,113 @@
+//! Host-side model of a DC motor + gearbox + wheel, so that the `wheel`, `servo`
+//! and `chassis` stacks can be run and tuned without the board.
+//!
+//! The plant is shared between a [`SimMotor`] and a [`SimEncoder`]; the encoder
+//! advances the simulation in its [`Update`] implementation, so stepping a
+//! `Wheel`, `Servo` or `Chassis` by `dt` also steps the physics by `dt`.
+
+mod plant;
+
+pub use crate::plant::{Plant, PlantParams};
+
+use std::{cell::RefCell, rc::Rc};
+
+use units::{Time, Angle, AngularVelocity};
+use motor::{SetSpeed, GetSpeed};
+use encoder::{Update, GetPosition, GetVelocity};
+
+pub type SharedPlant = Rc<RefCell<Plant>>;
+
+/// Creates a motor and an encoder attached to the same simulated wheel
+pub fn simulated_wheel(params: PlantParams) -> (SimMotor, SimEncoder) {
+    let plant = Rc::new(RefCell::new(Plant::new(params)));
+
+    (SimMotor::new(plant.clone()), SimEncoder::new(plant))
+}
+
+/// Drop-in replacement for `motor::Motor`, takes the speed in percent of the duty cycle
+pub struct SimMotor {
+    plant: SharedPlant,
+
+    current_speed: i8
+}
+
+impl SimMotor {
+    pub fn new(plant: SharedPlant) -> Self {
+        Self {
+            plant,
+
+            current_speed: 0
+        }
+    }
+
+    pub fn plant(&self) -> SharedPlant {
+        self.plant.clone()
+    }
+}
+
+impl SetSpeed for SimMotor {
+    type Speed = i8;
+
+    fn set_speed(&mut self, speed: Self::Speed) {
+        self.current_speed = speed.clamp(-100, 100);
+        self.plant.borrow_mut().set_duty(self.current_speed as f32);
+    }
+}
+
+impl GetSpeed for SimMotor {
+    type Speed = i8;
+
+    fn get_speed(&mut self) -> Self::Speed {
+        self.current_speed
+    }
+}
+
+/// Quadrature encoder reading the simulated shaft, like `RotaryEncoder`
+pub struct SimEncoder {
+    plant: SharedPlant,
+
+    position: Angle,
+    velocity: AngularVelocity,
+}
+
+impl SimEncoder {
+    pub fn new(plant: SharedPlant) -> Self {
+        Self {
+            plant,
+
+            position: Angle::ZERO,
+            velocity: AngularVelocity::ZERO,
+        }
+    }
+
+    pub fn plant(&self) -> SharedPlant {
+        self.plant.clone()
+    }
+}
+
+impl Update for SimEncoder {
+    fn update(&mut self, time_delta: Time) {
+        let mut plant = self.plant.borrow_mut();
+        plant.step(time_delta.to_seconds());
+
+        let last_position = self.position;
+        self.position = Angle::from_revolutions(plant.get_count() as f32 / plant.params.encoder_ppr);
+        self.velocity = (self.position - last_position) / time_delta;
+    }
+}
+
+impl GetPosition for SimEncoder {
+    type Position = Angle;
+
+    fn get_position(&self) -> Self::Position {
+        self.position
+    }
+}
+
+impl GetVelocity for SimEncoder {
+    type Velocity = AngularVelocity;
+
+    fn get_velocity(&self) -> Self::Velocity {
+        self.velocity
+    }
+}
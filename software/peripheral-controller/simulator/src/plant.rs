@@ -0,0 +1,161 @@
+use std::f32::consts::TAU;
+
+/// Physical parameters of a DC motor driving a wheel through a gearbox.
+///
+/// Torques and inertia are given at the output (wheel) shaft unless
+/// stated otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct PlantParams {
+    /// Motor shaft torque at full duty and zero speed, N*m
+    pub stall_torque: f32,
+    /// Motor shaft speed at full duty and no load, rad/s
+    pub free_speed: f32,
+    pub gear_ratio: f32,
+
+    /// Wheel inertia plus the share of the robot mass it carries, kg*m^2
+    pub inertia: f32,
+    /// Coulomb (dry) friction torque, N*m
+    pub coulomb_friction: f32,
+    /// Viscous friction coefficient, N*m*s/rad
+    pub viscous_friction: f32,
+
+    /// Duty cycle (in percent) below which the driver produces no torque
+    pub deadband: u8,
+
+    pub encoder_ppr: f32,
+}
+
+impl Default for PlantParams {
+    /// Roughly the chassis DC motors: ~1.6 rps at full duty with a loaded robot
+    fn default() -> Self {
+        Self {
+            stall_torque: 0.05,
+            free_speed: 300.0,
+            gear_ratio: 30.0,
+
+            inertia: 0.002,
+            coulomb_friction: 0.15,
+            viscous_friction: 0.01,
+
+            deadband: 10,
+
+            encoder_ppr: 1440.0,
+        }
+    }
+}
+
+/// Largest integration step, the plant is sub-stepped to stay stable
+const MAX_STEP_SECONDS: f32 = 0.0005;
+
+/// Velocity below which the wheel is considered to be standing still
+const STICTION_VELOCITY: f32 = 1e-3;
+
+pub struct Plant {
+    pub params: PlantParams,
+
+    duty: f32,
+
+    // output shaft, rad and rad/s
+    angle: f32,
+    velocity: f32,
+
+    time: f32,
+}
+
+impl Plant {
+    pub fn new(params: PlantParams) -> Self {
+        Self {
+            params,
+
+            duty: 0.0,
+
+            angle: 0.0,
+            velocity: 0.0,
+
+            time: 0.0,
+        }
+    }
+
+    /// Sets the applied duty cycle in percent, from -100 to 100
+    pub fn set_duty(&mut self, duty: f32) {
+        self.duty = duty.clamp(-100.0, 100.0);
+    }
+
+    pub fn get_duty(&self) -> f32 {
+        self.duty
+    }
+
+    /// Output shaft angle in revolutions
+    pub fn get_revolutions(&self) -> f32 {
+        self.angle / TAU
+    }
+
+    /// Output shaft velocity in revolutions per second
+    pub fn get_rps(&self) -> f32 {
+        self.velocity / TAU
+    }
+
+    /// Raw encoder count for the current shaft angle
+    pub fn get_count(&self) -> i64 {
+        (self.get_revolutions() * self.params.encoder_ppr).floor() as i64
+    }
+
+    /// Total simulated time in seconds
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn step(&mut self, time_delta_seconds: f32) {
+        let steps = (time_delta_seconds / MAX_STEP_SECONDS).ceil().max(1.0);
+        let dt = time_delta_seconds / steps;
+
+        for _ in 0..(steps as u32) {
+            self.integrate(dt);
+        }
+
+        self.time += time_delta_seconds;
+    }
+
+    fn drive_torque(&self) -> f32 {
+        let params = &self.params;
+
+        let voltage = if self.duty.abs() <= params.deadband as f32 {
+            0.0
+        } else {
+            self.duty / 100.0
+        };
+
+        let motor_speed = self.velocity * params.gear_ratio;
+        let motor_torque = params.stall_torque * (voltage - motor_speed / params.free_speed);
+
+        motor_torque * params.gear_ratio
+    }
+
+    fn integrate(&mut self, dt: f32) {
+        let params = self.params;
+        let drive = self.drive_torque();
+
+        if self.velocity.abs() < STICTION_VELOCITY && drive.abs() <= params.coulomb_friction {
+            self.velocity = 0.0;
+            return;
+        }
+
+        let direction = if self.velocity.abs() < STICTION_VELOCITY {
+            drive.signum()
+        } else {
+            self.velocity.signum()
+        };
+        let friction = params.coulomb_friction * direction + params.viscous_friction * self.velocity;
+
+        let acceleration = (drive - friction) / params.inertia;
+        let velocity = self.velocity + acceleration * dt;
+
+        // friction can stop the wheel, but never reverse it
+        self.velocity = if velocity.signum() != direction && drive.abs() <= params.coulomb_friction {
+            0.0
+        } else {
+            velocity
+        };
+        self.angle += self.velocity * dt;
+    }
+}
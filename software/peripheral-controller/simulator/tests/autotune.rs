@@ -0,0 +1,119 @@
+use units::{Length, Time, Velocity, Acceleration};
+use motor::{SetSpeed, GetSpeed};
+use encoder::{Update, GetPosition};
+use servo::{SetPosition, CheckTargetReached, TargetState, CompletionCriteria};
+use autotune::{RelayAutotune, AutotuneState, TuningRule, UltimateGain};
+
+use simulator::PlantParams;
+
+mod common;
+use common::{wheel, servo_with, SimServo, TIME_DELTA, WHEEL_RADIUS, WHEEL_MAX_ROTARY_SPEED};
+
+/// The relay of the position loop works in fractions of this travel, it was sized for it rather than for the firmware servo
+const SERVO_MAX_DISTANCE: Length = Length::from_m(20.0);
+const SERVO_MAX_ACCELERATION: Acceleration = Acceleration::from_cm_per_s2(30.0);
+
+fn servo() -> SimServo {
+    let completion_criteria = CompletionCriteria {
+        position_tolerance: Length::from_cm(1.0),
+        velocity_tolerance: Velocity::from_cm_per_s(0.5),
+        settle_time: Time::from_seconds(0.1),
+        timeout: Time::from_seconds(4.0),
+        stall_speed: Velocity::from_cm_per_s(10.0),
+        stall_time: Time::from_seconds(0.5)
+    };
+
+    servo_with(wheel(PlantParams::default()), completion_criteria, SERVO_MAX_DISTANCE, SERVO_MAX_ACCELERATION)
+}
+
+fn ultimate_gain(state: AutotuneState) -> UltimateGain {
+    match state {
+        AutotuneState::Done(ultimate_gain) => ultimate_gain,
+        state => panic!("{:?}", state)
+    }
+}
+
+#[test]
+fn tunes_the_wheel_speed_loop() {
+    let mut wheel = wheel(PlantParams::default());
+    // around 40% of the maximum speed, which takes ~55% duty, the counts make the speed jump by 2%
+    let mut autotune = RelayAutotune::new(20.0, 3.0, Time::from_seconds(30.0));
+    autotune.start(40.0, 55.0);
+
+    while autotune.is_running() {
+        autotune.update(&mut wheel, TIME_DELTA);
+    }
+    let ultimate_gain = ultimate_gain(autotune.get_state());
+    assert!(ultimate_gain.ku > 0.0 && ultimate_gain.tu > 2.0 * TIME_DELTA.to_seconds(), "{:?}", ultimate_gain);
+
+    // released towards a standstill
+    for _ in 0..80 {
+        wheel.update(TIME_DELTA);
+    }
+    assert!(wheel.get_speed().abs() < Velocity::from_cm_per_s(0.5), "{:?}", wheel.get_speed());
+
+    // the tuned loop follows a step without oscillating
+    let (kp, ki, kd) = ultimate_gain.gains(TuningRule::ZieglerNichols).to_incremental(TIME_DELTA.to_seconds());
+    wheel.set_pid_gains(kp, ki, kd);
+
+    let target = 0.6 * WHEEL_MAX_ROTARY_SPEED.to_tangential(WHEEL_RADIUS);
+    wheel.set_speed(target);
+    let mut max_speed = Velocity::ZERO;
+    for _ in 0..160 {
+        wheel.update(TIME_DELTA);
+        max_speed = max_speed.max(wheel.get_speed());
+    }
+    // the duty is a whole percent, so the speed settles within a few percent of the target
+    assert!((wheel.get_speed() - target).abs() < 0.05 * target, "{:?} {:?}", wheel.get_speed(), ultimate_gain);
+    assert!(max_speed < 1.2 * target, "{:?}", max_speed);
+}
+
+#[test]
+fn tunes_the_servo_position_loop() {
+    let mut servo = servo();
+    let start = servo.get_position() / SERVO_MAX_DISTANCE;
+    let mut autotune = RelayAutotune::new(0.3, 0.0005, Time::from_seconds(30.0));
+    autotune.start(start, 0.0);
+
+    while autotune.is_running() {
+        autotune.update(&mut servo, TIME_DELTA);
+    }
+    let ultimate_gain = ultimate_gain(autotune.get_state());
+    assert!(ultimate_gain.ku > 0.0 && ultimate_gain.tu > 2.0 * TIME_DELTA.to_seconds(), "{:?}", ultimate_gain);
+
+    // released to a standstill
+    for _ in 0..80 {
+        servo.update(TIME_DELTA);
+    }
+    assert_eq!(servo.get_target_state(), TargetState::Reached);
+
+    // the position loop integrates, the Ziegler-Nichols style rules make it oscillate
+    let (kp, ki, kd) = ultimate_gain.gains(TuningRule::TyreusLuyben).to_incremental(TIME_DELTA.to_seconds());
+    servo.set_pid_gains(kp, ki, kd);
+
+    let target = servo.get_position() + Length::from_cm(50.0);
+    servo.set_position(target);
+    let mut max_position = -Length::INFINITY;
+    for _ in 0..320 {
+        servo.update(TIME_DELTA);
+        max_position = max_position.max(servo.get_position());
+    }
+
+    // settled, short of the tolerance, as the small speeds are inside the deadband of the driver
+    assert_eq!(servo.get_speed(), Velocity::ZERO);
+    assert!((servo.get_position() - target).abs() < Length::from_cm(5.0), "{:?} {:?} {:?}", servo.get_position(), target, ultimate_gain);
+    assert!(max_position < target + Length::from_cm(5.0), "{:?}", max_position);
+}
+
+#[test]
+fn small_relay_does_not_oscillate() {
+    let mut servo = servo();
+    // the deadband of the driver swallows the whole relay output
+    let mut autotune = RelayAutotune::new(0.01, 0.0005, Time::from_seconds(5.0));
+    autotune.start(0.0, 0.0);
+
+    while autotune.is_running() {
+        autotune.update(&mut servo, TIME_DELTA);
+    }
+    assert_eq!(autotune.get_state(), AutotuneState::TimedOut);
+}
@@ -0,0 +1,45 @@
+use units::{Length, Angle};
+use encoder::{Update, GetPosition};
+use servo::{SetPosition, CheckTargetReached, TargetState};
+use carriage::{Carriage, CarriageState};
+use manipulator::Joint;
+
+use simulator::PlantParams;
+
+mod common;
+use common::{servo, TIME_DELTA, SERVO_MAX_TARRGET_DISTANCE};
+
+fn run_until_done<T: Update + CheckTargetReached>(axis: &mut T) {
+    for _ in 0..400 {
+        axis.update(TIME_DELTA);
+        if axis.get_target_state() != TargetState::Moving {
+            return;
+        }
+    }
+    panic!("the axis did not stop");
+}
+
+#[test]
+fn carriage_moves_on_a_servo() {
+    let bins = [Length::ZERO, Length::from_cm(8.0), Length::from_cm(16.0)];
+    let mut carriage = Carriage::new(servo(PlantParams::default()), bins);
+
+    carriage.select_bin(1).unwrap();
+    run_until_done(&mut carriage);
+
+    assert_eq!(carriage.get_state(), CarriageState::AtBin { bin: 1 });
+    assert!((carriage.get_position() - bins[1]).abs() <= SERVO_MAX_TARRGET_DISTANCE, "{:?}", carriage.get_position());
+}
+
+#[test]
+fn joint_moves_on_a_servo() {
+    // a revolution of the joint per 20 cm of the servo
+    let mut joint = Joint::rotary(servo(PlantParams::default()), Length::from_cm(20.0), Angle::ZERO, Angle::from_degrees(180.0));
+
+    joint.set_position(Angle::from_degrees(90.0));
+    run_until_done(&mut joint);
+
+    assert_eq!(joint.get_target_state(), TargetState::Reached);
+    let tolerance = Angle::from_revolutions(SERVO_MAX_TARRGET_DISTANCE / Length::from_cm(20.0));
+    assert!((joint.get_position() - Angle::from_degrees(90.0)).abs() <= tolerance, "{:?}", joint.get_position().to_degrees());
+}
@@ -0,0 +1,166 @@
+use units::{Length, Angle, Time, Velocity, AngularVelocity, Acceleration, AngularAcceleration};
+use encoder::{Update, GetPosition};
+use servo::CheckTargetReached;
+use chassis::{chassis::{Chassis, ChassisPosition}, movement_controller::{MovementController, MoveRelative}};
+
+use simulator::{simulated_wheel, PlantParams};
+
+mod common;
+use common::{servo, TIME_DELTA, SERVO_MAX_TARRGET_DISTANCE, SERVO_MAX_ACCELERATION};
+
+const WHEELS_DISTANCE: Length = Length::from_cm(17.0);
+
+#[test]
+fn wheel_spins_up_under_constant_duty() {
+    use motor::SetSpeed;
+    use encoder::GetVelocity;
+
+    let (mut motor, mut encoder) = simulated_wheel(PlantParams::default());
+    motor.set_speed(100);
+
+    for _ in 0..80 {
+        encoder.update(TIME_DELTA);
+    }
+
+    let velocity = encoder.get_velocity().to_rps();
+    assert!(velocity > 1.0 && velocity < 2.0, "velocity {}", velocity);
+}
+
+#[test]
+fn wheel_stays_still_inside_deadband() {
+    use motor::SetSpeed;
+
+    let (mut motor, mut encoder) = simulated_wheel(PlantParams::default());
+    motor.set_speed(8);
+
+    for _ in 0..40 {
+        encoder.update(TIME_DELTA);
+    }
+
+    assert_eq!(encoder.get_position(), Angle::ZERO);
+}
+
+#[test]
+fn movement_controller_reaches_relative_target() {
+    let chassis = Chassis::new(servo(PlantParams::default()), servo(PlantParams::default()), WHEELS_DISTANCE);
+    let mut chassis: MovementController<_> = MovementController::new(chassis);
+
+    chassis.move_relative(ChassisPosition { linear: (Length::from_cm(30.0), Length::ZERO), angular: Angle::ZERO }).unwrap();
+
+    let mut elapsed = 0.0;
+    while !chassis.is_target_reached() || elapsed < 1.0 {
+        chassis.update(TIME_DELTA);
+        elapsed += TIME_DELTA.to_seconds();
+
+        assert!(elapsed < 60.0, "target is not reached, position: {:?}", chassis.get_position());
+    }
+
+    let position = chassis.get_position();
+    assert!((position.linear.0.to_cm() - 30.0).abs() < 2.0, "position: {:?}", position);
+}
+
+#[test]
+fn blocked_wheel_is_reported_as_stalled() {
+    use servo::{SetPosition, TargetState};
+
+    let blocked = PlantParams { coulomb_friction: 100.0, ..PlantParams::default() };
+    let mut servo = servo(blocked);
+
+    servo.set_position(Length::from_cm(20.0));
+
+    let mut elapsed = 0.0;
+    while servo.get_target_state() == TargetState::Moving {
+        servo.update(TIME_DELTA);
+        elapsed += TIME_DELTA.to_seconds();
+
+        assert!(elapsed < 10.0);
+    }
+
+    assert_eq!(servo.get_target_state(), TargetState::Stalled);
+}
+
+#[test]
+fn servo_settles_on_target() {
+    use servo::{SetPosition, TargetState};
+
+    let mut servo = servo(PlantParams::default());
+    servo.set_position(Length::from_cm(-15.0));
+
+    for _ in 0..400 {
+        servo.update(TIME_DELTA);
+    }
+
+    assert_eq!(servo.get_target_state(), TargetState::Reached);
+    assert!((servo.get_position() - Length::from_cm(-15.0)).abs() <= SERVO_MAX_TARRGET_DISTANCE);
+}
+
+#[test]
+fn chassis_switches_from_velocity_to_position_mode() {
+    use servo::{SetVelocity, TargetState};
+    use chassis::chassis::{ChassisSpeed, VelocityLimits, MoveAtomic, AtomicMovement};
+
+    let limits = VelocityLimits {
+        linear_acceleration: Acceleration::from_cm_per_s2(30.0),
+        angular_acceleration: AngularAcceleration::from_degrees_per_s2(90.0),
+        command_timeout: Time::from_seconds(0.2)
+    };
+    let mut chassis = Chassis::new(servo(PlantParams::default()), servo(PlantParams::default()), WHEELS_DISTANCE)
+        .with_velocity_limits(limits);
+
+    for _ in 0..80 {
+        chassis.set_velocity(ChassisSpeed { linear: Velocity::from_cm_per_s(20.0), angular: AngularVelocity::ZERO });
+        chassis.update(TIME_DELTA);
+    }
+    let speed = chassis.get_measured_speed();
+    assert!((speed.linear.to_cm_per_s() - 20.0).abs() < 3.0, "speed: {:?}", speed);
+
+    let start = chassis.get_position().linear.0;
+    chassis.move_atomic(AtomicMovement::Linear(Length::from_cm(15.0)));
+
+    let mut elapsed = 0.0;
+    while chassis.get_target_state() != TargetState::Reached {
+        chassis.update(TIME_DELTA);
+        elapsed += TIME_DELTA.to_seconds();
+
+        assert!(elapsed < 10.0, "state: {:?}", chassis.get_target_state());
+    }
+
+    let travelled = (chassis.get_position().linear.0 - start).to_cm();
+    assert!((travelled - 15.0).abs() < 2.0, "travelled {}", travelled);
+}
+
+#[test]
+fn servo_stop_decelerates_and_halt_cuts_the_motor() {
+    use servo::{SetPosition, Stop, TargetState};
+
+    let mut servo = servo(PlantParams::default());
+    servo.set_position(Length::from_cm(200.0));
+    for _ in 0..80 {
+        servo.update(TIME_DELTA);
+    }
+
+    let position = servo.get_position();
+    let stopping_distance = servo.max_speed * (servo.max_speed / (2.0 * SERVO_MAX_ACCELERATION));
+    servo.stop();
+    for _ in 0..200 {
+        servo.update(TIME_DELTA);
+    }
+    assert_eq!(servo.get_target_state(), TargetState::Reached);
+    let travelled = servo.get_position() - position;
+    assert!((travelled - stopping_distance).abs() < Length::from_cm(5.0), "travelled {:?}, expected {:?}", travelled, stopping_distance);
+
+    servo.set_position(Length::from_cm(-100.0));
+    for _ in 0..40 {
+        servo.update(TIME_DELTA);
+    }
+    servo.halt();
+    assert!(servo.is_halted());
+    for _ in 0..80 {
+        servo.update(TIME_DELTA);
+    }
+    // friction stops the free wheel
+    let position = servo.get_position();
+    servo.update(TIME_DELTA);
+    assert_eq!(servo.get_position(), position);
+    assert_eq!(servo.get_target_state(), TargetState::Reached);
+}
@@ -0,0 +1,67 @@
+//! Simulated wheels and servos with the firmware tuning, shared by the simulator tests
+
+#![allow(dead_code)]
+
+use pid::Pid;
+
+use units::{Length, Time, Velocity, AngularVelocity, Acceleration};
+use wheel::Wheel;
+use servo::{Servo, CompletionCriteria};
+
+use simulator::{simulated_wheel, PlantParams, SimMotor, SimEncoder};
+
+pub const TIME_DELTA: Time = Time::from_seconds(0.025);
+
+/// The firmware constants were tuned in wheel radii per revolution, one of those units is 2 pi mm
+const TUNED_UNIT_MM: f32 = 2.0 * core::f32::consts::PI;
+pub const WHEEL_RADIUS: Length = Length::from_mm(37.0);
+pub const WHEEL_MAX_ROTARY_SPEED: AngularVelocity = AngularVelocity::from_rps(1.4);
+pub const SERVO_MAX_DISTANCE: Length = Length::from_mm(2000.0 * TUNED_UNIT_MM);
+pub const SERVO_MAX_TARRGET_DISTANCE: Length = Length::from_mm(1.0 * TUNED_UNIT_MM);
+pub const SERVO_MAX_ACCELERATION: Acceleration = Acceleration::from_m_per_s2(30.0 * TUNED_UNIT_MM / 1000.0);
+pub const SERVO_SETTLED_SPEED: Velocity = Velocity::from_m_per_s(0.5 * TUNED_UNIT_MM / 1000.0);
+pub const SERVO_SETTLE_TIME: Time = Time::from_seconds(0.1);
+pub const SERVO_TIMEOUT: Time = Time::from_seconds(2.0);
+pub const SERVO_STALL_SPEED: Velocity = Velocity::from_m_per_s(10.0 * TUNED_UNIT_MM / 1000.0);
+pub const SERVO_STALL_TIME: Time = Time::from_seconds(0.5);
+
+pub type SimWheel = Wheel<SimMotor, SimEncoder>;
+pub type SimServo = Servo<SimMotor, SimEncoder>;
+
+pub fn speed_pid() -> Pid<f32> {
+    Pid::new(0.25, 0.02, 1.0,
+             100.0, 100.0, 100.0,
+             100.0,
+             0.0)
+}
+
+pub fn wheel(params: PlantParams) -> SimWheel {
+    let (motor, encoder) = simulated_wheel(params);
+
+    Wheel::new(motor, encoder, speed_pid(), WHEEL_MAX_ROTARY_SPEED, WHEEL_RADIUS)
+}
+
+/// With the firmware tuning
+pub fn servo(params: PlantParams) -> SimServo {
+    let completion_criteria = CompletionCriteria {
+        position_tolerance: SERVO_MAX_TARRGET_DISTANCE,
+        velocity_tolerance: SERVO_SETTLED_SPEED,
+        settle_time: SERVO_SETTLE_TIME,
+        timeout: SERVO_TIMEOUT,
+        stall_speed: SERVO_STALL_SPEED,
+        stall_time: SERVO_STALL_TIME
+    };
+
+    servo_with(wheel(params), completion_criteria, SERVO_MAX_DISTANCE, SERVO_MAX_ACCELERATION)
+}
+
+/// With the firmware position loop, but the completion and the travel of the test
+pub fn servo_with(wheel: SimWheel, completion_criteria: CompletionCriteria,
+                  max_distance: Length, max_acceleration: Acceleration) -> SimServo {
+    let position_pid = Pid::new(500.0, 0.001, 4000.0,
+                                1.0, 0.1, 1.0,
+                                1.0,
+                                0.0);
+
+    Servo::new(wheel, position_pid, max_distance, completion_criteria, max_acceleration)
+}
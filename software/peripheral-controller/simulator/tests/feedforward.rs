@@ -0,0 +1,87 @@
+use units::{Time, Velocity, AngularVelocity};
+use motor::{SetSpeed, GetSpeed};
+use encoder::{Update, GetVelocity};
+use wheel::{Wheel, feedforward::{Feedforward, DutySweep}};
+
+use simulator::{simulated_wheel, PlantParams};
+
+mod common;
+use common::{wheel, speed_pid, TIME_DELTA, WHEEL_RADIUS, WHEEL_MAX_ROTARY_SPEED};
+
+/// Sweeps the simulated wheel open loop
+fn calibrate() -> Feedforward {
+    let (mut motor, mut encoder) = simulated_wheel(PlantParams::default());
+    let mut sweep = DutySweep::<10>::new(100.0, Time::from_seconds(1.0), Time::from_seconds(0.5), Velocity::from_cm_per_s(1.0));
+
+    while let Some(duty) = sweep.get_duty() {
+        motor.set_speed(duty as i8);
+        encoder.update(TIME_DELTA);
+        sweep.update(encoder.get_velocity().to_tangential(WHEEL_RADIUS), TIME_DELTA);
+    }
+
+    // the first step is inside the deadband
+    assert_eq!(sweep.get_results()[0].1, Velocity::ZERO);
+    sweep.fit().unwrap()
+}
+
+/// Time for the wheel to reach 90% of the target speed from standstill
+fn rise_time(feedforward: Feedforward, target_speed: Velocity) -> f32 {
+    let (motor, encoder) = simulated_wheel(PlantParams::default());
+    let plant = encoder.plant();
+    let mut wheel = Wheel::new(motor, encoder, speed_pid(), WHEEL_MAX_ROTARY_SPEED, WHEEL_RADIUS)
+        .with_feedforward(feedforward);
+
+    wheel.set_speed(target_speed);
+    for step in 1..400 {
+        wheel.update(TIME_DELTA);
+
+        let speed = AngularVelocity::from_rps(plant.borrow().get_rps()).to_tangential(WHEEL_RADIUS);
+        if speed >= 0.9 * target_speed {
+            return step as f32 * TIME_DELTA.to_seconds();
+        }
+    }
+    f32::INFINITY
+}
+
+#[test]
+fn sweep_fits_the_plant() {
+    let feedforward = calibrate();
+
+    // the dry friction takes 0.15 of the 1.5 N*m at full duty
+    assert!((feedforward.ks - 10.0).abs() < 1.0, "{:?}", feedforward);
+    // where the drive torque meets the friction at full duty, 1.34 rps
+    let full_duty_speed = Velocity::from_cm_per_s((100.0 - feedforward.ks) / feedforward.kv).to_angular(WHEEL_RADIUS).to_rps();
+    assert!((full_duty_speed - 1.34).abs() < 0.05, "{:?}", feedforward);
+}
+
+#[test]
+fn feedforward_speeds_up_the_start() {
+    let feedforward = calibrate();
+    let target_speed = 0.5 * WHEEL_MAX_ROTARY_SPEED.to_tangential(WHEEL_RADIUS);
+
+    let without = rise_time(Feedforward::default(), target_speed);
+    let with = rise_time(feedforward, target_speed);
+
+    assert!(without.is_finite() && with < 0.5 * without, "with {} without {}", with, without);
+}
+
+#[test]
+fn feedforward_holds_the_speed_without_integrating() {
+    let feedforward = calibrate();
+    let target_speed = 0.5 * WHEEL_MAX_ROTARY_SPEED.to_tangential(WHEEL_RADIUS);
+
+    let mut wheel = wheel(PlantParams::default()).with_feedforward(feedforward);
+
+    wheel.set_speed(target_speed);
+    for _ in 0..200 {
+        wheel.update(TIME_DELTA);
+    }
+    assert!((wheel.get_speed() - target_speed).abs() < 0.05 * target_speed, "{:?}", wheel.get_speed());
+
+    // stopping drops the static friction term at once
+    wheel.set_speed(Velocity::ZERO);
+    for _ in 0..200 {
+        wheel.update(TIME_DELTA);
+    }
+    assert!(wheel.get_speed().abs() < Velocity::from_cm_per_s(0.5), "{:?}", wheel.get_speed());
+}
@@ -0,0 +1,364 @@
+#![no_std]
+
+//! STEP/DIR/EN stepper driver (TB6600, A4988) with acceleration-ramped step timing.
+//!
+//! Steps are generated by [`Stepper::poll`], which is meant to be called from a timer
+//! interrupt at the moment it asks for. The speed is updated on every step so that
+//! the motor never exceeds the configured acceleration.
+
+use embedded_hal::digital::v2::{OutputPin, PinState};
+#[cfg(not(test))]
+use num_traits::float::FloatCore;
+
+use units::{Length, Velocity, Acceleration, Time};
+use motor::SetSpeed;
+use encoder::{Update, GetPosition};
+use servo::{SetPosition, SetVelocity, Stop, CheckTargetReached, TargetState};
+
+/// Minimal STEP pulse width, both drivers need less than 3 us
+pub const PULSE_WIDTH_MICROS: u32 = 5;
+/// Delay between a DIR change and the next STEP edge
+pub const DIRECTION_SETUP_MICROS: u32 = 5;
+
+pub struct Stepper<STEP, DIR, EN>
+where
+    STEP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin
+{
+    step: STEP,
+    dir: DIR,
+    enable: EN,
+
+    pub steps_per_meter: f32,
+    pub reverse: bool,
+
+    pub max_speed: Velocity,
+    pub max_acceleration: Acceleration,
+
+    position: i32,
+    target: i32,
+    // signed, set while in the velocity mode
+    velocity: Option<Velocity>,
+    // signed, steps per second
+    speed: f32,
+
+    forward: bool,
+    pulse_high: bool,
+    next_step_at: u32
+}
+
+impl<STEP, DIR, EN> Stepper<STEP, DIR, EN>
+where
+    STEP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin
+{
+    pub fn new(step: STEP, dir: DIR, enable: EN, steps_per_meter: f32, max_speed: Velocity, max_acceleration: Acceleration, reverse: bool) -> Self {
+        assert!(steps_per_meter > 0.0 && max_speed > Velocity::ZERO && max_acceleration > Acceleration::ZERO);
+
+        let mut stepper = Self {
+            step,
+            dir,
+            enable,
+
+            steps_per_meter,
+            reverse,
+
+            max_speed,
+            max_acceleration,
+
+            position: 0,
+            target: 0,
+            velocity: None,
+            speed: 0.0,
+
+            forward: true,
+            pulse_high: false,
+            next_step_at: 0
+        };
+        stepper.step.set_low().ok();
+        stepper.set_forward(true);
+        stepper.disable();
+
+        stepper
+    }
+
+    /// The enable input is active low on both the A4988 and the TB6600
+    pub fn enable(&mut self) {
+        self.enable.set_low().ok();
+    }
+
+    /// Releases the motor, the position is kept but may no longer match the shaft
+    pub fn disable(&mut self) {
+        self.enable.set_high().ok();
+    }
+
+    pub fn get_steps(&self) -> i32 {
+        self.position
+    }
+
+    /// Redefines the current position, e.g. after homing
+    pub fn set_steps(&mut self, steps: i32) {
+        self.position = steps;
+        self.target = steps;
+        self.velocity = None;
+        self.speed = 0.0;
+    }
+
+    pub fn move_to_steps(&mut self, steps: i32) {
+        self.target = steps;
+        self.velocity = None;
+        self.enable();
+    }
+
+    pub fn get_speed(&self) -> Velocity {
+        Velocity::from_m_per_s(self.speed / self.steps_per_meter)
+    }
+
+    pub fn is_idle(&self) -> bool {
+        let done = match self.velocity {
+            Some(velocity) => velocity == Velocity::ZERO,
+            None => self.position == self.target
+        };
+
+        done && self.speed == 0.0 && !self.pulse_high
+    }
+
+    /// Generates the step signal, returns when it should be called next,
+    /// or `None` if there is nothing to do until a new target is set
+    pub fn poll(&mut self, now_micros: u32) -> Option<u32> {
+        if self.pulse_high {
+            self.step.set_low().ok();
+            self.pulse_high = false;
+
+            return if self.velocity.is_none() && self.position == self.target {
+                self.stop_if_done(now_micros)
+            } else {
+                Some(self.next_step_at)
+            };
+        }
+
+        if self.is_idle() {
+            return None;
+        }
+
+        if (now_micros.wrapping_sub(self.next_step_at) as i32) < 0 {
+            return Some(self.next_step_at);
+        }
+
+        self.speed = self.next_speed();
+        if self.speed == 0.0 {
+            return self.stop_if_done(now_micros);
+        }
+
+        let forward = self.speed > 0.0;
+        if forward != self.forward {
+            self.set_forward(forward);
+            self.next_step_at = now_micros.wrapping_add(DIRECTION_SETUP_MICROS);
+            return Some(self.next_step_at);
+        }
+
+        self.step.set_high().ok();
+        self.pulse_high = true;
+        self.position += if forward { 1 } else { -1 };
+
+        let interval = 1_000_000.0 / self.speed.abs();
+        self.next_step_at = now_micros.wrapping_add(interval.round() as u32);
+
+        Some(now_micros.wrapping_add(PULSE_WIDTH_MICROS))
+    }
+
+    fn stop_if_done(&mut self, now_micros: u32) -> Option<u32> {
+        if self.velocity.is_some() || self.position == self.target {
+            self.speed = 0.0;
+            None
+        } else {
+            Some(now_micros)
+        }
+    }
+
+    fn set_forward(&mut self, forward: bool) {
+        self.forward = forward;
+        self.dir.set_state(PinState::from(forward != self.reverse)).ok();
+    }
+
+    /// Speed for the next step, in steps per second
+    fn next_speed(&self) -> f32 {
+        if let Some(velocity) = self.velocity {
+            return self.next_speed_towards(velocity.to_m_per_s() * self.steps_per_meter);
+        }
+
+        let to_go = (self.target - self.position) as f32;
+        if to_go == 0.0 {
+            return 0.0;
+        }
+
+        let max_speed = self.max_speed.to_m_per_s() * self.steps_per_meter;
+        let double_acceleration = 2.0 * self.max_acceleration.to_m_per_s2() * self.steps_per_meter;
+        let speed_squared = self.speed * self.speed;
+
+        let direction = to_go.signum();
+        let min_speed = libm::sqrtf(double_acceleration);
+
+        if self.speed * direction < 0.0 {
+            // moving away from the target, brake and turn around
+            let braked = speed_squared - double_acceleration;
+            return if braked > min_speed * min_speed {
+                self.speed.signum() * libm::sqrtf(braked)
+            } else {
+                direction * min_speed
+            };
+        }
+
+        let stopping_steps = speed_squared / double_acceleration;
+        let speed = if stopping_steps >= to_go.abs() {
+            libm::sqrtf((speed_squared - double_acceleration).max(double_acceleration))
+        } else if self.speed.abs() > max_speed {
+            libm::sqrtf((speed_squared - double_acceleration).max(max_speed * max_speed))
+        } else {
+            libm::sqrtf(speed_squared + double_acceleration).min(max_speed)
+        };
+
+        direction * speed
+    }
+
+    /// Speed for the next step in the velocity mode, zero once stopped
+    fn next_speed_towards(&self, target: f32) -> f32 {
+        let max_speed = self.max_speed.to_m_per_s() * self.steps_per_meter;
+        let double_acceleration = 2.0 * self.max_acceleration.to_m_per_s2() * self.steps_per_meter;
+        let speed_squared = self.speed * self.speed;
+
+        let target = target.min(max_speed).max(-max_speed);
+        let min_speed = libm::sqrtf(double_acceleration);
+
+        if self.speed * target <= 0.0 && self.speed != 0.0 {
+            // braking to a stop or to turn around
+            let braked = speed_squared - double_acceleration;
+            return if braked > min_speed * min_speed {
+                self.speed.signum() * libm::sqrtf(braked)
+            } else if target == 0.0 {
+                0.0
+            } else {
+                target.signum() * min_speed.min(target.abs())
+            };
+        }
+
+        let speed = if self.speed.abs() > target.abs() {
+            libm::sqrtf((speed_squared - double_acceleration).max(target * target))
+        } else {
+            libm::sqrtf(speed_squared + double_acceleration).min(target.abs())
+        };
+
+        target.signum() * speed
+    }
+}
+
+impl<STEP, DIR, EN> SetSpeed for Stepper<STEP, DIR, EN>
+where
+    STEP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin
+{
+    type Speed = Velocity;
+
+    fn set_speed(&mut self, speed: Self::Speed) {
+        assert!(speed > Velocity::ZERO);
+
+        self.max_speed = speed;
+    }
+}
+
+impl<STEP, DIR, EN> GetPosition for Stepper<STEP, DIR, EN>
+where
+    STEP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin
+{
+    type Position = Length;
+
+    fn get_position(&self) -> Self::Position {
+        Length::from_m(self.position as f32 / self.steps_per_meter)
+    }
+}
+
+impl<STEP, DIR, EN> SetPosition for Stepper<STEP, DIR, EN>
+where
+    STEP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin
+{
+    type Position = Length;
+
+    fn set_position(&mut self, position: Self::Position) {
+        self.move_to_steps((position.to_m() * self.steps_per_meter).round() as i32);
+    }
+}
+
+impl<STEP, DIR, EN> SetVelocity for Stepper<STEP, DIR, EN>
+where
+    STEP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin
+{
+    type Velocity = Velocity;
+
+    /// Keeps stepping at the given velocity, ramping to it with the maximal acceleration
+    fn set_velocity(&mut self, velocity: Self::Velocity) {
+        self.velocity = Some(velocity);
+        self.enable();
+    }
+}
+
+impl<STEP, DIR, EN> Stop for Stepper<STEP, DIR, EN>
+where
+    STEP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin
+{
+    fn stop(&mut self) {
+        let double_acceleration = 2.0 * self.max_acceleration.to_m_per_s2() * self.steps_per_meter;
+        let stopping_steps = (self.speed * self.speed / double_acceleration).ceil() as i32;
+
+        self.target = self.position + if self.speed < 0.0 { -stopping_steps } else { stopping_steps };
+        self.velocity = None;
+    }
+
+    /// Stops issuing steps and releases the motor, steps may be lost if it was going fast
+    fn halt(&mut self) {
+        self.step.set_low().ok();
+        self.pulse_high = false;
+
+        self.target = self.position;
+        self.velocity = None;
+        self.speed = 0.0;
+        self.disable();
+    }
+}
+
+impl<STEP, DIR, EN> CheckTargetReached for Stepper<STEP, DIR, EN>
+where
+    STEP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin
+{
+    /// Without an encoder a stepper can only tell whether it has issued all the steps,
+    /// in the velocity mode the target is reached once it has stopped
+    fn get_target_state(&self) -> TargetState {
+        if self.is_idle() {
+            TargetState::Reached
+        } else {
+            TargetState::Moving
+        }
+    }
+}
+
+impl<STEP, DIR, EN> Update for Stepper<STEP, DIR, EN>
+where
+    STEP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin
+{
+    /// Steps are generated by `poll`, there is nothing to do at the control loop rate
+    fn update(&mut self, _time_delta: Time) {}
+}
@@ -0,0 +1,226 @@
+use std::{cell::RefCell, convert::Infallible, rc::Rc};
+
+use embedded_hal::digital::v2::OutputPin;
+
+use units::{Length, Velocity, Acceleration};
+use encoder::GetPosition;
+use servo::{SetPosition, SetVelocity, Stop, CheckTargetReached, TargetState};
+use stepper::Stepper;
+
+const STEPS_PER_METER: f32 = 100_000.0;
+const MAX_SPEED: Velocity = Velocity::from_cm_per_s(2.0);
+const MAX_ACCELERATION: Acceleration = Acceleration::from_cm_per_s2(4.0);
+
+#[derive(Default)]
+struct Log {
+    now: u32,
+    forward: bool,
+    enabled: bool,
+    // time and direction of every rising STEP edge
+    steps: Vec<(u32, bool)>
+}
+
+type SharedLog = Rc<RefCell<Log>>;
+
+struct StepPin(SharedLog);
+struct DirPin(SharedLog);
+struct EnablePin(SharedLog);
+
+impl OutputPin for StepPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let mut log = self.0.borrow_mut();
+        let step = (log.now, log.forward);
+        log.steps.push(step);
+        Ok(())
+    }
+}
+
+impl OutputPin for DirPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.borrow_mut().forward = false;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.borrow_mut().forward = true;
+        Ok(())
+    }
+}
+
+impl OutputPin for EnablePin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.borrow_mut().enabled = true;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.borrow_mut().enabled = false;
+        Ok(())
+    }
+}
+
+type TestStepper = Stepper<StepPin, DirPin, EnablePin>;
+
+fn stepper() -> (TestStepper, SharedLog) {
+    let log = SharedLog::default();
+    let stepper = Stepper::new(StepPin(log.clone()), DirPin(log.clone()), EnablePin(log.clone()),
+                               STEPS_PER_METER, MAX_SPEED, MAX_ACCELERATION, false);
+    (stepper, log)
+}
+
+/// Polls the stepper as a timer interrupt would, until it goes idle
+fn run(stepper: &mut TestStepper, log: &SharedLog) {
+    let mut now = log.borrow().now;
+
+    while let Some(next) = stepper.poll(now) {
+        assert!(next.wrapping_sub(now) < 1_000_000);
+        now = next;
+        log.borrow_mut().now = now;
+    }
+}
+
+fn assert_limits(steps: &[(u32, bool)]) {
+    let max_speed = MAX_SPEED.to_m_per_s() * STEPS_PER_METER;
+    let double_acceleration = 2.0 * MAX_ACCELERATION.to_m_per_s2() * STEPS_PER_METER;
+
+    let speeds: Vec<f32> = steps.windows(2)
+        .filter(|pair| pair[0].1 == pair[1].1)
+        .map(|pair| 1_000_000.0 / (pair[1].0 - pair[0].0) as f32)
+        .collect();
+
+    for speed in speeds.iter() {
+        assert!(*speed <= max_speed * 1.01, "speed {}", speed);
+    }
+    for pair in speeds.windows(2) {
+        let change = (pair[1] * pair[1] - pair[0] * pair[0]).abs();
+        // step times are whole microseconds, an interval off by 1 us changes v^2 by about 2v^3 / 1e6
+        let quantization = 4.0 * pair[0].max(pair[1]).powi(3) / 1_000_000.0;
+        assert!(change <= double_acceleration + quantization, "speed {} -> {}", pair[0], pair[1]);
+    }
+}
+
+#[test]
+fn reaches_target_within_limits() {
+    let (mut stepper, log) = stepper();
+
+    stepper.set_position(Length::from_mm(25.0));
+    assert!(log.borrow().enabled);
+    assert_eq!(stepper.get_target_state(), TargetState::Moving);
+
+    run(&mut stepper, &log);
+
+    assert_eq!(stepper.get_steps(), 2500);
+    assert_eq!(stepper.get_position(), Length::from_mm(25.0));
+    assert_eq!(stepper.get_target_state(), TargetState::Reached);
+
+    let log = log.borrow();
+    assert_eq!(log.steps.len(), 2500);
+    assert!(log.steps.iter().all(|step| step.1));
+    assert_limits(&log.steps);
+
+    // 0.5 s ramping up and down each, 0.75 s cruising; the per-step ramp
+    // takes its first steps a bit faster than the continuous profile would
+    let duration = (log.steps.last().unwrap().0 - log.steps[0].0) as f32 / 1_000_000.0;
+    assert!(duration > 1.65 && duration < 1.8, "duration {}", duration);
+}
+
+#[test]
+fn reverses_smoothly() {
+    let (mut stepper, log) = stepper();
+    stepper.set_position(Length::from_mm(10.0));
+
+    // change the target while cruising forward
+    let mut now = 0;
+    while stepper.get_steps() < 600 {
+        now = stepper.poll(now).unwrap();
+        log.borrow_mut().now = now;
+    }
+    stepper.set_position(Length::from_mm(-5.0));
+    run(&mut stepper, &log);
+
+    assert_eq!(stepper.get_steps(), -500);
+
+    let log = log.borrow();
+    let forward = log.steps.iter().filter(|step| step.1).count() as i32;
+    let backward = log.steps.iter().filter(|step| !step.1).count() as i32;
+    assert_eq!(forward - backward, -500);
+    assert_limits(&log.steps);
+}
+
+#[test]
+fn set_steps_redefines_position() {
+    let (mut stepper, log) = stepper();
+
+    stepper.set_steps(300);
+    assert_eq!(stepper.get_position(), Length::from_mm(3.0));
+    assert!(stepper.poll(0).is_none());
+
+    stepper.set_position(Length::from_mm(2.0));
+    run(&mut stepper, &log);
+    assert_eq!(log.borrow().steps.len(), 100);
+}
+
+#[test]
+fn velocity_mode_ramps_and_stops() {
+    let (mut stepper, log) = stepper();
+    stepper.set_velocity(Velocity::from_cm_per_s(-1.0));
+    assert_eq!(stepper.get_target_state(), TargetState::Moving);
+
+    let mut now = 0;
+    while log.borrow().steps.len() < 1000 {
+        now = stepper.poll(now).unwrap();
+        log.borrow_mut().now = now;
+    }
+    assert!((stepper.get_speed() - Velocity::from_cm_per_s(-1.0)).abs() < Velocity::from_m_per_s(1e-6), "{:?}", stepper.get_speed());
+
+    stepper.set_velocity(Velocity::ZERO);
+    run(&mut stepper, &log);
+    assert_eq!(stepper.get_target_state(), TargetState::Reached);
+
+    let log = log.borrow();
+    assert!(log.steps.iter().all(|step| !step.1));
+    assert_eq!(stepper.get_steps(), -(log.steps.len() as i32));
+    assert_limits(&log.steps);
+
+    // braking from 10 mm/s takes 10^2 / (2 * 40) mm
+    let braking_steps = log.steps.len() - 1000;
+    assert!((braking_steps as f32 - 125.0).abs() <= 2.0, "braked in {} steps", braking_steps);
+}
+
+#[test]
+fn stop_decelerates_and_halt_releases() {
+    let (mut stepper, log) = stepper();
+    stepper.set_position(Length::from_mm(25.0));
+
+    let mut now = 0;
+    while log.borrow().steps.len() < 1000 {
+        now = stepper.poll(now).unwrap();
+        log.borrow_mut().now = now;
+    }
+    stepper.stop();
+    run(&mut stepper, &log);
+
+    // braking from 20 mm/s takes 20^2 / (2 * 40) mm
+    let steps = stepper.get_steps();
+    assert!((steps - 1500).abs() <= 2, "stopped at {}", steps);
+    assert_limits(&log.borrow().steps);
+
+    stepper.set_position(Length::ZERO);
+    now = stepper.poll(now).unwrap();
+    log.borrow_mut().now = now;
+    stepper.halt();
+
+    assert!(!log.borrow().enabled);
+    assert!(stepper.poll(now).is_none());
+    assert_eq!(stepper.get_target_state(), TargetState::Reached);
+}
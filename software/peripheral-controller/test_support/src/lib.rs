@@ -0,0 +1,186 @@
+//! Fixtures shared by the tests of the crates built on an axis or a wheel, e.g. `carriage`, `manipulator` and `chassis`.
+
+use std::{cell::{Cell, RefCell}, rc::Rc};
+
+use units::{Length, Velocity, Time};
+use motor::SetSpeed;
+use encoder::{Update, GetPosition};
+use servo::{SetPosition, SetVelocity, Stop, CheckTargetReached, TargetState};
+
+pub const TIME_DELTA: Time = Time::from_seconds(0.1);
+/// Speed every mock axis moves at
+pub const SPEED: Velocity = Velocity::from_cm_per_s(10.0);
+
+/// Targets set on the axes, by the name of the axis
+pub type Log = Rc<RefCell<Vec<(&'static str, Length)>>>;
+
+/// Moves at a constant speed, without any dynamics
+pub struct MockAxis {
+    /// Shared, e.g. with a mock switch that reads the position
+    pub position: Rc<Cell<Length>>,
+    /// The axis stands still and reports a stall
+    pub stuck: bool,
+
+    target: Length,
+    velocity: Option<Velocity>,
+    log: Option<(&'static str, Log)>
+}
+
+impl MockAxis {
+    pub fn new(position: Length) -> Self {
+        Self { position: Rc::new(Cell::new(position)), stuck: false, target: position, velocity: None, log: None }
+    }
+
+    /// Starts at zero and logs its targets under the name
+    pub fn logged(name: &'static str, log: &Log) -> Self {
+        Self { log: Some((name, log.clone())), ..Self::new(Length::ZERO) }
+    }
+}
+
+impl GetPosition for MockAxis {
+    type Position = Length;
+
+    fn get_position(&self) -> Length {
+        self.position.get()
+    }
+}
+
+impl SetPosition for MockAxis {
+    type Position = Length;
+
+    fn set_position(&mut self, position: Length) {
+        if let Some((name, ref log)) = self.log {
+            log.borrow_mut().push((name, position));
+        }
+        self.velocity = None;
+        self.target = position;
+    }
+}
+
+impl SetVelocity for MockAxis {
+    type Velocity = Velocity;
+
+    fn set_velocity(&mut self, velocity: Velocity) {
+        self.velocity = Some(velocity);
+    }
+}
+
+impl Stop for MockAxis {
+    fn stop(&mut self) {
+        self.velocity = None;
+        self.target = self.get_position();
+    }
+
+    fn halt(&mut self) {
+        self.stop();
+    }
+}
+
+impl CheckTargetReached for MockAxis {
+    fn get_target_state(&self) -> TargetState {
+        match self.velocity {
+            Some(_) => TargetState::Moving,
+            None if self.stuck => TargetState::Stalled,
+            None if self.get_position() == self.target => TargetState::Reached,
+            None => TargetState::Moving
+        }
+    }
+}
+
+impl Update for MockAxis {
+    fn update(&mut self, time_delta: Time) {
+        if self.stuck {
+            return;
+        }
+
+        let position = self.get_position();
+        let step = SPEED * time_delta;
+        let next = match self.velocity {
+            Some(velocity) => position + velocity * time_delta,
+            None => position + (self.target - position).clamp(-step, step)
+        };
+        self.position.set(next);
+    }
+}
+
+/// Wheel which follows the commanded velocity exactly.
+/// A position target is only recorded, the position is left to the test otherwise
+#[derive(Clone, Default)]
+pub struct MockWheel {
+    pub position: Rc<Cell<Length>>,
+    pub target: Rc<Cell<Option<Length>>>,
+    pub velocity: Rc<Cell<Option<Velocity>>>,
+    pub max_speed: Rc<Cell<Velocity>>
+}
+
+impl MockWheel {
+    pub fn advance(&self, distance: Length) {
+        self.position.set(self.position.get() + distance);
+    }
+
+    /// Commanded velocity, cm/s
+    pub fn velocity(&self) -> f32 {
+        self.velocity.get().unwrap().to_cm_per_s()
+    }
+}
+
+impl SetSpeed for MockWheel {
+    type Speed = Velocity;
+
+    fn set_speed(&mut self, speed: Self::Speed) {
+        self.max_speed.set(speed);
+    }
+}
+
+impl GetPosition for MockWheel {
+    type Position = Length;
+
+    fn get_position(&self) -> Self::Position {
+        self.position.get()
+    }
+}
+
+impl SetPosition for MockWheel {
+    type Position = Length;
+
+    fn set_position(&mut self, position: Self::Position) {
+        self.velocity.set(None);
+        self.target.set(Some(position));
+    }
+}
+
+impl SetVelocity for MockWheel {
+    type Velocity = Velocity;
+
+    fn set_velocity(&mut self, velocity: Self::Velocity) {
+        self.target.set(None);
+        self.velocity.set(Some(velocity));
+    }
+}
+
+impl Stop for MockWheel {
+    fn stop(&mut self) {
+        self.velocity.set(None);
+    }
+
+    fn halt(&mut self) {
+        self.velocity.set(None);
+    }
+}
+
+impl CheckTargetReached for MockWheel {
+    fn get_target_state(&self) -> TargetState {
+        match self.velocity.get() {
+            Some(velocity) if velocity != Velocity::ZERO => TargetState::Moving,
+            _ => TargetState::Reached
+        }
+    }
+}
+
+impl Update for MockWheel {
+    fn update(&mut self, time_delta: Time) {
+        if let Some(velocity) = self.velocity.get() {
+            self.advance(velocity * time_delta);
+        }
+    }
+}
@@ -0,0 +1,374 @@
+#![no_std]
+
+//! Physical quantities as `f32` newtypes in SI units, so that a length cannot be passed
+//! where an angle or a speed is expected. Values go in and out through the named
+//! conversions only, e.g. `Length::from_cm(12.0).to_mm()`.
+
+use core::{f32::consts::TAU, ops::{Add, Sub, Neg, Mul, Div, AddAssign, SubAssign}};
+
+macro_rules! quantity {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+        pub struct $name(f32);
+
+        impl $name {
+            pub const ZERO: Self = Self(0.0);
+            pub const INFINITY: Self = Self(f32::INFINITY);
+
+            pub fn abs(self) -> Self {
+                Self(self.0.abs())
+            }
+
+            /// `1.0` or `-1.0`, with the sign of the value
+            pub fn signum(self) -> f32 {
+                self.0.signum()
+            }
+
+            pub fn min(self, other: Self) -> Self {
+                Self(self.0.min(other.0))
+            }
+
+            pub fn max(self, other: Self) -> Self {
+                Self(self.0.max(other.0))
+            }
+
+            pub fn clamp(self, min: Self, max: Self) -> Self {
+                self.max(min).min(max)
+            }
+
+            pub fn is_finite(self) -> bool {
+                self.0.is_finite()
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                Self(self.0 + other.0)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                Self(self.0 - other.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: Self) {
+                self.0 += other.0;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                Self(-self.0)
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = Self;
+
+            fn mul(self, factor: f32) -> Self {
+                Self(self.0 * factor)
+            }
+        }
+
+        impl Mul<$name> for f32 {
+            type Output = $name;
+
+            fn mul(self, quantity: $name) -> $name {
+                $name(self * quantity.0)
+            }
+        }
+
+        impl Div<f32> for $name {
+            type Output = Self;
+
+            fn div(self, divisor: f32) -> Self {
+                Self(self.0 / divisor)
+            }
+        }
+
+        /// Ratio of two quantities of the same kind
+        impl Div for $name {
+            type Output = f32;
+
+            fn div(self, divisor: Self) -> f32 {
+                self.0 / divisor.0
+            }
+        }
+    };
+}
+
+/// `$rate * Time = $quantity` and `$quantity / Time = $rate`
+macro_rules! rate {
+    ($quantity:ident, $rate:ident) => {
+        impl Div<Time> for $quantity {
+            type Output = $rate;
+
+            fn div(self, time: Time) -> $rate {
+                $rate(self.0 / time.0)
+            }
+        }
+
+        impl Mul<Time> for $rate {
+            type Output = $quantity;
+
+            fn mul(self, time: Time) -> $quantity {
+                $quantity(self.0 * time.0)
+            }
+        }
+
+        impl Mul<$rate> for Time {
+            type Output = $quantity;
+
+            fn mul(self, rate: $rate) -> $quantity {
+                $quantity(self.0 * rate.0)
+            }
+        }
+
+        impl Div<$rate> for $quantity {
+            type Output = Time;
+
+            fn div(self, rate: $rate) -> Time {
+                Time(self.0 / rate.0)
+            }
+        }
+    };
+}
+
+quantity!(
+    /// Metres
+    Length
+);
+quantity!(
+    /// Radians, growing in the direction the user of the angle defines
+    Angle
+);
+quantity!(
+    /// Seconds
+    Time
+);
+quantity!(
+    /// Metres per second
+    Velocity
+);
+quantity!(
+    /// Radians per second
+    AngularVelocity
+);
+quantity!(
+    /// Metres per second squared
+    Acceleration
+);
+quantity!(
+    /// Radians per second squared
+    AngularAcceleration
+);
+
+rate!(Length, Velocity);
+rate!(Velocity, Acceleration);
+rate!(Angle, AngularVelocity);
+rate!(AngularVelocity, AngularAcceleration);
+
+impl Length {
+    pub const fn from_m(meters: f32) -> Self {
+        Self(meters)
+    }
+
+    pub const fn from_cm(centimeters: f32) -> Self {
+        Self(centimeters / 100.0)
+    }
+
+    pub const fn from_mm(millimeters: f32) -> Self {
+        Self(millimeters / 1000.0)
+    }
+
+    pub fn to_m(self) -> f32 {
+        self.0
+    }
+
+    pub fn to_cm(self) -> f32 {
+        self.0 * 100.0
+    }
+
+    pub fn to_mm(self) -> f32 {
+        self.0 * 1000.0
+    }
+
+    /// Angle a wheel of the given radius turns by while rolling along this length
+    pub fn to_angle(self, radius: Length) -> Angle {
+        Angle(self.0 / radius.0)
+    }
+}
+
+impl Angle {
+    pub const fn from_radians(radians: f32) -> Self {
+        Self(radians)
+    }
+
+    pub const fn from_degrees(degrees: f32) -> Self {
+        Self(degrees.to_radians())
+    }
+
+    pub const fn from_revolutions(revolutions: f32) -> Self {
+        Self(revolutions * TAU)
+    }
+
+    pub fn to_radians(self) -> f32 {
+        self.0
+    }
+
+    pub fn to_degrees(self) -> f32 {
+        self.0.to_degrees()
+    }
+
+    pub fn to_revolutions(self) -> f32 {
+        self.0 / TAU
+    }
+
+    /// Length of the arc of the given radius
+    pub fn to_arc(self, radius: Length) -> Length {
+        Length(self.0 * radius.0)
+    }
+
+    /// Wrapped into the (-180, 180] degrees range
+    pub fn normalized(self) -> Self {
+        let half_turn = TAU / 2.0;
+        let angle = self.0 % TAU;
+
+        Self(if angle > half_turn {
+            angle - TAU
+        } else if angle <= -half_turn {
+            angle + TAU
+        } else {
+            angle
+        })
+    }
+}
+
+impl Time {
+    pub const fn from_seconds(seconds: f32) -> Self {
+        Self(seconds)
+    }
+
+    pub const fn from_millis(milliseconds: f32) -> Self {
+        Self(milliseconds / 1000.0)
+    }
+
+    pub fn to_seconds(self) -> f32 {
+        self.0
+    }
+
+    pub fn to_millis(self) -> f32 {
+        self.0 * 1000.0
+    }
+}
+
+impl Velocity {
+    pub const fn from_m_per_s(meters_per_second: f32) -> Self {
+        Self(meters_per_second)
+    }
+
+    pub const fn from_cm_per_s(centimeters_per_second: f32) -> Self {
+        Self(centimeters_per_second / 100.0)
+    }
+
+    pub fn to_m_per_s(self) -> f32 {
+        self.0
+    }
+
+    pub fn to_cm_per_s(self) -> f32 {
+        self.0 * 100.0
+    }
+
+    /// Angular velocity of a wheel of the given radius rolling at this velocity
+    pub fn to_angular(self, radius: Length) -> AngularVelocity {
+        AngularVelocity(self.0 / radius.0)
+    }
+}
+
+impl AngularVelocity {
+    pub const fn from_radians_per_s(radians_per_second: f32) -> Self {
+        Self(radians_per_second)
+    }
+
+    pub const fn from_degrees_per_s(degrees_per_second: f32) -> Self {
+        Self(degrees_per_second.to_radians())
+    }
+
+    pub const fn from_rps(revolutions_per_second: f32) -> Self {
+        Self(revolutions_per_second * TAU)
+    }
+
+    pub fn to_radians_per_s(self) -> f32 {
+        self.0
+    }
+
+    pub fn to_degrees_per_s(self) -> f32 {
+        self.0.to_degrees()
+    }
+
+    pub fn to_rps(self) -> f32 {
+        self.0 / TAU
+    }
+
+    /// Velocity along a circle of the given radius
+    pub fn to_tangential(self, radius: Length) -> Velocity {
+        Velocity(self.0 * radius.0)
+    }
+}
+
+impl Acceleration {
+    pub const fn from_m_per_s2(meters_per_second_squared: f32) -> Self {
+        Self(meters_per_second_squared)
+    }
+
+    pub const fn from_cm_per_s2(centimeters_per_second_squared: f32) -> Self {
+        Self(centimeters_per_second_squared / 100.0)
+    }
+
+    pub fn to_m_per_s2(self) -> f32 {
+        self.0
+    }
+
+    pub fn to_cm_per_s2(self) -> f32 {
+        self.0 * 100.0
+    }
+}
+
+impl AngularAcceleration {
+    pub const fn from_radians_per_s2(radians_per_second_squared: f32) -> Self {
+        Self(radians_per_second_squared)
+    }
+
+    pub const fn from_degrees_per_s2(degrees_per_second_squared: f32) -> Self {
+        Self(degrees_per_second_squared.to_radians())
+    }
+
+    pub fn to_radians_per_s2(self) -> f32 {
+        self.0
+    }
+
+    pub fn to_degrees_per_s2(self) -> f32 {
+        self.0.to_degrees()
+    }
+
+    /// Tangential acceleration along a circle of the given radius
+    pub fn to_tangential(self, radius: Length) -> Acceleration {
+        Acceleration(self.0 * radius.0)
+    }
+}
@@ -0,0 +1,78 @@
+use core::f32::consts::PI;
+
+use units::{Length, Angle, Time, Velocity, AngularVelocity, Acceleration, AngularAcceleration};
+
+const EPSILON: f32 = 1e-5;
+
+fn assert_close(value: f32, expected: f32) {
+    assert!((value - expected).abs() <= EPSILON * expected.abs().max(1.0), "{} != {}", value, expected);
+}
+
+#[test]
+fn lengths() {
+    let length = Length::from_mm(37.0);
+    assert_close(length.to_cm(), 3.7);
+    assert_close(length.to_m(), 0.037);
+    assert_close(Length::from_cm(17.0).to_mm(), 170.0);
+
+    assert_close((Length::from_cm(10.0) + Length::from_mm(5.0)).to_cm(), 10.5);
+    assert_close(Length::from_cm(30.0) / Length::from_cm(20.0), 1.5);
+}
+
+#[test]
+fn angles() {
+    assert_close(Angle::from_degrees(180.0).to_radians(), PI);
+    assert_close(Angle::from_revolutions(0.25).to_degrees(), 90.0);
+    assert_close(Angle::from_radians(PI).to_revolutions(), 0.5);
+
+    assert_close(Angle::from_degrees(270.0).normalized().to_degrees(), -90.0);
+    assert_close(Angle::from_degrees(-450.0).normalized().to_degrees(), -90.0);
+    assert_close(Angle::from_degrees(-180.0).normalized().to_degrees(), 180.0);
+    assert_close(Angle::from_degrees(45.0).normalized().to_degrees(), 45.0);
+}
+
+#[test]
+fn rolling() {
+    let radius = Length::from_mm(37.0);
+
+    // one turn rolls along the circumference
+    assert_close(Angle::from_revolutions(1.0).to_arc(radius).to_cm(), 2.0 * PI * 3.7);
+    assert_close(Length::from_cm(2.0 * PI * 3.7).to_angle(radius).to_revolutions(), 1.0);
+
+    let velocity = AngularVelocity::from_rps(1.4).to_tangential(radius);
+    assert_close(velocity.to_cm_per_s(), 1.4 * 2.0 * PI * 3.7);
+    assert_close(velocity.to_angular(radius).to_rps(), 1.4);
+
+    assert_close(AngularAcceleration::from_radians_per_s2(2.0).to_tangential(Length::from_cm(10.0)).to_cm_per_s2(), 20.0);
+}
+
+#[test]
+fn rates() {
+    let time = Time::from_millis(25.0);
+    assert_close(time.to_seconds(), 0.025);
+
+    let velocity = Velocity::from_cm_per_s(40.0);
+    assert_close((velocity * time).to_cm(), 1.0);
+    assert_close((time * velocity).to_cm(), 1.0);
+    assert_close((Length::from_cm(1.0) / time).to_cm_per_s(), 40.0);
+    assert_close((Length::from_cm(1.0) / velocity).to_millis(), 25.0);
+
+    assert_close((Acceleration::from_cm_per_s2(30.0) * Time::from_seconds(2.0)).to_cm_per_s(), 60.0);
+    assert_close((AngularVelocity::from_degrees_per_s(90.0) * Time::from_seconds(2.0)).to_degrees(), 180.0);
+    assert_close((AngularVelocity::from_degrees_per_s(90.0) / Time::from_seconds(0.5)).to_degrees_per_s2(), 180.0);
+}
+
+#[test]
+fn arithmetic() {
+    let velocity = Velocity::from_cm_per_s(-20.0);
+
+    assert_close(velocity.abs().to_cm_per_s(), 20.0);
+    assert_eq!(velocity.signum(), -1.0);
+    assert_close((-velocity).to_cm_per_s(), 20.0);
+    assert_close((velocity * 0.5).to_cm_per_s(), -10.0);
+    assert_close((2.0 * velocity).to_cm_per_s(), -40.0);
+
+    let limit = Velocity::from_cm_per_s(15.0);
+    assert_close(velocity.clamp(-limit, limit).to_cm_per_s(), -15.0);
+    assert!(Velocity::ZERO < limit && limit < Velocity::INFINITY);
+}
@@ -0,0 +1,135 @@
+//! Duty a wheel needs for a speed, added to the feedback so that the PID only corrects the model
+
+use units::{Time, Velocity, Acceleration};
+
+/// Gains in duty percent
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Feedforward {
+    /// Duty to overcome the static friction, applied in the direction of the target
+    pub ks: f32,
+    /// Duty per cm/s
+    pub kv: f32,
+    /// Duty per cm/s^2
+    pub ka: f32
+}
+
+impl Feedforward {
+    pub fn new(ks: f32, kv: f32, ka: f32) -> Self {
+        Self { ks, kv, ka }
+    }
+
+    pub fn calculate(&self, velocity: Velocity, acceleration: Acceleration) -> f32 {
+        let direction = if velocity == Velocity::ZERO { 0.0 } else { velocity.signum() };
+
+        self.ks * direction + self.kv * velocity.to_cm_per_s() + self.ka * acceleration.to_cm_per_s2()
+    }
+}
+
+/// Open loop calibration: steps the duty from a small value up to `max_duty`, measures the
+/// settled speed at each step and fits `ks` and `kv` to them. The caller applies `get_duty`
+/// and feeds the measured speed to `update` every period, e.g. on the host against the
+/// simulator, or on the robot lifted off the floor
+pub struct DutySweep<const N: usize> {
+    /// Time for the speed to settle after a step
+    pub settle_time: Time,
+    /// Time the speed is averaged over after settling
+    pub measure_time: Time,
+    /// Speed below which the wheel is considered standing, such steps are left out of the fit
+    pub min_speed: Velocity,
+
+    max_duty: f32,
+    step: usize,
+    elapsed: Time,
+    speed_sum: Velocity,
+    samples: usize,
+
+    results: [(f32, Velocity); N]
+}
+
+impl<const N: usize> DutySweep<N> {
+    pub fn new(max_duty: f32, settle_time: Time, measure_time: Time, min_speed: Velocity) -> Self {
+        assert!(N > 1);
+
+        Self {
+            settle_time,
+            measure_time,
+            min_speed,
+
+            max_duty,
+            step: 0,
+            elapsed: Time::ZERO,
+            speed_sum: Velocity::ZERO,
+            samples: 0,
+
+            results: [(0.0, Velocity::ZERO); N]
+        }
+    }
+
+    /// Duty to apply, `None` once the sweep is done
+    pub fn get_duty(&self) -> Option<f32> {
+        match self.is_done() {
+            true => None,
+            false => Some(self.max_duty * (self.step + 1) as f32 / N as f32)
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.step >= N
+    }
+
+    /// Duty and settled speed of every step done so far
+    pub fn get_results(&self) -> &[(f32, Velocity)] {
+        &self.results[..self.step.min(N)]
+    }
+
+    pub fn update(&mut self, measured_speed: Velocity, time_delta: Time) {
+        let duty = match self.get_duty() {
+            Some(duty) => duty,
+            None => return
+        };
+
+        self.elapsed += time_delta;
+        if self.elapsed <= self.settle_time {
+            return;
+        }
+
+        self.speed_sum += measured_speed;
+        self.samples += 1;
+
+        if self.elapsed >= self.settle_time + self.measure_time {
+            self.results[self.step] = (duty, self.speed_sum / self.samples as f32);
+
+            self.step += 1;
+            self.elapsed = Time::ZERO;
+            self.speed_sum = Velocity::ZERO;
+            self.samples = 0;
+        }
+    }
+
+    /// Least squares fit of `duty = ks + kv * speed` over the steps the wheel was moving at,
+    /// `None` until there are two of them
+    pub fn fit(&self) -> Option<Feedforward> {
+        let moving = || self.get_results().iter()
+            .filter(|(_, speed)| speed.abs() >= self.min_speed)
+            .map(|&(duty, speed)| (duty, speed.to_cm_per_s()));
+
+        let count = moving().count() as f32;
+        if count < 2.0 {
+            return None;
+        }
+
+        let (duty_sum, speed_sum) = moving().fold((0.0, 0.0), |(duties, speeds), (duty, speed)| (duties + duty.abs(), speeds + speed.abs()));
+        let (duty_mean, speed_mean) = (duty_sum / count, speed_sum / count);
+
+        let (covariance, variance) = moving().fold((0.0, 0.0), |(covariance, variance), (duty, speed)| {
+            let speed_deviation = speed.abs() - speed_mean;
+            (covariance + speed_deviation * (duty.abs() - duty_mean), variance + speed_deviation * speed_deviation)
+        });
+        if variance <= 0.0 {
+            return None;
+        }
+
+        let kv = covariance / variance;
+        Some(Feedforward::new(duty_mean - kv * speed_mean, kv, 0.0))
+    }
+}
@@ -1,60 +1,120 @@
 #![no_std]
 
+pub mod feedforward;
+
 use core::{ops::Add, fmt::Display};
 
 use num_traits::{NumCast, Signed, ToPrimitive, bounds::Bounded};
 
 use pid::Pid;
 
+use units::{Time, Length, Velocity, AngularVelocity};
 use motor::{SetSpeed, GetSpeed};
 use encoder::{Encoder, Update, GetPosition};
 
+use crate::feedforward::Feedforward;
+
 pub struct Wheel<S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
     speed: S,
     encoder: E,
 
     pid: Pid<f32>,
+    enabled: bool,
+    // open loop duty, which replaces the PID until the next `set_speed`
+    duty: Option<f32>,
 
-    pub max_speed: f32,
-    pub radius: f32
+    pub feedforward: Feedforward,
+    // feedforward part of the current duty, and the target it was calculated for
+    last_feedforward: f32,
+    last_target_speed: Velocity,
+
+    pub max_speed: Velocity,
+    pub radius: Length
 }
 
 impl<S, E> Wheel<S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
-    pub fn new(speed_controller: S, encoder: E, pid: Pid<f32>, max_speed_rps: f32, radius_cm: f32) -> Self {
-        let max_speed_cm = max_speed_rps * radius_cm;
-
+    /// The PID works in percent of the maximum speed, which the wheel reaches at full duty
+    pub fn new(speed_controller: S, encoder: E, pid: Pid<f32>, max_speed: AngularVelocity, radius: Length) -> Self {
         Self {
             speed: speed_controller,
             encoder,
 
             pid,
+            enabled: true,
+            duty: None,
 
-            max_speed: max_speed_cm,
-            radius: radius_cm
+            feedforward: Feedforward::default(),
+            last_feedforward: 0.0,
+            last_target_speed: Velocity::ZERO,
+
+            max_speed: max_speed.to_tangential(radius),
+            radius
         }
     }
 
-    fn velocity_to_percent(&self, vel: f32) -> f32 {
-        vel / self.max_speed * 100.0
+    /// Adds the duty of the model of the wheel to the PID output, which then corrects
+    /// only what the model gets wrong
+    pub fn with_feedforward(mut self, feedforward: Feedforward) -> Self {
+        self.feedforward = feedforward;
+        self
     }
 
-    fn to_cm(&self, val: f32) -> f32 {
-        val * self.radius
+    fn velocity_to_percent(&self, velocity: Velocity) -> f32 {
+        velocity / self.max_speed * 100.0
     }
 
-    pub fn get_target_speed(&self) -> f32 {
+    pub fn get_target_speed(&self) -> Velocity {
         self.max_speed * (self.pid.setpoint / 100.0)
     }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Drives the motor with a fixed duty in percent, bypassing the PID, e.g. for a calibration.
+    /// The speed control takes over again with the next `set_speed`
+    pub fn set_duty(&mut self, duty: f32) {
+        self.duty = Some(duty);
+    }
+
+    /// Replaces the gains of the speed PID, the state of the loop is kept
+    pub fn set_pid_gains(&mut self, kp: f32, ki: f32, kd: f32) {
+        self.pid.kp = kp;
+        self.pid.ki = ki;
+        self.pid.kd = kd;
+    }
+
+    /// Resumes the speed control after `disable`, starting from standstill
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+}
+
+impl<S, E> Wheel<S, E>
+where
+    S: SetSpeed + GetSpeed,
+    <S as SetSpeed>::Speed: NumCast,
+    E: Encoder
+{
+    /// Cuts the motor output at once, the encoder is still read
+    pub fn disable(&mut self) {
+        self.enabled = false;
+        self.duty = None;
+
+        self.pid.setpoint = 0.0;
+        self.pid.reset_integral_term();
+        self.last_feedforward = 0.0;
+        self.last_target_speed = Velocity::ZERO;
+        self.speed.set_speed(NumCast::from(0.0).unwrap());
+    }
 }
 
 // TODO: it is kinda incorrect to use encoder's Update here, but it'll do for now
@@ -65,23 +125,40 @@ where
     <S as SetSpeed>::Speed: NumCast + Bounded,
     <S as GetSpeed>::Speed: NumCast + Add + Copy,
     <<S as GetSpeed>::Speed as Add>::Output: ToPrimitive + Display,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
-    fn update(&mut self, time_delta_seconds: f32) {
+    fn update(&mut self, time_delta: Time) {
         let min_speed_val: f32 = NumCast::from(<S as SetSpeed>::Speed::min_value()).unwrap();
         let max_speed_val: f32 = NumCast::from(<S as SetSpeed>::Speed::max_value()).unwrap();
         let min_speed_val = if min_speed_val.abs() > max_speed_val { -max_speed_val } else { min_speed_val };
 
-        self.encoder.update(time_delta_seconds);
+        self.encoder.update(time_delta);
 
-        let velocity = self.to_cm(self.encoder.get_velocity());
-        let velocity = self.velocity_to_percent(velocity);
+        if !self.enabled {
+            self.speed.set_speed(NumCast::from(0.0).unwrap());
+            return;
+        }
 
+        if let Some(duty) = self.duty {
+            self.speed.set_speed(NumCast::from(duty.max(min_speed_val).min(max_speed_val)).unwrap());
+            return;
+        }
+
+        let speed = self.get_speed();
+        let velocity = self.velocity_to_percent(speed);
+
+        let target_speed = self.get_target_speed();
+        let target_acceleration = (target_speed - self.last_target_speed) / time_delta;
+        let feedforward = self.feedforward.calculate(target_speed, target_acceleration);
+        self.last_target_speed = target_speed;
+
+        // the PID output is incremental, it accumulates in the duty on top of the feedforward
         let control = self.pid.next_control_output(velocity).output;
         let current_speed: f32 = NumCast::from(self.speed.get_speed()).unwrap();
-        let new_speed = current_speed + control;
+        let feedback = current_speed - self.last_feedforward + control;
+        let new_speed = feedback + feedforward;
         let new_speed = new_speed.max(min_speed_val).min(max_speed_val);
+        self.last_feedforward = feedforward;
 
         self.speed.set_speed(NumCast::from(new_speed).unwrap());
     }
@@ -90,12 +167,15 @@ where
 impl<S, E> SetSpeed for Wheel<S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
-    type Speed = f32;
+    type Speed = Velocity;
 
     fn set_speed(&mut self, speed: Self::Speed) {
+        if self.duty.take().is_some() {
+            // the incremental PID starts from the open loop duty, without the feedforward
+            self.last_feedforward = 0.0;
+        }
         self.pid.setpoint = self.velocity_to_percent(speed);
     }
 }
@@ -103,24 +183,25 @@ where
 impl<S, E> GetSpeed for Wheel<S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
-    type Speed = f32;
+    type Speed = Velocity;
 
     fn get_speed(&mut self) -> Self::Speed {
-        self.to_cm(self.encoder.get_velocity())
+        self.encoder.get_velocity().to_tangential(self.radius)
     }
 }
 
 impl<S, E> GetPosition for Wheel<S, E>
 where
     S: SetSpeed + GetSpeed,
-    E: Encoder,
-    f32: From<<E as GetPosition>::Position>
+    E: Encoder
 {
+    /// Distance rolled
+    type Position = Length;
+
     fn get_position(&self) -> Self::Position {
-        self.to_cm(self.encoder.get_position().into())
+        self.encoder.get_position().to_arc(self.radius)
     }
 }
 